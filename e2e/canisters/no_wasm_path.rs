@@ -0,0 +1,20 @@
+use ic_kit::prelude::*;
+use ic_kit::{InstallConfig, InstallMode};
+
+#[query]
+fn name() -> String {
+    "no-wasm-path".to_string()
+}
+
+/// Try to install this canister's own code, which fails since the wasm_path is not set.
+#[update]
+async fn install_self() -> Result<(), String> {
+    NoWasmPathCanister::install_code(id(), InstallConfig::new(InstallMode::Reinstall))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[derive(KitCanister)]
+pub struct NoWasmPathCanister;
+
+fn main() {}
@@ -1,4 +1,4 @@
-use ic_kit::macros::{post_upgrade, pre_upgrade, query, update};
+use ic_kit::macros::{export_candid, post_upgrade, pre_upgrade, query, update};
 use serde_bytes::ByteBuf;
 use std::collections::BTreeMap;
 
@@ -25,4 +25,6 @@ fn post_upgrade() {
     ic_kit::ic::swap(persisted_store);
 }
 
+export_candid!();
+
 fn main() {}
@@ -84,6 +84,19 @@ fn test_storage_roundtrip() {
     assert_eq!(result, Some(ByteBuf::from(b"did".to_vec())));
 }
 
+#[test]
+fn test_export_candid() {
+    let env = StateMachine::new();
+    let kv_store_wasm = cargo_build_canister("simple-kv-store");
+    let canister_id = env.install_canister(kv_store_wasm, vec![], None).unwrap();
+
+    let (candid,): (String,) =
+        query_candid(&env, canister_id, "__get_candid_interface_tmp_hack", ())
+            .expect("failed to get the candid");
+    assert!(candid.contains("insert : (text, "));
+    assert!(candid.contains("lookup : (text) -> (opt "));
+}
+
 #[test]
 fn test_panic_after_async_frees_resources() {
     let env = StateMachine::new();
@@ -117,6 +130,25 @@ fn test_panic_after_async_frees_resources() {
     }
 }
 
+/// Checks that a `KitCanister` without a `wasm_path` builds for the IC, and that its
+/// `install_code` returns an error there.
+#[test]
+fn test_install_code_without_wasm_path() {
+    let env = StateMachine::new();
+    let wasm = cargo_build_canister("no-wasm-path");
+    let canister_id = env.install_canister(wasm, vec![], None).unwrap();
+
+    let (name,): (String,) =
+        query_candid(&env, canister_id, "name", ()).expect("failed to call name");
+    assert_eq!(name, "no-wasm-path");
+
+    let (result,): (Result<(), String>,) =
+        call_candid(&env, canister_id, "install_self", ()).expect("failed to call install_self");
+    assert!(result
+        .unwrap_err()
+        .contains("The wasm_path of NoWasmPathCanister is not set."));
+}
+
 #[test]
 fn test_raw_api() {
     let env = StateMachine::new();
@@ -1,4 +1,5 @@
 use ic_kit::prelude::*;
+use std::time::Duration;
 
 #[derive(Default)]
 pub struct Counter {
@@ -30,11 +31,38 @@ pub fn increment_by(counter: &mut Counter, n: u8) -> u64 {
     counter.increment_by(n)
 }
 
+/// Reply with the new value of the counter and then increment it once more, the candid type
+/// of a manual reply method can not be inferred, so it is hidden.
+#[update(manual_reply = true, hidden = true)]
+pub fn increment_twice(counter: &mut Counter) {
+    ic::reply(counter.increment());
+    counter.increment();
+}
+
+#[update]
+pub fn increment_after(seconds: u64) {
+    ic::set_timer(Duration::from_secs(seconds), || {
+        ic::with_mut(Counter::increment);
+    });
+}
+
 #[query]
 pub fn get_counter(counter: &Counter) -> u64 {
     counter.number
 }
 
+#[pre_upgrade]
+pub fn pre_upgrade() {
+    let number = ic::with(|counter: &Counter| counter.number);
+    ic::stable_save((number,)).expect("Failed to save the counter.");
+}
+
+#[post_upgrade]
+pub fn post_upgrade() {
+    let (number,): (u64,) = ic::stable_restore().expect("Failed to restore the counter.");
+    ic::with_mut(|counter: &mut Counter| counter.number = number);
+}
+
 #[derive(KitCanister)]
 #[candid_path("candid.did")]
 pub struct CounterCanister;
@@ -82,6 +110,22 @@ mod tests {
                 .unwrap(),
             2
         );
+
+        replica.assert_no_pending().await;
+    }
+
+    #[kit_test]
+    async fn test_increment_with_timeout(replica: Replica) {
+        let c = replica.add_canister(CounterCanister::anonymous());
+
+        let r = c
+            .new_call("increment")
+            .perform_with_timeout(Duration::from_secs(5))
+            .await
+            .decode_one::<u64>()
+            .unwrap();
+
+        assert_eq!(r, 1);
     }
 
     #[kit_test]
@@ -97,4 +141,90 @@ mod tests {
             2
         );
     }
+
+    #[kit_test]
+    async fn test_increment_twice(replica: Replica) {
+        let c = replica.add_canister(CounterCanister::anonymous());
+
+        let r = c
+            .new_call("increment_twice")
+            .perform()
+            .await
+            .decode_one::<u64>()
+            .unwrap();
+
+        assert_eq!(r, 1);
+
+        assert_eq!(
+            c.new_call("get_counter")
+                .perform()
+                .await
+                .decode_one::<u64>()
+                .unwrap(),
+            2
+        );
+    }
+
+    #[kit_test]
+    async fn test_increment_after(replica: Replica) {
+        let c = replica.add_canister(CounterCanister::anonymous());
+
+        c.new_call("increment_after")
+            .with_arg(10u64)
+            .perform()
+            .await
+            .assert_ok();
+
+        replica.advance_time(Duration::from_secs(5));
+        assert_eq!(
+            c.new_call("get_counter")
+                .perform()
+                .await
+                .decode_one::<u64>()
+                .unwrap(),
+            0
+        );
+
+        replica.advance_time(Duration::from_secs(5));
+        assert_eq!(
+            c.new_call("get_counter")
+                .perform()
+                .await
+                .decode_one::<u64>()
+                .unwrap(),
+            1
+        );
+    }
+
+    #[kit_test(time = 1_700_000_000_000_000_000, cycles = 1_000_000_000_000)]
+    async fn test_increment_after_with_fixed_time(replica: Replica) {
+        let c = replica.add_canister(CounterCanister::anonymous());
+
+        c.new_call("increment_after")
+            .with_arg(10u64)
+            .perform()
+            .await
+            .assert_ok();
+
+        replica.advance_time(Duration::from_secs(10));
+        assert_eq!(
+            c.new_call("get_counter")
+                .perform()
+                .await
+                .decode_one::<u64>()
+                .unwrap(),
+            1
+        );
+
+        c.custom(
+            || {
+                assert_eq!(ic::time(), 1_700_000_010_000_000_000);
+                assert_eq!(ic::balance(), 1_000_000_000_000);
+                ic::reply(());
+            },
+            rt::types::Env::default(),
+        )
+        .await
+        .assert_ok();
+    }
 }
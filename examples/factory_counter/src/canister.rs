@@ -1,14 +1,110 @@
 use ic_kit::prelude::*;
+use ic_kit::{InstallConfig, InstallMode};
 use ic_kit_example_counter::CounterCanister;
+use ic_kit_management::{perform, CreateCanister, CreateCanisterArgument};
 
 #[update]
 async fn deploy_counter() -> Principal {
-    // TODO(qti3e) we should make it possible to deploy an instance of CounterCanister.
-    // It should work in the IC and the ic-kit-runtime environment.
-    // CounterCanister::install_code(CANISTER_ID);
-    todo!()
+    let (record,) = perform::<CreateCanister>((CreateCanisterArgument { settings: None },))
+        .await
+        .expect("Failed to create the canister.");
+
+    CounterCanister::install_code(
+        record.canister_id,
+        InstallConfig::new(InstallMode::Install),
+    )
+    .await
+    .expect("Failed to install the counter canister.");
+
+    record.canister_id
+}
+
+#[update]
+async fn upgrade_counter(canister_id: Principal) {
+    CounterCanister::install_code(canister_id, InstallConfig::new(InstallMode::Upgrade))
+        .await
+        .expect("Failed to upgrade the counter canister.");
 }
 
 #[derive(KitCanister)]
 #[candid_path("candid.did")]
 pub struct FactoryCounterCanister;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[kit_test]
+    async fn install_code(replica: Replica) {
+        let canister_id = replica.create_canister().await;
+        assert_ne!(replica.create_canister().await, canister_id);
+
+        let counter = replica.install_code(CounterCanister::build(canister_id));
+
+        let r = counter
+            .new_call("increment")
+            .perform()
+            .await
+            .decode_one::<u64>()
+            .unwrap();
+
+        assert_eq!(r, 1);
+    }
+
+    #[kit_test]
+    async fn deploy_counter(replica: Replica) {
+        let factory = replica.add_canister(FactoryCounterCanister::anonymous());
+
+        let counter_id = factory
+            .new_call("deploy_counter")
+            .perform()
+            .await
+            .decode_one::<Principal>()
+            .unwrap();
+
+        let r = replica
+            .new_call(counter_id, "increment")
+            .perform()
+            .await
+            .decode_one::<u64>()
+            .unwrap();
+
+        assert_eq!(r, 1);
+    }
+
+    #[kit_test]
+    async fn upgrade_counter(replica: Replica) {
+        let factory = replica.add_canister(FactoryCounterCanister::anonymous());
+
+        let counter_id = factory
+            .new_call("deploy_counter")
+            .perform()
+            .await
+            .decode_one::<Principal>()
+            .unwrap();
+
+        replica
+            .new_call(counter_id, "increment_by")
+            .with_arg(5u8)
+            .perform()
+            .await
+            .assert_ok();
+
+        factory
+            .new_call("upgrade_counter")
+            .with_arg(counter_id)
+            .perform()
+            .await
+            .assert_ok();
+
+        // The counter is saved to the stable memory before the upgrade and restored after.
+        let r = replica
+            .new_call(counter_id, "get_counter")
+            .perform()
+            .await
+            .decode_one::<u64>()
+            .unwrap();
+
+        assert_eq!(r, 5);
+    }
+}
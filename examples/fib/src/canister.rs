@@ -11,19 +11,18 @@ async fn fib(n: u64) -> u64 {
         return n;
     }
 
-    let a = CallBuilder::new(id(), "fib")
-        .with_arg(n - 1)
-        .perform_one::<u64>()
-        .await
-        .unwrap();
-
-    let b = CallBuilder::new(id(), "fib")
-        .with_arg(n - 2)
-        .perform_one::<u64>()
-        .await
-        .unwrap();
-
-    a + b
+    // Perform both of the calls in parallel.
+    let (a, b) = ic::join(
+        CallBuilder::new(id(), "fib")
+            .with_arg(n - 1)
+            .perform_one::<u64>(),
+        CallBuilder::new(id(), "fib")
+            .with_arg(n - 2)
+            .perform_one::<u64>(),
+    )
+    .await;
+
+    a.unwrap() + b.unwrap()
 }
 
 #[derive(KitCanister)]
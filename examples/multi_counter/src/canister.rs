@@ -25,6 +25,22 @@ fn add_counter(counters: &mut MultiCounter, canister_id: Principal) {
     counters.canister_ids.insert(canister_id);
 }
 
+/// Return the sum of all of the counters.
+#[composite_query]
+async fn sum() -> u64 {
+    let canister_ids = ic::with(|counters: &MultiCounter| counters.canister_ids.clone());
+    let mut sum = 0;
+
+    for canister_id in canister_ids {
+        let (value,): (u64,) = ic::call(canister_id, "get_counter", ())
+            .await
+            .expect("Expected the counter to return its value.");
+        sum += value;
+    }
+
+    sum
+}
+
 #[derive(KitCanister)]
 #[candid_path("candid.did")]
 pub struct MultiCounterCanister;
@@ -77,11 +93,31 @@ mod tests {
             .perform()
             .await;
 
-        // Do a proxy increment call.
-        let x = canister.new_call("increment").perform().await;
+        let sum = canister
+            .new_call("sum")
+            .as_composite_query()
+            .perform()
+            .await
+            .decode_one::<u64>()
+            .unwrap();
+
+        assert_eq!(sum, 2);
+
+        // Do a proxy increment call, the one-way calls to the counters are still in flight
+        // when the reply arrives.
+        canister.new_call("increment").perform().await.assert_ok();
+        replica.run_until_idle().await;
 
-        // TODO(qti3e) replica.idle.await
+        for counter in [&counter1, &counter2] {
+            let r = counter
+                .new_call("get_counter")
+                .as_query()
+                .perform()
+                .await
+                .decode_one::<u64>()
+                .unwrap();
 
-        println!("{:#?}", x);
+            assert_eq!(r, 2);
+        }
     }
 }
@@ -11,7 +11,26 @@ struct Stats {
     called_register: u64,
 }
 
-#[update]
+mod guards {
+    use ic_kit::prelude::*;
+
+    pub fn not_anonymous() -> Result<(), String> {
+        if caller() == Principal::anonymous() {
+            return Err("Anonymous principal can not register a name.".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Register the name of the owner of the registry during the install, for example using
+/// `dfx deploy --argument '(principal "...", "Owner")'`.
+#[init]
+fn init(registry: &mut Registry, owner: Principal, name: String) {
+    registry.names.insert(owner, name);
+}
+
+#[update(guard = "guards::not_anonymous")]
 fn register(registry: &mut Registry, stats: &mut Stats, name: String) {
     stats.called_register += 1;
     registry.names.insert(caller(), name);
@@ -22,6 +41,11 @@ fn get_name(registry: &Registry, user: Principal) -> Option<&String> {
     registry.names.get(&user)
 }
 
+#[query(name = "whoami")]
+fn get_own_name(registry: &Registry) -> Option<&String> {
+    registry.names.get(&caller())
+}
+
 #[derive(KitCanister)]
 #[candid_path("candid.did")]
 pub struct NamingSystemCanister;
@@ -30,6 +54,34 @@ pub struct NamingSystemCanister;
 mod tests {
     use super::*;
 
+    #[kit_test]
+    async fn init_args(replica: Replica) {
+        let ns = replica.add_canister(NamingSystemCanister::anonymous());
+
+        ns.init_with_args((*users::ALICE, "Alice".to_string()))
+            .await
+            .assert_ok();
+
+        let name = ns
+            .new_call("get_name")
+            .with_arg(*users::ALICE)
+            .perform()
+            .await
+            .decode_one::<Option<String>>()
+            .unwrap();
+
+        assert_eq!(name, Some("Alice".to_string()));
+
+        // The install fails if the arguments are missing.
+        let canister_id = Principal::from_text("whq4n-xiaaa-aaaam-qaazq-cai").unwrap();
+        let ns = replica.add_canister(NamingSystemCanister::build(canister_id));
+        let reply = ns.init().await;
+        assert!(reply
+            .rejection_message()
+            .unwrap()
+            .contains("Could not decode arguments"));
+    }
+
     #[kit_test]
     async fn test(replica: Replica) {
         let ns = replica.add_canister(NamingSystemCanister::anonymous());
@@ -48,24 +100,35 @@ mod tests {
             .await
             .assert_ok();
 
-        let alice_name = ns
-            .new_call("get_name")
-            .with_arg(*users::ALICE)
-            .perform()
-            .await
-            .decode_one::<Option<String>>()
-            .unwrap();
+        assert_reply!(
+            ns.new_call("get_name").with_arg(*users::ALICE),
+            Some("Alice".to_string())
+        );
 
-        assert_eq!(alice_name, Some("Alice".to_string()));
+        assert_reply!(
+            ns.new_call("get_name").with_arg(*users::BOB),
+            Some("Bob".to_string())
+        );
 
-        let bob_name = ns
-            .new_call("get_name")
-            .with_arg(*users::BOB)
-            .perform()
-            .await
-            .decode_one::<Option<String>>()
-            .unwrap();
+        assert_reply!(
+            ns.new_call("whoami").with_caller(*users::ALICE),
+            Some("Alice".to_string())
+        );
+
+        ns.new_call("get_own_name").perform().await.assert_error();
+    }
+
+    #[kit_test]
+    async fn guard(replica: Replica) {
+        let ns = replica.add_canister(NamingSystemCanister::anonymous());
 
-        assert_eq!(bob_name, Some("Bob".to_string()));
+        // The guard should run before the arguments are decoded.
+        assert_reject!(
+            ns.new_call("register")
+                .with_caller(Principal::anonymous())
+                .with_arg_raw(vec![0, 1, 2]),
+            ic::RejectionCode::CanisterReject,
+            "Anonymous principal can not register a name."
+        );
     }
 }
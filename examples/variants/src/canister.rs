@@ -0,0 +1,174 @@
+use ic_kit::prelude::*;
+use std::collections::HashMap;
+
+#[derive(Default)]
+struct Balances {
+    accounts: HashMap<Principal, u64>,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub enum AccountError {
+    Anonymous,
+    Blocked { reason: String },
+}
+
+/// An error with data variants, one of which is another enum.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub enum TransferError {
+    InsufficientFunds { balance: u64 },
+    InvalidAccount(AccountError),
+    TemporarilyUnavailable,
+}
+
+/// A recursive type.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct Tree {
+    pub value: u64,
+    pub children: Vec<Tree>,
+}
+
+#[update]
+fn deposit(balances: &mut Balances, amount: u64) -> Result<u64, AccountError> {
+    if caller() == Principal::anonymous() {
+        return Err(AccountError::Anonymous);
+    }
+
+    let balance = balances.accounts.entry(caller()).or_default();
+    *balance += amount;
+    Ok(*balance)
+}
+
+#[update]
+fn transfer(balances: &mut Balances, to: Principal, amount: u64) -> Result<u64, TransferError> {
+    if to == Principal::anonymous() {
+        return Err(TransferError::InvalidAccount(AccountError::Blocked {
+            reason: "Can not transfer to the anonymous principal.".to_string(),
+        }));
+    }
+
+    let balance = balances.accounts.entry(caller()).or_default();
+
+    if *balance < amount {
+        return Err(TransferError::InsufficientFunds { balance: *balance });
+    }
+
+    *balance -= amount;
+    let remaining = *balance;
+    *balances.accounts.entry(to).or_default() += amount;
+
+    Ok(remaining)
+}
+
+#[query]
+fn tree(depth: u8) -> Tree {
+    fn build(value: u64, depth: u8) -> Tree {
+        let children = if depth == 0 {
+            Vec::new()
+        } else {
+            vec![build(value * 2, depth - 1), build(value * 2 + 1, depth - 1)]
+        };
+
+        Tree { value, children }
+    }
+
+    build(1, depth)
+}
+
+#[derive(KitCanister)]
+#[candid_path("candid.did")]
+pub struct VariantsCanister;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_kit::candid::types::Type;
+    use ic_kit::candid::{check_prog, IDLProg, TypeEnv};
+
+    #[test]
+    fn candid_type_checks() {
+        let candid = VariantsCanister::candid();
+        let prog = candid.parse::<IDLProg>().unwrap();
+        let mut env = TypeEnv::new();
+        check_prog(&mut env, &prog).unwrap();
+
+        match env.find_type("TransferError").unwrap() {
+            Type::Variant(fields) => assert_eq!(fields.len(), 3),
+            t => panic!("TransferError is not a variant: {}", t),
+        }
+
+        match env.find_type("AccountError").unwrap() {
+            Type::Variant(fields) => assert_eq!(fields.len(), 2),
+            t => panic!("AccountError is not a variant: {}", t),
+        }
+
+        match env.find_type("Tree").unwrap() {
+            Type::Record(fields) => assert_eq!(fields.len(), 2),
+            t => panic!("Tree is not a record: {}", t),
+        }
+    }
+
+    #[kit_test]
+    async fn transfer_errors(replica: Replica) {
+        let canister = replica.add_canister(VariantsCanister::anonymous());
+
+        let reply = canister
+            .new_call("deposit")
+            .with_arg(100u64)
+            .perform()
+            .await
+            .decode_one::<Result<u64, AccountError>>()
+            .unwrap();
+
+        assert_eq!(reply, Err(AccountError::Anonymous));
+
+        let reply = canister
+            .new_call("deposit")
+            .with_caller(*users::ALICE)
+            .with_arg(100u64)
+            .perform()
+            .await
+            .decode_one::<Result<u64, AccountError>>()
+            .unwrap();
+
+        assert_eq!(reply, Ok(100));
+
+        let reply = canister
+            .new_call("transfer")
+            .with_caller(*users::ALICE)
+            .with_args((*users::BOB, 150u64))
+            .perform()
+            .await
+            .decode_one::<Result<u64, TransferError>>()
+            .unwrap();
+
+        assert_eq!(
+            reply,
+            Err(TransferError::InsufficientFunds { balance: 100 })
+        );
+
+        let reply = canister
+            .new_call("transfer")
+            .with_caller(*users::ALICE)
+            .with_args((Principal::anonymous(), 50u64))
+            .perform()
+            .await
+            .decode_one::<Result<u64, TransferError>>()
+            .unwrap();
+
+        assert!(matches!(
+            reply,
+            Err(TransferError::InvalidAccount(AccountError::Blocked { .. }))
+        ));
+
+        let tree = canister
+            .new_call("tree")
+            .with_arg(2u8)
+            .perform()
+            .await
+            .decode_one::<Tree>()
+            .unwrap();
+
+        assert_eq!(tree.children.len(), 2);
+        assert_eq!(tree.children[1].children[0].value, 6);
+    }
+}
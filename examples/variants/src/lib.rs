@@ -0,0 +1,2 @@
+pub mod canister;
+pub use canister::VariantsCanister;
@@ -8,8 +8,20 @@ use std::sync::Arc;
 
 /// Defines any type that can be converted to a [`HashTree`].
 ///
-/// For number types this trait is implemented to use the BigEndian byte ordering to represent
-/// the number as a [`[u8]`].
+/// The implementations provided by this crate all return a single leaf, the bytes of the leaf
+/// are part of the hash, so they are stable across versions:
+///
+/// - `u8`..`u128`, `i8`..`i128`, `f32` and `f64`: The big-endian bytes (`to_be_bytes`), so the
+///   leaf has the fixed size of the type.
+/// - `usize` and `isize`: The big-endian bytes, which are 4 bytes on wasm32, so the hash differs
+///   from the one computed on a 64-bit target.
+/// - `bool`: `[1]` for `true` and `[0]` for `false`.
+/// - `String` and `&str`: The UTF-8 bytes.
+/// - `Vec<u8>`, `&[u8]` and `[u8; N]` for `N <= 32`: The bytes as is.
+/// - `Principal`: The raw bytes of the principal, see `Principal::as_slice`.
+/// - `Nat`: The minimal big-endian bytes of the number, which is `[0]` for zero.
+///
+/// `Box<T>`, `Rc<T>` and `Arc<T>` return the tree of the value they point to.
 pub trait AsHashTree {
     /// This method should return the root hash of this hash tree.
     /// Must be equivalent to `as_hash_tree().reconstruct()`.
@@ -215,3 +227,66 @@ impl_fixed_size!(
     1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
     27, 28, 29, 30, 31, 32
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex_hash<T: AsHashTree>(value: T) -> String {
+        let hash = value.root_hash();
+        assert_eq!(hash, value.as_hash_tree().reconstruct());
+        hex::encode(hash)
+    }
+
+    #[test]
+    fn number_hashes() {
+        let one = "6d0298170467fc16015b1473c87a39474cbce9167d0c8e99f5173fda21e00420";
+        assert_eq!(hex_hash(1u8), one);
+        assert_eq!(hex_hash(1i8), one);
+        assert_eq!(
+            hex_hash(1u32),
+            "8a55d17b2e5f2f2b28c2bbd9e1ba503e241d92524004e50b996e9c40c292fe1d"
+        );
+        assert_eq!(
+            hex_hash(-1i16),
+            "10993de3a63cc66d362fa58eeed308f713270a655ac350094128282ac105a1ef"
+        );
+        assert_eq!(
+            hex_hash(0x0102030405060708u64),
+            "454e98acf7a044ae5faacabea552b7ee2745a0b1f3ff990ba01dc9b72f55fa53"
+        );
+        assert_eq!(
+            hex_hash(1u128),
+            "e7d6ad16b0b9b01cf5bf1ec4b2add1170cd1c50fe97a1d1df36867d5cd646ce1"
+        );
+        assert_eq!(hex_hash(Nat::from(1u64)), one);
+    }
+
+    #[test]
+    fn bool_hashes() {
+        assert_eq!(hex_hash(true), hex_hash(1u8));
+        assert_eq!(
+            hex_hash(false),
+            "11da38ec7ff5f437609f2096d39a9a557cfe6c411f121fd5cb42296a44a33d83"
+        );
+    }
+
+    #[test]
+    fn bytes_hashes() {
+        let hello = "99cf694471b0e9c54db36120f914f12537b3a7417c301e10851f341f4d5ca14a";
+        assert_eq!(hex_hash("hello"), hello);
+        assert_eq!(hex_hash(String::from("hello")), hello);
+        assert_eq!(hex_hash(b"hello".to_vec()), hello);
+        assert_eq!(hex_hash(&b"hello"[..]), hello);
+        assert_eq!(hex_hash(*b"hello"), hello);
+        assert_eq!(hex_hash(Box::new(b"hello".to_vec())), hello);
+        assert_eq!(
+            hex_hash(Vec::<u8>::new()),
+            "d001f3e7b82166c6d343a1efe776e96ac02a23a51e0898bc2c4e323fce0e622c"
+        );
+        assert_eq!(
+            hex_hash(Principal::anonymous()),
+            "f1cbe5b49dc100566c442215a48f86b12dce8d083393072b43d9529d7d78d8a5"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! A value whose root hash is kept as the certified data of the canister.
+//!
+//! The IC only allows a canister to certify 32 bytes, so most canisters keep all of their
+//! certified state in one tree and certify its root hash. [`CertifiedData`] owns that tree and
+//! updates the certified data every time the tree is changed, so a mutation can not be left
+//! uncertified by accident.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use ic_kit_certified::{CertifiedData, Map};
+//!
+//! let mut data = CertifiedData::new(Map::<String, Vec<u8>>::new());
+//!
+//! // The new root hash is certified once the guard is dropped.
+//! data.modify().insert("name".to_string(), b"ic-kit".to_vec());
+//!
+//! // In a query, the witness and the certificate can be sent to the client.
+//! let witness = data.witness("name");
+//! let certificate = data.data_certificate();
+//! ```
+
+use crate::collections::map::Map;
+use crate::label::Label;
+use crate::{AsHashTree, HashTree};
+use ic_kit::ic;
+use std::borrow::Borrow;
+use std::ops::{Deref, DerefMut};
+
+/// A value whose root hash is the certified data of the canister, see the
+/// [module level documentation](self).
+///
+/// There should only be one instance of this type in a canister, since each one overwrites the
+/// certified data set by the others.
+pub struct CertifiedData<T: AsHashTree> {
+    value: T,
+}
+
+/// A guard that gives mutable access to the value of a [`CertifiedData`] and certifies the new
+/// root hash once it is dropped, see [`CertifiedData::modify`].
+pub struct CertifiedDataMut<'a, T: AsHashTree> {
+    data: &'a mut CertifiedData<T>,
+}
+
+impl<T: AsHashTree> CertifiedData<T> {
+    /// Wrap the given value and certify its root hash.
+    ///
+    /// # Traps
+    ///
+    /// If called from a query, the certified data can only be set in the update calls, the
+    /// callbacks, the timers and the lifecycle hooks.
+    pub fn new(value: T) -> Self {
+        let data = Self { value };
+        data.commit();
+        data
+    }
+
+    /// Return a guard which can be used to modify the value, the root hash of the value is
+    /// certified when the guard is dropped.
+    #[inline]
+    pub fn modify(&mut self) -> CertifiedDataMut<'_, T> {
+        CertifiedDataMut { data: self }
+    }
+
+    /// Set the certified data of the canister to the current root hash of the value. This is
+    /// done automatically by [`new`](Self::new) and [`modify`](Self::modify), and only needs to
+    /// be called if the certified data was changed by someone else, for example after an upgrade
+    /// the certified data of the canister is empty until it is set again.
+    pub fn commit(&self) {
+        ic::set_certified_data(&self.value.root_hash());
+    }
+
+    /// Build a witness of the value using the given function, the witness can be sent to a
+    /// client along with the [`data_certificate`](Self::data_certificate).
+    #[inline]
+    pub fn witness_with<'a, F>(&'a self, f: F) -> HashTree<'a>
+    where
+        F: FnOnce(&'a T) -> HashTree<'a>,
+    {
+        f(&self.value)
+    }
+
+    /// Returns the certificate for the certified data, this is only available in a query.
+    #[inline]
+    pub fn data_certificate(&self) -> Option<Vec<u8>> {
+        ic::data_certificate()
+    }
+
+    /// Return the value and leave the certified data of the canister as is.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<K: 'static + Label, V: AsHashTree + 'static> CertifiedData<Map<K, V>> {
+    /// Create a witness for the value associated with the given key, see [`Map::witness`].
+    #[inline]
+    pub fn witness<Q: ?Sized>(&self, key: &Q) -> HashTree
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        self.value.witness(key)
+    }
+}
+
+impl<T: AsHashTree + Default> Default for CertifiedData<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: AsHashTree> Deref for CertifiedData<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<'a, T: AsHashTree> Deref for CertifiedDataMut<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.data.value
+    }
+}
+
+impl<'a, T: AsHashTree> DerefMut for CertifiedDataMut<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.data.value
+    }
+}
+
+impl<'a, T: AsHashTree> Drop for CertifiedDataMut<'a, T> {
+    fn drop(&mut self) {
+        self.data.commit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_kit::rt::types::{EntryMode, Env};
+    use ic_kit::rt::{Canister, Replica, TokioRuntimeBuilder};
+    use ic_kit::Principal;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static DATA: RefCell<Option<CertifiedData<Map<String, Vec<u8>>>>> = RefCell::new(None);
+    }
+
+    #[test]
+    fn certified_data() {
+        TokioRuntimeBuilder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let replica = Replica::default();
+                let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+
+                canister
+                    .custom(
+                        || {
+                            let mut data = CertifiedData::new(Map::new());
+                            data.modify().insert("a".to_string(), vec![1]);
+                            data.modify().insert("b".to_string(), vec![2]);
+                            DATA.with(|d| d.replace(Some(data)));
+                            ic_kit::utils::reply(&[]);
+                        },
+                        Env::default(),
+                    )
+                    .await
+                    .assert_ok();
+
+                let root_hash = canister
+                    .custom(
+                        || {
+                            DATA.with(|d| {
+                                let data = d.borrow();
+                                let data = data.as_ref().unwrap();
+                                assert!(data.data_certificate().is_some());
+
+                                let witness = data.witness("a");
+                                assert_eq!(witness.reconstruct(), data.root_hash());
+                                ic_kit::utils::reply(&data.root_hash());
+                            });
+                        },
+                        Env::default().with_entry_mode(EntryMode::Query),
+                    )
+                    .await;
+
+                let certified_data = canister.snapshot().await.certified_data;
+                assert_eq!(root_hash.bytes().unwrap(), certified_data.as_slice());
+            });
+    }
+}
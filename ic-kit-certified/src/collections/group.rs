@@ -1,4 +1,5 @@
-use crate::hashtree::HashTree::Pruned;
+use crate::collections::group::builder::GroupBuilderNode;
+use crate::hashtree::HashTree::{Empty, Pruned};
 use crate::hashtree::{fork_hash, labeled_hash, ForkInner};
 use crate::{AsHashTree, Hash, HashTree};
 use std::any::{Any, TypeId};
@@ -15,6 +16,8 @@ type NodeId = u64;
 pub struct Group {
     /// The root node of the group is a shadow of the shape of the group's tree.
     root: GroupNode,
+    /// The paths of the leaves, used to rebuild the tree when a leaf is added or removed.
+    layout: GroupBuilderNode,
     /// The data in this group.
     data: HashMap<TypeId, Box<dyn GroupLeaf>>,
     /// Map each typeId used in a Leaf node to all of its ancestors.
@@ -42,6 +45,7 @@ enum GroupNodeInner {
     Fork(Box<GroupNode>, Box<GroupNode>),
     Labeled(String, Box<GroupNode>),
     Leaf(TypeId),
+    Empty,
 }
 
 impl Group {
@@ -52,6 +56,40 @@ impl Group {
         self.root.visit_node(0, &mut self.dependencies, &mut path);
     }
 
+    /// Add a new leaf to the group at the given path, the shape of the tree is rebuilt so any
+    /// node ids and dependencies are recomputed.
+    ///
+    /// # Panics
+    ///
+    /// If the type of the leaf is already used in the group or the path is already used.
+    pub fn insert_leaf(&mut self, path: &[&str], leaf: Box<dyn GroupLeaf>) {
+        let tid = Any::type_id(&*leaf);
+
+        if self.data.contains_key(&tid) {
+            panic!("The type is already used in the group.");
+        }
+
+        self.layout
+            .insert(path.iter().map(|p| p.to_string()).collect(), tid);
+        self.data.insert(tid, leaf);
+        self.rebuild();
+    }
+
+    /// Remove the leaf at the given path from the group and return it, directories that become
+    /// empty are removed from the tree as well.
+    pub fn remove_leaf(&mut self, path: &[&str]) -> Option<Box<dyn GroupLeaf>> {
+        let tid = self.layout.remove(path)?;
+        let leaf = self.data.remove(&tid);
+        self.rebuild();
+        leaf
+    }
+
+    #[inline]
+    fn rebuild(&mut self) {
+        self.root = self.layout.build();
+        self.init();
+    }
+
     /// Create a new witness builder that can be used to generate a [`HashTree`] for
     /// the entire group.
     #[must_use = "This method does not have any effects on the group."]
@@ -122,6 +160,10 @@ impl GroupNode {
                 self.id = id;
                 next_id
             }
+            GroupNodeInner::Empty => {
+                self.id = id;
+                id + 1
+            }
         }
     }
 
@@ -141,6 +183,7 @@ impl GroupNode {
                 HashTree::Labeled(Cow::Borrowed(label.as_bytes()), Box::new(tree))
             }
             GroupNodeInner::Leaf(tid) => ray.leaves.remove(tid).unwrap(),
+            GroupNodeInner::Empty => Empty,
         }
     }
 
@@ -156,6 +199,7 @@ impl GroupNode {
                 HashTree::Labeled(Cow::Borrowed(label.as_bytes()), Box::new(tree))
             }
             GroupNodeInner::Leaf(tid) => group.data.get(tid).unwrap().as_hash_tree(),
+            GroupNodeInner::Empty => Empty,
         }
     }
 
@@ -169,6 +213,7 @@ impl GroupNode {
                 labeled_hash(label.as_bytes(), &hash)
             }
             GroupNodeInner::Leaf(id) => group.data.get(id).unwrap().root_hash(),
+            GroupNodeInner::Empty => Empty.reconstruct(),
         }
     }
 }
@@ -299,6 +344,9 @@ mod tests {
                     }),
                 ),
             },
+            layout: GroupBuilderNode::Directory {
+                children: Default::default(),
+            },
             data: Default::default(),
             dependencies: Default::default(),
         };
@@ -388,4 +436,80 @@ mod tests {
             vec![b"canister" as &[u8], b"url", b"meta", b"name"]
         );
     }
+
+    #[test]
+    fn insert_remove_leaf() {
+        type Ledger = Map<Principal, u64>;
+        type Names = Map<String, String>;
+
+        let mut group = GroupBuilder::new()
+            .insert(["ledger"], Ledger::new())
+            .build();
+
+        let hash = group.root_hash();
+        let mut names = Names::new();
+        names.insert("X".to_string(), "XTC".to_string());
+
+        group.insert_leaf(&["meta", "names"], Box::new(names));
+        assert_ne!(group.root_hash(), hash);
+
+        let expected = GroupBuilder::new()
+            .insert(["ledger"], Ledger::new())
+            .insert(["meta", "names"], {
+                let mut names = Names::new();
+                names.insert("X".to_string(), "XTC".to_string());
+                names
+            })
+            .build();
+        assert_eq!(group.root_hash(), expected.root_hash());
+
+        let tree = group.witness().full::<Names>().build();
+        assert_eq!(tree.reconstruct(), group.root_hash());
+        assert_eq!(tree.get_labels(), vec![b"meta" as &[u8], b"names", b"X"]);
+        assert_eq!(group.get::<Names>().len(), 1);
+
+        assert!(group.remove_leaf(&["meta"]).is_none());
+        let removed = group.remove_leaf(&["meta", "names"]).unwrap();
+        assert!(removed.is::<Names>());
+        assert_eq!(group.root_hash(), hash);
+
+        assert!(group.remove_leaf(&["ledger"]).is_some());
+        assert_eq!(group.root_hash(), Empty.reconstruct());
+        assert_eq!(group.as_hash_tree(), Empty);
+    }
+
+    #[test]
+    fn lookup_path() {
+        use crate::hashtree::LookupResult::{Absent, Found, Unknown};
+
+        type Ledger = Map<Principal, u64>;
+        type Names = Map<String, String>;
+
+        let mut names = Names::new();
+        names.insert("A".to_string(), "a".to_string());
+        names.insert("C".to_string(), "c".to_string());
+
+        let group = GroupBuilder::new()
+            .insert(["ledger"], Ledger::new())
+            .insert(["meta", "names"], names)
+            .build();
+
+        let tree = group
+            .witness()
+            .partial(|names: &Names| names.witness("A"))
+            .build();
+        assert_eq!(tree.reconstruct(), group.root_hash());
+        assert_eq!(tree.lookup_path(&[b"meta", b"names", b"A"]), Found(b"a"));
+        assert_eq!(tree.lookup_path(&[b"meta", b"names", b"C"]), Unknown);
+        assert_eq!(tree.lookup_path(&[b"ledger"]), Unknown);
+        assert_eq!(tree.lookup_path(&[b"other"]), Absent);
+
+        let tree = group
+            .witness()
+            .partial(|names: &Names| names.witness("B"))
+            .build();
+        assert_eq!(tree.reconstruct(), group.root_hash());
+        assert_eq!(tree.lookup_path(&[b"meta", b"names", b"B"]), Absent);
+        assert_eq!(tree.lookup_path(&[b"meta", b"names", b"A"]), Unknown);
+    }
 }
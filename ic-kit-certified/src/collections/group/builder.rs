@@ -7,7 +7,8 @@ pub struct GroupBuilder {
     data: HashMap<TypeId, Box<dyn GroupLeaf>>,
 }
 
-enum GroupBuilderNode {
+/// The shape of a group as a directory tree, the [`GroupNode`] tree is built from this.
+pub(super) enum GroupBuilderNode {
     Directory {
         children: BTreeMap<String, Box<GroupBuilderNode>>,
     },
@@ -51,6 +52,7 @@ impl GroupBuilder {
     pub fn build(self) -> Group {
         let mut group = Group {
             root: self.root.build(),
+            layout: self.root,
             data: self.data,
             dependencies: Default::default(),
         };
@@ -98,14 +100,51 @@ impl GroupBuilderNode {
         panic!("Can not insert to a leaf node.");
     }
 
-    pub fn build(self) -> GroupNode {
+    /// Remove the leaf at the given path and return its type id, directories that become empty
+    /// are removed as well.
+    pub fn remove(&mut self, path: &[&str]) -> Option<TypeId> {
+        let children = match self {
+            GroupBuilderNode::Directory { children } => children,
+            GroupBuilderNode::Leaf { .. } => return None,
+        };
+
+        let (name, rest) = path.split_first()?;
+
+        if rest.is_empty() {
+            return match children.get(*name).map(|node| node.as_ref()) {
+                Some(GroupBuilderNode::Leaf { tid }) => {
+                    let tid = *tid;
+                    children.remove(*name);
+                    Some(tid)
+                }
+                _ => None,
+            };
+        }
+
+        let child = children.get_mut(*name)?;
+        let tid = child.remove(rest)?;
+
+        if let GroupBuilderNode::Directory { children: c } = child.as_ref() {
+            if c.is_empty() {
+                children.remove(*name);
+            }
+        }
+
+        Some(tid)
+    }
+
+    pub fn build(&self) -> GroupNode {
         match self {
+            GroupBuilderNode::Directory { children } if children.is_empty() => GroupNode {
+                id: 0,
+                data: GroupNodeInner::Empty,
+            },
             GroupBuilderNode::Directory { children } => {
                 let mut children = children
-                    .into_iter()
+                    .iter()
                     .map(|(k, v)| GroupNode {
                         id: 0,
-                        data: GroupNodeInner::Labeled(k, Box::new(v.build())),
+                        data: GroupNodeInner::Labeled(k.clone(), Box::new(v.build())),
                     })
                     .collect::<VecDeque<_>>();
 
@@ -134,7 +173,7 @@ impl GroupBuilderNode {
             }
             GroupBuilderNode::Leaf { tid } => GroupNode {
                 id: 0,
-                data: GroupNodeInner::Leaf(tid),
+                data: GroupNodeInner::Leaf(*tid),
             },
         }
     }
@@ -11,7 +11,7 @@ use serde::ser::SerializeMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::borrow::Borrow;
 use std::fmt::{self, Debug, Formatter};
-use std::iter::FromIterator;
+use std::iter::{FromIterator, Rev};
 use std::marker::PhantomData;
 
 #[derive(Default)]
@@ -27,6 +27,16 @@ impl<K: 'static + Label, V: AsHashTree + 'static> Map<K, V> {
         }
     }
 
+    /// Create a map from entries that are sorted by their key in strictly increasing order, this
+    /// is faster than inserting the entries one by one, since each entry is hashed exactly once.
+    /// See [`RbTree::bulk_load`] for the details.
+    #[inline]
+    pub fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        Self {
+            inner: RbTree::bulk_load(entries),
+        }
+    }
+
     /// Returns `true` if the map does not contain any values.
     #[inline]
     pub fn is_empty(&self) -> bool {
@@ -64,6 +74,32 @@ impl<K: 'static + Label, V: AsHashTree + 'static> Map<K, V> {
         self.inner.delete(key).map(|(_, v)| v)
     }
 
+    /// Remove all of the keys in the range `[first, last]` from the map and return the number of
+    /// the removed entries.
+    #[inline]
+    pub fn remove_range<Q: ?Sized>(&mut self, first: &Q, last: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        self.inner.delete_range(first, last)
+    }
+
+    /// Like [`Map::remove_range`] but also returns a witness for the key-value pairs in the range
+    /// taken before they were removed, which proves what was removed against the old root hash.
+    pub fn remove_range_with_witness<Q: ?Sized>(
+        &mut self,
+        first: &Q,
+        last: &Q,
+    ) -> (usize, HashTree<'static>)
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        let witness = self.inner.value_range(first, last).into_owned();
+        (self.inner.delete_range(first, last), witness)
+    }
+
     /// Remove an entry from the map and return the key and value.
     #[inline]
     pub fn remove_entry<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
@@ -105,6 +141,35 @@ impl<K: 'static + Label, V: AsHashTree + 'static> Map<K, V> {
         RbTreeIterator::new(&self.inner)
     }
 
+    /// Return an iterator over the key-values in the map in the descending order of the keys,
+    /// this can be used to show the most recent entries first when the keys are increasing.
+    #[inline]
+    pub fn iter_rev(&self) -> Rev<RbTreeIterator<K, V>> {
+        self.iter().rev()
+    }
+
+    /// Return an iterator over the key-values with a key in the range `[first, last]` in the
+    /// sorted order, nothing is yielded if `first > last`.
+    #[inline]
+    pub fn range<Q: ?Sized>(&self, first: &Q, last: &Q) -> RbTreeIterator<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        RbTreeIterator::range(&self.inner, first, last)
+    }
+
+    /// Return an iterator over the key-values with a key greater than or equal to `first` in the
+    /// sorted order.
+    #[inline]
+    pub fn range_from<Q: ?Sized>(&self, first: &Q) -> RbTreeIterator<K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        RbTreeIterator::range_from(&self.inner, first)
+    }
+
     /// Create a HashTree witness for the value associated with given key.
     #[inline]
     pub fn witness<Q: ?Sized>(&self, key: &Q) -> HashTree
@@ -384,4 +449,99 @@ mod tests {
             assert_eq!(map.get(&hex::encode(&i.to_be_bytes())), None);
         }
     }
+
+    #[test]
+    fn remove_range() {
+        let mut map = Map::<String, u32>::new();
+
+        for i in 0..200u32 {
+            map.insert(hex::encode(&i.to_be_bytes()), i);
+        }
+
+        let first = hex::encode(&50u32.to_be_bytes());
+        let last = hex::encode(&149u32.to_be_bytes());
+        assert_eq!(map.remove_range(&first, &last), 100);
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.remove_range(&first, &last), 0);
+
+        let expected = (0..50u32)
+            .chain(150..200)
+            .map(|i| (hex::encode(&i.to_be_bytes()), i))
+            .collect::<Map<_, _>>();
+
+        assert_eq!(map.root_hash(), expected.root_hash());
+
+        assert_eq!(map.remove_range("0", "1"), 100);
+        assert!(map.is_empty());
+        assert_eq!(map.root_hash(), Map::<String, u32>::new().root_hash());
+    }
+
+    #[test]
+    fn remove_range_with_witness() {
+        let mut map = Map::<String, u32>::new();
+
+        for i in 0..20u32 {
+            map.insert(hex::encode(&i.to_be_bytes()), i);
+        }
+
+        let old_hash = map.root_hash();
+        let first = hex::encode(&5u32.to_be_bytes());
+        let last = hex::encode(&9u32.to_be_bytes());
+        let (count, witness) = map.remove_range_with_witness(&first, &last);
+
+        assert_eq!(count, 5);
+        assert_eq!(witness.reconstruct(), old_hash);
+
+        for i in 5..10u32 {
+            let key = hex::encode(&i.to_be_bytes());
+            assert!(witness.get_labels().contains(&key.as_bytes()));
+            assert_eq!(map.get(&key), None);
+        }
+    }
+
+    #[test]
+    fn from_sorted_iter() {
+        for len in 0..100u32 {
+            let entries = (0..len).map(|i| (hex::encode(&i.to_be_bytes()), i));
+            let mut map = Map::from_sorted_iter(entries.clone());
+            assert_eq!(map.len(), len as usize);
+
+            for (key, value) in entries {
+                assert_eq!(map.get(&key), Some(&value));
+                assert_eq!(map.witness(&key).reconstruct(), map.root_hash());
+            }
+
+            // The tree should remain valid through the insertions and the removals.
+            map.insert("X".to_string(), 0);
+            for i in 0..len {
+                assert_eq!(map.remove(&hex::encode(&i.to_be_bytes())), Some(i));
+            }
+            assert_eq!(map.remove("X"), Some(0));
+            assert!(map.is_empty());
+        }
+    }
+
+    #[test]
+    fn from_unsorted_iter() {
+        let map = Map::from_sorted_iter(vec![
+            ("B".to_string(), 1),
+            ("A".to_string(), 2),
+            ("B".to_string(), 3),
+        ]);
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("A"), Some(&2));
+        assert_eq!(map.get("B"), Some(&3));
+    }
+
+    #[test]
+    fn iter_rev() {
+        let map = (0..10u32)
+            .map(|i| (i.to_be_bytes(), i))
+            .collect::<Map<_, _>>();
+
+        let values = map.iter_rev().map(|(_, v)| *v).collect::<Vec<_>>();
+        assert_eq!(values, (0..10).rev().collect::<Vec<_>>());
+        assert_eq!(map.iter_rev().nth(2), Some((&7u32.to_be_bytes(), &7)));
+    }
 }
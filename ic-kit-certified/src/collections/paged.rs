@@ -4,6 +4,34 @@ use candid::CandidType;
 use serde::{Deserialize, Serialize};
 use std::borrow::{Borrow, Cow};
 
+/// A certified map from a key to a list of items which is split into pages of `S` items.
+///
+/// Each page is stored as a separate [`Seq`] in a [`Map`] under a label made out of the label
+/// of the key followed by the page number as a big-endian `u32`, so the items with index
+/// `n * S..(n + 1) * S` are in the page `n` and only the last page of a key may contain less
+/// than `S` items. This allows a canister to return one page of a list (i.e. the transaction
+/// history of a user) along with a witness that only contains that page.
+///
+/// # Example
+///
+/// ```
+/// use ic_kit_certified::Paged;
+///
+/// let mut paged = Paged::<u8, u32, 2>::new();
+///
+/// paged.push(0, 10);
+/// paged.push(0, 11);
+/// paged.push(0, 12);
+///
+/// assert_eq!(paged.len(&0), 3);
+/// assert_eq!(paged.get_last_page_number(&0), Some(1));
+/// assert_eq!(paged.get_page(&0, 0), &[10, 11]);
+/// assert_eq!(paged.get_page(&0, 1), &[12]);
+/// assert!(paged.get_page(&0, 2).is_empty());
+///
+/// // A certified query returns the page along with this witness.
+/// let witness = paged.witness_page(&0, 1);
+/// ```
 #[derive(CandidType, Serialize, Deserialize, Debug)]
 pub struct Paged<K: Label + Ord + 'static, V: AsHashTree + 'static, const S: usize> {
     data: Map<PagedKey<K>, Seq<V>>,
@@ -44,7 +72,9 @@ impl<K: Label + Ord + 'static, V: AsHashTree + 'static, const S: usize> Paged<K,
         Self { data: Map::new() }
     }
 
-    pub fn insert(&mut self, key: K, item: V) {
+    /// Append the item to the last page of the given key, a new page is created when the last
+    /// page is already full.
+    pub fn push(&mut self, key: K, item: V) {
         let tree = &mut self.data.inner;
         let mut item = Some(item);
 
@@ -66,6 +96,23 @@ impl<K: Label + Ord + 'static, V: AsHashTree + 'static, const S: usize> Paged<K,
         }
     }
 
+    /// Append the item to the last page of the given key, same as [`Paged::push`].
+    #[inline]
+    pub fn insert(&mut self, key: K, item: V) {
+        self.push(key, item)
+    }
+
+    /// Return the total number of items inserted for the given key.
+    pub fn len(&self, key: &K) -> usize {
+        self.data
+            .inner
+            .max_entry_with_prefix(key)
+            .map(|(k, seq)| k.page as usize * S + seq.len())
+            .unwrap_or(0)
+    }
+
+    /// Return the number of the last page of the given key, or `None` if there are no items
+    /// for the key.
     pub fn get_last_page_number(&self, key: &K) -> Option<usize> {
         self.data
             .inner
@@ -73,6 +120,8 @@ impl<K: Label + Ord + 'static, V: AsHashTree + 'static, const S: usize> Paged<K,
             .map(|(k, _)| k.page as usize)
     }
 
+    /// Create a witness proving the number of the last page of the key, this is the witness for
+    /// the absence of the page after the last page.
     // TODO(qti3e) Remove the Clone.
     pub fn witness_last_page_number(&self, key: &K) -> HashTree<'_>
     where
@@ -91,12 +140,32 @@ impl<K: Label + Ord + 'static, V: AsHashTree + 'static, const S: usize> Paged<K,
         self.data.witness(&key)
     }
 
+    /// Return the items in the given page of the key.
     pub fn get(&self, key: &K, page: usize) -> Option<&Seq<V>> {
         let page = page as u32;
         let key = (key, page);
         self.data.inner.get_with(|k| key.cmp(&(&k.key, k.page)))
     }
 
+    /// Return the items in the given page of the key, the page is empty if it does not exist.
+    pub fn get_page(&self, key: &K, page: usize) -> &[V] {
+        self.get(key, page)
+            .map(|seq| seq.as_vec().as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Create a witness for the items in the given page of the key, or for the absence of the
+    /// page if it does not exist. The witness only reveals that page, so it can be returned
+    /// along with the result of [`Paged::get_page`].
+    pub fn witness_page(&self, key: &K, page: usize) -> HashTree<'_>
+    where
+        K: Clone,
+    {
+        self.witness(key, page)
+    }
+
+    /// Create a witness for the items in the given page of the key, or for the absence of the
+    /// page if it does not exist.
     // TODO(qti3e) Remove the Clone in future.
     pub fn witness(&self, key: &K, page: usize) -> HashTree<'_>
     where
@@ -203,4 +272,55 @@ mod tests {
             assert_eq!(paged.get(&k, 4), None);
         }
     }
+
+    #[test]
+    fn last_page() {
+        let mut paged = Paged::<u8, u32, 3>::new();
+        assert_eq!(paged.len(&0), 0);
+        assert_eq!(paged.get_last_page_number(&0), None);
+
+        for i in 0..7 {
+            paged.insert(0, i);
+            paged.insert(1, i + 100);
+        }
+        paged.insert(1, 107);
+
+        assert_eq!(paged.len(&0), 7);
+        assert_eq!(paged.len(&1), 8);
+        assert_eq!(paged.len(&2), 0);
+        assert_eq!(paged.get_last_page_number(&0), Some(2));
+        assert_eq!(paged.get(&0, 2).unwrap().as_vec(), &vec![6]);
+        assert_eq!(paged.get(&1, 2).unwrap().as_vec(), &vec![106, 107]);
+
+        let tree = paged.witness(&0, 2);
+        assert_eq!(tree.reconstruct(), paged.root_hash());
+        assert_eq!(tree.get_labels(), vec![&[0, 0, 0, 0, 2] as &[u8]]);
+
+        let tree = paged.witness_last_page_number(&0);
+        assert_eq!(tree.reconstruct(), paged.root_hash());
+    }
+
+    #[test]
+    fn witness_page() {
+        let mut paged = Paged::<u8, u32, 3>::new();
+
+        for i in 0..8 {
+            paged.push(0, i);
+            paged.push(1, i + 100);
+        }
+
+        assert_eq!(paged.get_page(&1, 0), &[100, 101, 102]);
+        assert_eq!(paged.get_page(&1, 2), &[106, 107]);
+        assert_eq!(paged.get_page(&1, 3), &[] as &[u32]);
+        assert_eq!(paged.get_page(&2, 0), &[] as &[u32]);
+
+        let tree = paged.witness_page(&1, 1);
+        assert_eq!(tree.reconstruct(), paged.root_hash());
+        assert_eq!(tree.get_labels(), vec![&[1, 0, 0, 0, 1] as &[u8]]);
+
+        assert_eq!(paged.get_page(&1, 1), &[103, 104, 105]);
+
+        // The other pages are pruned from the witness.
+        assert_ne!(tree, paged.as_hash_tree());
+    }
 }
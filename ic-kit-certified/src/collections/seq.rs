@@ -109,6 +109,19 @@ impl<T: AsHashTree> Seq<T> {
         self.items.capacity()
     }
 
+    /// Returns a reference to the element at the given index, or `None` if the index is out of
+    /// bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index)
+    }
+
+    /// Returns the last element of the sequence, or `None` if it is empty.
+    #[inline]
+    pub fn last(&self) -> Option<&T> {
+        self.items.last()
+    }
+
     /// Returns an iterator over the data.
     #[inline]
     pub fn iter(&self) -> Iter<'_, T> {
@@ -359,6 +372,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn get() {
+        let mut seq = Seq::<u64>::new();
+        assert_eq!(seq.get(0), None);
+        assert_eq!(seq.last(), None);
+
+        seq.extend(0..1000);
+        assert_eq!(seq.get(0), Some(&0));
+        assert_eq!(seq.get(999), Some(&999));
+        assert_eq!(seq.get(1000), None);
+        assert_eq!(seq.last(), Some(&999));
+
+        let mut appended = Seq::<u64>::new();
+        for i in 0..1000 {
+            appended.append(i);
+        }
+
+        assert_eq!(appended.root_hash(), seq.root_hash());
+    }
+
     #[test]
     #[should_panic]
     fn index_out_of_range() {
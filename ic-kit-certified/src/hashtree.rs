@@ -32,6 +32,26 @@ pub enum HashTree<'a> {
     Pruned(Hash),
 }
 
+/// The result of [`HashTree::lookup_path`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LookupResult<'a> {
+    /// The value of the leaf at the path.
+    Found(&'a [u8]),
+    /// The tree proves that there is no value at the path.
+    Absent,
+    /// The path is pruned from the tree, so the tree does not prove anything about it.
+    Unknown,
+    /// The path points to a labeled or fork node instead of a value.
+    Error,
+}
+
+/// The result of looking up a label among the children of a node.
+enum LabelLookup<'b, 'a> {
+    Found(&'b HashTree<'a>),
+    Absent,
+    Unknown,
+}
+
 pub fn fork<'a>(l: HashTree<'a>, r: HashTree<'a>) -> HashTree<'a> {
     HashTree::Fork(Box::new(ForkInner(l, r)))
 }
@@ -74,6 +94,103 @@ impl<'a> HashTree<'a> {
         }
     }
 
+    /// Encode the tree to CBOR prefixed with the self-describe tag (55799), this is the encoding
+    /// that the IC expects in the `tree` field of the `IC-Certificate` header.
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        let mut serializer = serde_cbor::Serializer::new(&mut buffer);
+        serializer
+            .self_describe()
+            .expect("Writing to a vector can not fail.");
+        self.serialize(&mut serializer)
+            .expect("Writing to a vector can not fail.");
+        buffer
+    }
+
+    /// Convert this tree to a tree that owns all of its labels and leaves, so it no longer
+    /// borrows from the data structure that created it.
+    pub fn into_owned(self) -> HashTree<'static> {
+        match self {
+            Self::Empty => HashTree::Empty,
+            Self::Fork(f) => {
+                let ForkInner(l, r) = *f;
+                fork(l.into_owned(), r.into_owned())
+            }
+            Self::Labeled(l, t) => {
+                HashTree::Labeled(Cow::Owned(l.into_owned()), Box::new(t.into_owned()))
+            }
+            Self::Leaf(data) => HashTree::Leaf(Cow::Owned(data.into_owned())),
+            Self::Pruned(h) => HashTree::Pruned(h),
+        }
+    }
+
+    /// Lookup the value at the given path in the tree, this follows the lookup semantics of the
+    /// certificates as defined in the interface spec, so it can be used to read a value from a
+    /// witness and tell if it is missing or just pruned.
+    pub fn lookup_path(&self, path: &[&[u8]]) -> LookupResult<'_> {
+        let (label, rest) = match path.split_first() {
+            Some(split) => split,
+            None => {
+                return match self {
+                    Self::Empty => LookupResult::Absent,
+                    Self::Leaf(data) => LookupResult::Found(data),
+                    Self::Pruned(_) => LookupResult::Unknown,
+                    Self::Labeled(_, _) | Self::Fork(_) => LookupResult::Error,
+                }
+            }
+        };
+
+        match self.find_label(label) {
+            LabelLookup::Found(tree) => tree.lookup_path(rest),
+            LabelLookup::Absent => LookupResult::Absent,
+            LabelLookup::Unknown => LookupResult::Unknown,
+        }
+    }
+
+    /// Find the subtree with the given label among the labeled nodes under the forks of this
+    /// tree, the labels are sorted, so the neighbours of a missing label prove its absence.
+    fn find_label(&self, label: &[u8]) -> LabelLookup<'_, 'a> {
+        fn flatten<'b, 'a>(nodes: &mut Vec<&'b HashTree<'a>>, tree: &'b HashTree<'a>) {
+            match tree {
+                HashTree::Empty => (),
+                HashTree::Fork(lr) => {
+                    flatten(nodes, lr.left());
+                    flatten(nodes, lr.right());
+                }
+                _ => nodes.push(tree),
+            }
+        }
+
+        let mut nodes = Vec::new();
+        flatten(&mut nodes, self);
+
+        let mut previous_is_label = true;
+
+        for node in nodes {
+            match node {
+                HashTree::Labeled(l, tree) if l.as_ref() == label => {
+                    return LabelLookup::Found(tree)
+                }
+                HashTree::Labeled(l, _) if label < l.as_ref() => {
+                    // The label is not in the tree if it is right after a smaller label.
+                    return if previous_is_label {
+                        LabelLookup::Absent
+                    } else {
+                        LabelLookup::Unknown
+                    };
+                }
+                HashTree::Labeled(_, _) => previous_is_label = true,
+                _ => previous_is_label = false,
+            }
+        }
+
+        if previous_is_label {
+            LabelLookup::Absent
+        } else {
+            LabelLookup::Unknown
+        }
+    }
+
     /// Collect and return all of the labels in this HashTree.
     ///
     /// This method is intended for testing purposes.
@@ -176,7 +293,8 @@ fn domain_sep(s: &str) -> sha2::Sha256 {
 mod tests {
     use super::{
         fork, labeled,
-        HashTree::{Empty, Leaf},
+        HashTree::{Empty, Leaf, Pruned},
+        LookupResult::{Absent, Error, Found, Unknown},
     };
     use std::borrow::Cow;
 
@@ -213,5 +331,69 @@ mod tests {
         assert_eq!(
             hex::encode(serde_cbor::to_vec(&t).unwrap()),
             "8301830183024161830183018302417882034568656c6c6f810083024179820345776f726c6483024162820344676f6f648301830241638100830241648203476d6f726e696e67".to_string());
+
+        assert_eq!(
+            hex::encode(t.to_cbor()),
+            "d9d9f78301830183024161830183018302417882034568656c6c6f810083024179820345776f726c6483024162820344676f6f648301830241638100830241648203476d6f726e696e67".to_string());
+    }
+
+    #[test]
+    fn to_cbor_pruned() {
+        let t = fork(Pruned([1; 32]), labeled(b"a", Empty));
+        let cbor = t.to_cbor();
+
+        assert_eq!(&cbor[..3], &[0xd9, 0xd9, 0xf7]);
+        assert_eq!(&cbor[3..], serde_cbor::to_vec(&t).unwrap().as_slice());
+        assert_eq!(cbor[3], 0x83);
+    }
+
+    #[test]
+    fn lookup_path() {
+        // The lookup examples from the interface spec, using the tree of `test_public_spec_example`
+        // with some of the subtrees pruned.
+        let t = fork(
+            fork(
+                labeled(
+                    b"a",
+                    fork(
+                        Pruned([1; 32]),
+                        labeled(b"y", Leaf(Cow::Borrowed(b"world"))),
+                    ),
+                ),
+                labeled(b"b", Pruned([2; 32])),
+            ),
+            fork(
+                Pruned([3; 32]),
+                labeled(b"d", Leaf(Cow::Borrowed(b"morning"))),
+            ),
+        );
+
+        assert_eq!(t.lookup_path(&[b"a", b"a"]), Unknown);
+        assert_eq!(t.lookup_path(&[b"a", b"y"]), Found(b"world"));
+        assert_eq!(t.lookup_path(&[b"aa"]), Absent);
+        assert_eq!(t.lookup_path(&[b"ax"]), Absent);
+        assert_eq!(t.lookup_path(&[b"b"]), Unknown);
+        assert_eq!(t.lookup_path(&[b"bb"]), Unknown);
+        assert_eq!(t.lookup_path(&[b"d"]), Found(b"morning"));
+        assert_eq!(t.lookup_path(&[b"e"]), Absent);
+        assert_eq!(t.lookup_path(&[b"a"]), Error);
+        assert_eq!(t.lookup_path(&[]), Error);
+    }
+
+    #[test]
+    fn lookup_path_absent() {
+        let t = fork(
+            labeled(b"a", Leaf(Cow::Borrowed(b"x"))),
+            labeled(b"c", Pruned([1; 32])),
+        );
+
+        assert_eq!(t.lookup_path(&[]), Error);
+        assert_eq!(t.lookup_path(&[b"0"]), Absent);
+        assert_eq!(t.lookup_path(&[b"b"]), Absent);
+        assert_eq!(t.lookup_path(&[b"d"]), Absent);
+        assert_eq!(t.lookup_path(&[b"c"]), Unknown);
+        assert_eq!(t.lookup_path(&[b"a", b"x"]), Unknown);
+        assert_eq!(Empty.lookup_path(&[b"a"]), Absent);
+        assert_eq!(Empty.lookup_path(&[]), Absent);
     }
 }
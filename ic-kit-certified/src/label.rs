@@ -4,6 +4,10 @@ use std::ptr::NonNull;
 use std::rc::Rc;
 use std::sync::Arc;
 
+/// Derive the [`Label`] trait for a struct or an enum, see the documentation of the macro for the
+/// encoding of the label.
+pub use ic_kit_macros::Label;
+
 /// Any value that can be used as a label in the [`HashTree`] and can be a key
 /// in the [`RbTree`].
 ///
@@ -165,3 +169,256 @@ macro_rules! impl_num {
 }
 
 impl_num!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, usize, isize);
+
+/// Append the label of a non-final tuple element to the buffer in an order-preserving way, every
+/// `0x00` in the label is escaped as `0x00 0xFF` and the label is terminated by `0x00 0x00`.
+///
+/// The terminator is smaller than any byte of a longer label, so a label that is a prefix of the
+/// other one still sorts first, and no encoded label is a prefix of another one, so the element
+/// that follows can never change the ordering decided by the elements before it.
+///
+/// This is also used by the code generated by `#[derive(Label)]`.
+#[doc(hidden)]
+#[inline]
+pub fn append_escaped(buffer: &mut Vec<u8>, label: &[u8]) {
+    for &byte in label {
+        buffer.push(byte);
+        if byte == 0 {
+            buffer.push(0xFF);
+        }
+    }
+
+    buffer.extend_from_slice(&[0, 0]);
+}
+
+/// Composite keys, every element except the last one is escaped and terminated so the ordering
+/// of the labels matches the ordering of the tuples, see [`append_escaped`].
+impl<A: Label, B: Label> Label for (A, B) {
+    fn as_label(&self) -> Cow<[u8]> {
+        let a = self.0.as_label();
+        let b = self.1.as_label();
+
+        let mut buffer = Vec::with_capacity(2 + a.len() + b.len());
+        append_escaped(&mut buffer, &a);
+        buffer.extend_from_slice(&b);
+
+        Cow::Owned(buffer)
+    }
+}
+
+/// See the implementation for `(A, B)`.
+impl<A: Label, B: Label, C: Label> Label for (A, B, C) {
+    fn as_label(&self) -> Cow<[u8]> {
+        let a = self.0.as_label();
+        let b = self.1.as_label();
+        let c = self.2.as_label();
+
+        let mut buffer = Vec::with_capacity(4 + a.len() + b.len() + c.len());
+        append_escaped(&mut buffer, &a);
+        append_escaped(&mut buffer, &b);
+        buffer.extend_from_slice(&c);
+
+        Cow::Owned(buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AsHashTree, Map};
+
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n; 10])
+    }
+
+    #[derive(Label, PartialEq, Eq, PartialOrd, Ord)]
+    struct UserId(Principal);
+
+    #[derive(Label, PartialEq, Eq, PartialOrd, Ord)]
+    struct Key {
+        user: Principal,
+        index: u64,
+    }
+
+    #[derive(Label, PartialEq, Eq, PartialOrd, Ord)]
+    enum Event {
+        Created,
+        Transfer(u32, u8),
+        Burn { amount: u64 },
+    }
+
+    #[test]
+    fn derive() {
+        assert_eq!(UserId(principal(1)).as_label(), principal(1).as_label());
+
+        let key = Key {
+            user: principal(1),
+            index: 7,
+        };
+        assert_eq!(key.as_label(), (principal(1), 7u64).as_label());
+
+        assert_eq!(Event::Created.as_label().as_ref(), &[0]);
+        assert_eq!(
+            Event::Transfer(1, 2).as_label().as_ref(),
+            &[1, 0, 0xFF, 0, 0xFF, 0, 0xFF, 1, 0, 0, 2]
+        );
+        assert_eq!(
+            Event::Burn { amount: 1 }.as_label().as_ref(),
+            &[2, 0, 0, 0, 0, 0, 0, 0, 1]
+        );
+
+        let mut map = Map::<Key, u64>::new();
+        map.insert(key, 1);
+        assert_eq!(
+            map.get(&Key {
+                user: principal(1),
+                index: 7
+            }),
+            Some(&1)
+        );
+    }
+
+    #[derive(Label, PartialEq, Eq, PartialOrd, Ord)]
+    struct Path {
+        namespace: String,
+        name: String,
+        version: u32,
+    }
+
+    #[derive(Label, PartialEq, Eq, PartialOrd, Ord)]
+    enum Entry {
+        File(String, u64),
+        Directory { name: String },
+    }
+
+    #[test]
+    fn derive_string_ordering() {
+        let path = |namespace: &str, name: &str, version| Path {
+            namespace: namespace.into(),
+            name: name.into(),
+            version,
+        };
+
+        let mut paths = vec![
+            path("", "z", 0),
+            path("a", "", 9),
+            path("a", "b", 0),
+            path("a", "b\0", 0),
+            path("a", "ba", 0),
+            path("a\0", "", 0),
+            path("aa", "", 0),
+            path("b", "", 0),
+        ];
+        paths.sort();
+
+        for pair in paths.windows(2) {
+            assert!(pair[0].as_label() < pair[1].as_label());
+        }
+
+        let mut entries = vec![
+            Entry::File("a".into(), 1),
+            Entry::File("a".into(), 2),
+            Entry::File("aa".into(), 0),
+            Entry::File("b".into(), 0),
+            Entry::Directory { name: "".into() },
+            Entry::Directory { name: "a".into() },
+        ];
+        entries.sort();
+
+        for pair in entries.windows(2) {
+            assert!(pair[0].as_label() < pair[1].as_label());
+        }
+    }
+
+    #[test]
+    fn tuple_ordering() {
+        let mut keys = vec![
+            (principal(1), 0u64),
+            (principal(1), 1),
+            (principal(1), 255),
+            (principal(1), 256),
+            (principal(1), u64::MAX),
+            (principal(2), 0),
+            (principal(2), 7),
+        ];
+        keys.sort();
+
+        for pair in keys.windows(2) {
+            assert!(pair[0].as_label() < pair[1].as_label());
+        }
+
+        let triple_a = (1u8, principal(1), 9u32);
+        let triple_b = (1u8, principal(1), 10u32);
+        let triple_c = (2u8, principal(0), 0u32);
+        assert!(triple_a.as_label() < triple_b.as_label());
+        assert!(triple_b.as_label() < triple_c.as_label());
+    }
+
+    #[test]
+    fn string_tuple_ordering() {
+        let mut keys = vec![
+            (String::from(""), 9u64),
+            (String::from("a"), 5),
+            (String::from("a\0"), 0),
+            (String::from("a\0\0"), 0),
+            (String::from("a\x01"), 0),
+            (String::from("aa"), 0),
+            (String::from("aa"), 1),
+            (String::from("b"), 0),
+        ];
+        keys.sort();
+
+        for pair in keys.windows(2) {
+            assert!(pair[0].as_label() < pair[1].as_label());
+        }
+
+        let triple_a = (String::from("a"), String::from("z"), 0u8);
+        let triple_b = (String::from("aa"), String::from(""), 0u8);
+        let triple_c = (String::from("b"), String::from(""), 0u8);
+        assert!(triple_a.as_label() < triple_b.as_label());
+        assert!(triple_b.as_label() < triple_c.as_label());
+
+        let mut map = Map::<(String, u64), u64>::new();
+        for (i, (name, index)) in keys.iter().enumerate() {
+            map.insert((name.clone(), *index), i as u64);
+        }
+
+        let witness = map
+            .as_tree()
+            .key_range(&(String::from("a"), 0), &(String::from("aa"), 0));
+        assert_eq!(witness.reconstruct(), map.root_hash());
+
+        let labels = witness.get_labels();
+        for key in &keys[1..6] {
+            assert!(labels.contains(&key.as_label().as_ref()));
+        }
+        assert!(!labels.contains(&(String::from("b"), 0).as_label().as_ref()));
+    }
+
+    #[test]
+    fn tuple_range_witness() {
+        let mut map = Map::<(Principal, u64), u64>::new();
+
+        for i in (0..20u64).rev() {
+            map.insert((principal(1), i), i);
+            map.insert((principal(2), i), i + 100);
+        }
+
+        let witness = map
+            .as_tree()
+            .key_range(&(principal(1), 5), &(principal(1), 8));
+        assert_eq!(witness.reconstruct(), map.root_hash());
+
+        let labels = witness.get_labels();
+        let mut sorted = labels.clone();
+        sorted.sort();
+        assert_eq!(labels, sorted);
+
+        for i in 5..=8u64 {
+            let label = (principal(1), i).as_label();
+            assert!(labels.contains(&label.as_ref()));
+        }
+
+        assert!(!labels.contains(&(principal(2), 5).as_label().as_ref()));
+    }
+}
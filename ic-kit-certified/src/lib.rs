@@ -1,13 +1,18 @@
+// Allow the derive macros to refer to this crate as `ic_kit_certified` from within the crate.
+extern crate self as ic_kit_certified;
+
 pub mod as_hash_tree;
+pub mod certified_data;
 pub mod collections;
 pub mod hashtree;
 pub mod label;
 pub mod rbtree;
 
 pub use as_hash_tree::AsHashTree;
+pub use certified_data::CertifiedData;
 pub use collections::group::builder::GroupBuilder;
 pub use collections::group::Group;
 pub use collections::map::Map;
 pub use collections::paged::Paged;
 pub use collections::seq::Seq;
-pub use hashtree::{Hash, HashTree};
+pub use hashtree::{Hash, HashTree, LookupResult};
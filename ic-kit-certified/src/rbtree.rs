@@ -228,7 +228,13 @@ impl<K: 'static + Label, V: AsHashTree + 'static> Node<K, V> {
             return Empty.reconstruct();
         }
 
-        let h = Node::data_hash(n);
+        Node::combine_hash(n, Node::data_hash(n))
+    }
+
+    /// Compute the subtree hash of the node from the given data hash of the node and the subtree
+    /// hashes of its children.
+    unsafe fn combine_hash(n: *mut Self, h: Hash) -> Hash {
+        debug_assert!(!n.is_null());
 
         match ((*n).left.is_null(), (*n).right.is_null()) {
             (true, true) => h,
@@ -282,6 +288,18 @@ impl<K: 'static + Label, V: AsHashTree + 'static> RbTree<K, V> {
         self.root.is_null()
     }
 
+    /// Return an iterator over the key-values in the ascending order of the keys.
+    #[inline]
+    pub fn iter(&self) -> iterator::RbTreeIterator<K, V> {
+        iterator::RbTreeIterator::new(self)
+    }
+
+    /// Return an iterator over the key-values in the descending order of the keys.
+    #[inline]
+    pub fn iter_rev(&self) -> std::iter::Rev<iterator::RbTreeIterator<K, V>> {
+        self.iter().rev()
+    }
+
     pub fn entry(&mut self, key: K) -> entry::Entry<K, V> {
         let node = unsafe { self.get_node(&key) };
 
@@ -945,6 +963,102 @@ impl<K: 'static + Label, V: AsHashTree + 'static> RbTree<K, V> {
         }
     }
 
+    /// Build a tree from entries that are sorted by their key in strictly increasing order, the
+    /// tree is built bottom-up, so unlike repeated calls to [`RbTree::insert`] every node is
+    /// allocated and hashed exactly once, without any rotations.
+    ///
+    /// The shape of the tree, and therefore its root hash, can be different from a tree that has
+    /// the same entries but was built using insertions.
+    ///
+    /// If the entries are not sorted or contain duplicate keys, they are inserted one by one
+    /// instead, and the last value of a duplicate key wins.
+    pub fn bulk_load<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        /// Build a subtree with the given number of entries and black height, every path from
+        /// the root of a subtree to its leaves goes through `black_height` black nodes, and each
+        /// red node is the left child of a black node, so the tree is a valid left-leaning
+        /// red-black tree.
+        unsafe fn build<K: 'static + Label, V: AsHashTree + 'static>(
+            entries: &mut impl Iterator<Item = (K, V)>,
+            len: usize,
+            black_height: u32,
+        ) -> *mut Node<K, V> {
+            if len == 0 {
+                return Node::null();
+            }
+
+            // Each child has at most 3^(h - 1) - 1 entries, which is when all of its nodes are
+            // 3-nodes, and at least 2^(h - 1) - 1 entries.
+            let child_max = 3usize.saturating_pow(black_height - 1) - 1;
+
+            if len <= 2 * child_max + 1 {
+                // A 2-node: one black node with two children.
+                let left_len = (len - 1) / 2;
+                let left = build(entries, left_len, black_height - 1);
+                let node = next(entries, left, Color::Black);
+                (*node).right = build(entries, len - 1 - left_len, black_height - 1);
+                (*node).subtree_hash = Node::combine_hash(node, (*node).subtree_hash);
+                node
+            } else {
+                // A 3-node: a black node with a red left child and three children in total.
+                let child_len = (len - 2) / 3;
+                let extra = (len - 2) % 3;
+                let left = build(entries, child_len + (extra > 0) as usize, black_height - 1);
+                let red = next(entries, left, Color::Red);
+                (*red).right = build(entries, child_len + (extra > 1) as usize, black_height - 1);
+                (*red).subtree_hash = Node::combine_hash(red, (*red).subtree_hash);
+                let node = next(entries, red, Color::Black);
+                (*node).right = build(entries, child_len, black_height - 1);
+                (*node).subtree_hash = Node::combine_hash(node, (*node).subtree_hash);
+                node
+            }
+        }
+
+        /// Create a node for the next entry with the given left child, the subtree hash of the
+        /// node is its data hash until the right child is set.
+        unsafe fn next<K: 'static + Label, V: AsHashTree + 'static>(
+            entries: &mut impl Iterator<Item = (K, V)>,
+            left: *mut Node<K, V>,
+            color: Color,
+        ) -> *mut Node<K, V> {
+            let (key, value) = entries
+                .next()
+                .expect("The iterator should have the exact number of entries.");
+            let node = Node::new(key, value);
+            (*node).left = left;
+            (*node).color = color;
+            node
+        }
+
+        let entries = entries.into_iter().collect::<Vec<_>>();
+
+        if entries.windows(2).any(|w| w[0].0 >= w[1].0) {
+            let mut tree = Self::new();
+            for (key, value) in entries {
+                tree.insert(key, value);
+            }
+            return tree;
+        }
+
+        let len = entries.len();
+        let mut black_height = 0;
+        while 3usize.saturating_pow(black_height) - 1 < len {
+            black_height += 1;
+        }
+
+        unsafe {
+            let root = build(&mut entries.into_iter(), len, black_height);
+
+            #[cfg(test)]
+            debug_assert!(
+                is_balanced(root),
+                "the tree is not balanced:\n{:?}",
+                DebugView(root)
+            );
+
+            Self { len, root }
+        }
+    }
+
     /// Removes the specified key from the map.
     #[inline]
     pub fn delete<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
@@ -952,6 +1066,62 @@ impl<K: 'static + Label, V: AsHashTree + 'static> RbTree<K, V> {
         K: Borrow<Q>,
         Q: Ord,
     {
+        self.delete_with(|k| key.cmp(k.borrow()))
+    }
+
+    /// Removes all of the keys in the range `[first, last]` from the map and returns the number
+    /// of the removed entries.
+    pub fn delete_range<Q: ?Sized>(&mut self, first: &Q, last: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        let mut count = 0;
+
+        loop {
+            // Find the smallest key in the range.
+            let label = unsafe {
+                let mut node = self.root;
+                let mut candidate = Node::null();
+
+                while !node.is_null() {
+                    if (*node).key.borrow() < first {
+                        node = (*node).right;
+                    } else {
+                        candidate = node;
+                        node = (*node).left;
+                    }
+                }
+
+                if candidate.is_null() || (*candidate).key.borrow() > last {
+                    break;
+                }
+
+                (*candidate).key.as_label().into_owned()
+            };
+
+            // The key is the smallest key that is not less than `first`, so any other key in
+            // that range is greater than it. The labels of the keys are unique.
+            self.delete_with(|k| {
+                if k.borrow() < first {
+                    Greater
+                } else if &*k.as_label() == label.as_slice() {
+                    Equal
+                } else {
+                    Less
+                }
+            });
+
+            count += 1;
+        }
+
+        count
+    }
+
+    /// Removes the key for which the given function returns [`Equal`] from the map, the function
+    /// must return the ordering of the key being removed relative to the provided key and be
+    /// consistent with the ordering of the keys in the tree, otherwise the tree is corrupted.
+    fn delete_with(&mut self, cmp: impl Fn(&K) -> Ordering) -> Option<(K, V)> {
         unsafe fn move_red_left<K: 'static + Label, V: AsHashTree + 'static>(
             mut h: *mut Node<K, V>,
         ) -> *mut Node<K, V> {
@@ -1002,25 +1172,24 @@ impl<K: 'static + Label, V: AsHashTree + 'static> RbTree<K, V> {
             balance(h)
         }
 
-        unsafe fn go<K: 'static + Label, V: AsHashTree + 'static, Q: ?Sized>(
+        unsafe fn go<K: 'static + Label, V: AsHashTree + 'static, F>(
             mut h: *mut Node<K, V>,
             result: &mut Option<(K, V)>,
-            key: &Q,
+            cmp: &F,
         ) -> *mut Node<K, V>
         where
-            K: Borrow<Q>,
-            Q: Ord,
+            F: Fn(&K) -> Ordering,
         {
-            if key < (*h).key.borrow() {
+            if cmp(&(*h).key) == Less {
                 if !is_red((*h).left) && !is_red((*(*h).left).left) {
                     h = move_red_left(h);
                 }
-                (*h).left = go((*h).left, result, key);
+                (*h).left = go((*h).left, result, cmp);
             } else {
                 if is_red((*h).left) {
                     h = rotate_right(h);
                 }
-                if key == (*h).key.borrow() && (*h).right.is_null() {
+                if cmp(&(*h).key) == Equal && (*h).right.is_null() {
                     debug_assert!((*h).left.is_null());
                     *result = Some(Node::delete(h).unwrap());
                     return Node::null();
@@ -1030,13 +1199,13 @@ impl<K: 'static + Label, V: AsHashTree + 'static> RbTree<K, V> {
                     h = move_red_right(h);
                 }
 
-                if key == (*h).key.borrow() {
+                if cmp(&(*h).key) == Equal {
                     let m = min((*h).right);
                     std::mem::swap(&mut (*h).key, &mut (*m).key);
                     std::mem::swap(&mut (*h).value, &mut (*m).value);
                     (*h).right = delete_min((*h).right, result);
                 } else {
-                    (*h).right = go((*h).right, result, key);
+                    (*h).right = go((*h).right, result, cmp);
                 }
             }
             (*h).subtree_hash = Node::subtree_hash(h);
@@ -1044,13 +1213,13 @@ impl<K: 'static + Label, V: AsHashTree + 'static> RbTree<K, V> {
         }
 
         unsafe {
-            self.get(key)?;
+            self.get_with(&cmp)?;
             if !is_red((*self.root).left) && !is_red((*self.root).right) {
                 (*self.root).color = Color::Red;
             }
 
             let mut result = None;
-            self.root = go(self.root, &mut result, key);
+            self.root = go(self.root, &mut result, &cmp);
             if !self.root.is_null() {
                 (*self.root).color = Color::Black;
             }
@@ -1066,7 +1235,7 @@ impl<K: 'static + Label, V: AsHashTree + 'static> RbTree<K, V> {
             debug_assert!(result.is_some());
             self.len -= 1;
 
-            debug_assert!(self.get(key).is_none());
+            debug_assert!(self.get_with(&cmp).is_none());
             result
         }
     }
@@ -1,13 +1,30 @@
 use super::{Node, RbTree};
 use crate::label::Label;
 use crate::AsHashTree;
+use std::borrow::Borrow;
 use std::marker::PhantomData;
 
-/// An iterator over key-values in a RbTree.
+/// An iterator over key-values in a RbTree, the entries are yielded in the ascending order of
+/// their keys, and in the descending order when iterating from the back.
+///
+/// Both of the directions take O(1) amortized time per step, and the iteration ends once the two
+/// directions meet, so each entry is yielded exactly once.
 pub struct RbTreeIterator<'tree, K: 'static + Label, V: AsHashTree + 'static> {
     visit: *mut Node<K, V>,
     stack: Vec<*mut Node<K, V>>,
+    /// The node whose right subtree should be visited next when iterating from the back.
+    back_visit: *mut Node<K, V>,
+    back_stack: Vec<*mut Node<K, V>>,
+    /// The first node that should be visited, null if the iterator is not bounded from below.
+    first: *mut Node<K, V>,
+    /// The last node that should be visited, null if the iterator is not bounded from above.
+    last: *mut Node<K, V>,
+    /// The last nodes yielded from the front and the back, used to detect when they meet.
+    front_yielded: *mut Node<K, V>,
+    back_yielded: *mut Node<K, V>,
+    /// The number of remaining elements, for ranges this is only an upper bound.
     remaining_elements: usize,
+    is_range: bool,
     lifetime: PhantomData<&'tree RbTree<K, V>>,
 }
 
@@ -16,10 +33,108 @@ impl<'tree, K: 'static + Label, V: AsHashTree + 'static> RbTreeIterator<'tree, K
         Self {
             visit: tree.root,
             stack: Vec::with_capacity(8),
+            back_visit: tree.root,
+            back_stack: Vec::with_capacity(8),
+            first: Node::null(),
+            last: Node::null(),
+            front_yielded: Node::null(),
+            back_yielded: Node::null(),
             remaining_elements: tree.len(),
+            is_range: false,
             lifetime: PhantomData::default(),
         }
     }
+
+    /// Create an iterator over the key-values with a key in the range `[first, last]`, the
+    /// iterator is empty if `first > last`.
+    pub fn range<Q: ?Sized>(tree: &'tree RbTree<K, V>, first: &Q, last: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        let mut iter = Self::range_from(tree, first);
+        let mut back_stack = Vec::with_capacity(8);
+
+        unsafe {
+            // Push the path to the largest key that is not greater than `last`, skipping the
+            // nodes that are out of the range along with their right subtree.
+            let mut node = tree.root;
+
+            while !node.is_null() {
+                if (*node).key.borrow() > last {
+                    node = (*node).left;
+                } else {
+                    back_stack.push(node);
+                    node = (*node).right;
+                }
+            }
+
+            match (iter.stack.last(), back_stack.last()) {
+                (Some(&start), Some(&end)) if (*start).key.borrow() <= last => {
+                    iter.last = end;
+                    iter.back_visit = Node::null();
+                    iter.back_stack = back_stack;
+                }
+                _ => iter.finish(),
+            }
+        }
+
+        iter
+    }
+
+    /// Create an iterator over the key-values with a key greater than or equal to `first`.
+    pub fn range_from<Q: ?Sized>(tree: &'tree RbTree<K, V>, first: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        let mut stack = Vec::with_capacity(8);
+
+        // Push the path to the smallest key not less than `first`, skipping the nodes that are
+        // out of the range along with their left subtree.
+        unsafe {
+            let mut node = tree.root;
+
+            while !node.is_null() {
+                if (*node).key.borrow() < first {
+                    node = (*node).right;
+                } else {
+                    stack.push(node);
+                    node = (*node).left;
+                }
+            }
+        }
+
+        let mut iter = Self {
+            visit: Node::null(),
+            first: stack.last().copied().unwrap_or_else(Node::null),
+            stack,
+            back_visit: tree.root,
+            back_stack: Vec::with_capacity(8),
+            last: Node::null(),
+            front_yielded: Node::null(),
+            back_yielded: Node::null(),
+            remaining_elements: tree.len(),
+            is_range: true,
+            lifetime: PhantomData::default(),
+        };
+
+        if iter.first.is_null() {
+            iter.finish();
+        }
+
+        iter
+    }
+
+    /// Stop the iteration in both of the directions.
+    #[inline]
+    fn finish(&mut self) {
+        self.visit = Node::null();
+        self.stack.clear();
+        self.back_visit = Node::null();
+        self.back_stack.clear();
+        self.remaining_elements = 0;
+    }
 }
 
 impl<'tree, K: 'static + Label, V: AsHashTree + 'static> Iterator for RbTreeIterator<'tree, K, V> {
@@ -33,19 +148,63 @@ impl<'tree, K: 'static + Label, V: AsHashTree + 'static> Iterator for RbTreeIter
                 self.visit = (*self.visit).left;
             }
 
-            if let Some(node) = self.stack.pop() {
-                self.visit = (*node).right;
-                self.remaining_elements -= 1;
-                return Some((&(*node).key, &(*node).value));
+            let node = self.stack.pop()?;
+
+            if node == self.back_yielded {
+                self.finish();
+                return None;
             }
 
-            None
+            self.visit = (*node).right;
+            self.remaining_elements -= 1;
+            self.front_yielded = node;
+
+            if node == self.last {
+                self.finish();
+            }
+
+            Some((&(*node).key, &(*node).value))
         }
     }
 
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.remaining_elements, Some(self.remaining_elements))
+        if self.is_range {
+            (0, Some(self.remaining_elements))
+        } else {
+            (self.remaining_elements, Some(self.remaining_elements))
+        }
+    }
+}
+
+impl<'tree, K: 'static + Label, V: AsHashTree + 'static> DoubleEndedIterator
+    for RbTreeIterator<'tree, K, V>
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        unsafe {
+            while !self.back_visit.is_null() {
+                self.back_stack.push(self.back_visit);
+                self.back_visit = (*self.back_visit).right;
+            }
+
+            let node = self.back_stack.pop()?;
+
+            if node == self.front_yielded {
+                self.finish();
+                return None;
+            }
+
+            self.back_visit = (*node).left;
+            self.remaining_elements -= 1;
+            self.back_yielded = node;
+
+            if node == self.first {
+                self.finish();
+            }
+
+            Some((&(*node).key, &(*node).value))
+        }
     }
 }
 
@@ -72,4 +231,99 @@ mod tests {
 
         assert_eq!(expected_v, 250);
     }
+
+    #[test]
+    fn range() {
+        let mut tree = RbTree::<[u8; 1], u8>::new();
+
+        for i in (0..250u8).step_by(2) {
+            tree.insert([i], i);
+        }
+
+        let values = |iter: RbTreeIterator<[u8; 1], u8>| iter.map(|(_, v)| *v).collect::<Vec<_>>();
+
+        assert_eq!(
+            values(RbTreeIterator::range(&tree, &[10], &[16])),
+            vec![10, 12, 14, 16]
+        );
+        assert_eq!(
+            values(RbTreeIterator::range(&tree, &[9], &[17])),
+            vec![10, 12, 14, 16]
+        );
+        assert_eq!(values(RbTreeIterator::range(&tree, &[11], &[11])), vec![]);
+        assert_eq!(values(RbTreeIterator::range(&tree, &[16], &[10])), vec![]);
+        assert_eq!(values(RbTreeIterator::range(&tree, &[0], &[0])), vec![0]);
+        assert_eq!(values(RbTreeIterator::range(&tree, &[250], &[255])), vec![]);
+        assert_eq!(
+            values(RbTreeIterator::range_from(&tree, &[245])),
+            vec![246, 248]
+        );
+        assert_eq!(RbTreeIterator::range_from(&tree, &[0]).count(), 125);
+        assert_eq!(RbTreeIterator::range(&tree, &[0], &[255]).count(), 125);
+    }
+
+    #[test]
+    fn rev() {
+        let mut tree = RbTree::<[u8; 1], u8>::new();
+
+        for i in (0..250u8).step_by(2) {
+            tree.insert([i], i);
+        }
+
+        let values = |iter: std::iter::Rev<RbTreeIterator<[u8; 1], u8>>| {
+            iter.map(|(_, v)| *v).collect::<Vec<_>>()
+        };
+
+        let expected = (0..250u8).step_by(2).rev().collect::<Vec<_>>();
+        assert_eq!(values(RbTreeIterator::new(&tree).rev()), expected);
+        assert_eq!(
+            values(RbTreeIterator::range(&tree, &[9], &[17]).rev()),
+            vec![16, 14, 12, 10]
+        );
+        assert_eq!(
+            values(RbTreeIterator::range(&tree, &[11], &[11]).rev()),
+            vec![]
+        );
+        assert_eq!(
+            values(RbTreeIterator::range_from(&tree, &[243]).rev()),
+            vec![248, 246, 244]
+        );
+        assert_eq!(
+            values(RbTreeIterator::range_from(&tree, &[249]).rev()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn both_ends_meet() {
+        let mut tree = RbTree::<[u8; 1], u8>::new();
+
+        for i in 0..100u8 {
+            tree.insert([i], i);
+        }
+
+        for len in 0..100u8 {
+            let mut iter = RbTreeIterator::range(&tree, &[0], &[len]);
+            let mut seen = Vec::new();
+
+            // Alternate between the two ends, every value should be yielded exactly once.
+            loop {
+                let item = if seen.len() % 2 == 0 {
+                    iter.next()
+                } else {
+                    iter.next_back()
+                };
+
+                match item {
+                    Some((_, v)) => seen.push(*v),
+                    None => break,
+                }
+            }
+
+            assert_eq!(iter.next(), None);
+            assert_eq!(iter.next_back(), None);
+            seen.sort_unstable();
+            assert_eq!(seen, (0..=len).collect::<Vec<_>>());
+        }
+    }
 }
@@ -293,3 +293,51 @@ fn test_witness_value_range() {
         vec![b"x", b"y", b"z"]
     );
 }
+
+#[test]
+fn test_bulk_load_hashes_each_node_once() {
+    use crate::hashtree::leaf_hash;
+    use crate::Hash;
+    use std::borrow::Cow;
+    use std::cell::Cell;
+
+    thread_local! {
+        static HASHED: Cell<usize> = Cell::new(0);
+    }
+
+    /// A value that counts how many times its hash is computed.
+    struct Counted(Vec<u8>);
+
+    impl AsHashTree for Counted {
+        fn root_hash(&self) -> Hash {
+            HASHED.with(|hashed| hashed.set(hashed.get() + 1));
+            leaf_hash(&self.0)
+        }
+
+        fn as_hash_tree(&self) -> HashTree<'_> {
+            HashTree::Leaf(Cow::from(self.0.as_slice()))
+        }
+    }
+
+    let entries = || {
+        (0u64..1000).map(|i| {
+            let key: Vec<u8> = i.to_be_bytes().into();
+            (key, Counted(i.to_le_bytes().into()))
+        })
+    };
+
+    let t = RbTree::bulk_load(entries());
+    assert_eq!(super::debug_alloc::count_allocated_pointers(), 1000);
+    assert_eq!(HASHED.with(Cell::get), 1000);
+    assert_eq!(t.as_hash_tree().reconstruct(), t.root_hash());
+    drop(t);
+
+    // Inserting the same entries hashes the values again as the nodes on the path are rebalanced.
+    HASHED.with(|hashed| hashed.set(0));
+    let mut t = RbTree::new();
+    for (key, value) in entries() {
+        t.insert(key, value);
+    }
+    assert_eq!(super::debug_alloc::count_allocated_pointers(), 1000);
+    assert!(HASHED.with(Cell::get) > 1000);
+}
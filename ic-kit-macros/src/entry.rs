@@ -19,6 +19,7 @@ pub enum EntryPoint {
     Heartbeat,
     Update,
     Query,
+    CompositeQuery,
 }
 
 impl std::fmt::Display for EntryPoint {
@@ -31,6 +32,7 @@ impl std::fmt::Display for EntryPoint {
             EntryPoint::Heartbeat => f.write_str("heartbeat"),
             EntryPoint::Update => f.write_str("update"),
             EntryPoint::Query => f.write_str("query"),
+            EntryPoint::CompositeQuery => f.write_str("composite_query"),
         }
     }
 }
@@ -38,7 +40,7 @@ impl std::fmt::Display for EntryPoint {
 impl EntryPoint {
     pub fn is_lifecycle(&self) -> bool {
         match &self {
-            EntryPoint::Update | EntryPoint::Query => false,
+            EntryPoint::Update | EntryPoint::Query | EntryPoint::CompositeQuery => false,
             _ => true,
         }
     }
@@ -56,6 +58,7 @@ struct Config {
     name: Option<String>,
     guard: Option<String>,
     hidden: Option<bool>,
+    manual_reply: Option<bool>,
 }
 
 /// Process a rust syntax and generate the code for processing it.
@@ -135,6 +138,13 @@ pub fn gen_entry_point_code(
             ));
         }
 
+        if attrs.manual_reply.is_some() {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("#[{}] function cannot reply manually.", entry_point),
+            ));
+        }
+
         if is_async {
             return Err(Error::new(
                 Span::call_site(),
@@ -143,16 +153,34 @@ pub fn gen_entry_point_code(
         }
     }
 
+    let manual_reply = attrs.manual_reply.unwrap_or(false);
+
+    if manual_reply && return_length > 0 {
+        return Err(Error::new(
+            signature.output.span(),
+            format!(
+                "#[{}] function with manual_reply cannot have a return value.",
+                entry_point
+            ),
+        ));
+    }
+
     let outer_function_ident = Ident::new(
         &format!("_ic_kit_canister_{}_{}", entry_point, name),
         Span::call_site(),
     );
 
     let guard = if let Some(guard_name) = attrs.guard {
-        let guard_ident = Ident::new(&guard_name, Span::call_site());
+        let guard_path = syn::parse_str::<syn::Path>(&guard_name).map_err(|_| {
+            Error::new(
+                Span::call_site(),
+                format!("'{}' is not a valid path to a guard function.", guard_name),
+            )
+        })?;
 
+        // The guard runs before the arguments are decoded.
         quote! {
-            let r: Result<(), String> = #guard_ident ();
+            let r: Result<(), String> = #guard_path ();
             if let Err(e) = r {
                 ic_kit::utils::reject(&e);
                 return;
@@ -162,6 +190,15 @@ pub fn gen_entry_point_code(
         quote! {}
     };
 
+    if let Some(name) = &attrs.name {
+        if name.is_empty() {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("#[{}] function cannot be renamed to an empty name.", entry_point),
+            ));
+        }
+    }
+
     let candid_name = attrs.name.unwrap_or_else(|| name.to_string());
     let export_name = if entry_point.is_lifecycle() {
         format!("canister_{}", entry_point)
@@ -177,9 +214,19 @@ pub fn gen_entry_point_code(
     let (mut_args, mut_types): (Vec<_>, Vec<_>) = tmp.mut_args.into_iter().unzip();
 
     // If the method does not accept any arguments, don't even read the msg_data, and if the
-    // deserialization fails, just reject the message, which is cheaper than trap.
+    // deserialization fails, just reject the message, which is cheaper than trap. The lifecycle
+    // hooks can not reject, so they trap instead, which fails the install or the upgrade.
     let arg_decode = if can_args.len() == 0 {
         quote! {}
+    } else if entry_point.is_lifecycle() {
+        quote! {
+            let bytes = ic_kit::utils::arg_data_raw();
+            let args = match ic_kit::candid::decode_args(&bytes) {
+                Ok(v) => v,
+                Err(e) => ic_kit::ic::trap(&format!("Could not decode arguments: {}", e)),
+            };
+            let ( #( #can_args, )* ) = args;
+        }
     } else {
         quote! {
             let bytes = ic_kit::utils::arg_data_raw();
@@ -203,8 +250,13 @@ pub fn gen_entry_point_code(
         }
     } else if entry_point.is_lifecycle() {
         quote! {}
+    } else if manual_reply {
+        // The method is responsible for replying to the message itself.
+        quote! {
+            let _ = result;
+        }
     } else {
-        match return_length {
+        let reply = match return_length {
             0 => quote! {
                 // Send the precomputed `encode_args(())` available in ic-kit.
                 let _ = result; // to ignore result not being used.
@@ -220,6 +272,16 @@ pub fn gen_entry_point_code(
                     .expect("Could not encode canister's response.");
                 ic_kit::utils::reply(&bytes);
             },
+        };
+
+        // The function might have already replied using `ic::reply`, in which case replying
+        // again would trap, so the return value is dropped instead.
+        quote! {
+            if ic_kit::utils::is_replied() {
+                let _ = result;
+            } else {
+                #reply
+            }
         }
     };
 
@@ -260,14 +322,15 @@ pub fn gen_entry_point_code(
     // only spawn for async methods.
     let body = if is_async {
         quote! {
-            ic_kit::ic::spawn(async {
+            ic_kit::ic::spawn(ic_kit::utils::reply_scope(async {
                 #arg_decode
                 let result = #name ( #(#args),* ).await;
                 #return_encode
-            });
+            }));
         }
     } else {
         quote! {
+            ic_kit::utils::reset_replied();
             #arg_decode
             #sync_result;
         }
@@ -87,7 +87,17 @@ pub(crate) fn declare(
     Ok(())
 }
 
-pub fn export_service(input: DeriveInput, save_candid_path: Option<syn::LitStr>) -> TokenStream {
+/// The methods declared so far in the crate, see [`take_service`].
+struct Service {
+    /// The rust names of the entry points, including the lifecycle hooks.
+    rust_methods: Vec<Ident>,
+    /// The body of a function which returns the candid of the service.
+    candid: TokenStream,
+}
+
+/// Take all of the methods declared so far and generate the code for their candid, the methods
+/// are removed so the next canister in the crate starts with an empty service.
+fn take_service() -> Result<Service, Error> {
     let methods = {
         let mut map = METHODS.lock().unwrap();
         std::mem::replace(&mut *map, BTreeMap::new())
@@ -133,7 +143,9 @@ pub fn export_service(input: DeriveInput, save_candid_path: Option<syn::LitStr>)
 
             let modes = match mode {
                 EntryPoint::Update => quote! { vec![] },
-                EntryPoint::Query => {
+                // The candid version we use has no mode for composite queries, so they are
+                // declared as queries.
+                EntryPoint::Query | EntryPoint::CompositeQuery => {
                     quote! { vec![ic_kit::candid::parser::types::FuncMode::Query] }
                 }
                 _ => unreachable!(),
@@ -165,95 +177,241 @@ pub fn export_service(input: DeriveInput, save_candid_path: Option<syn::LitStr>)
         let ty = Type::Service(service);
     };
 
-    let actor = if let Some(init) = life_cycles.remove(&EntryPoint::Init) {
-        let args = init
-            .arg_types
-            .iter()
-            .map(|t| generate_arg(quote! { init_args }, t))
-            .collect::<Vec<_>>();
-
-        quote! {
-            let mut init_args = Vec::new();
-            #(#args)*
-            let actor = Some(Type::Class(init_args, Box::new(ty)));
+    // The arguments of the canister are the arguments of the init method, the post upgrade
+    // method receives the same arguments when the canister is upgraded.
+    let init_arg_types = {
+        let init = life_cycles.remove(&EntryPoint::Init);
+        let post_upgrade = life_cycles.remove(&EntryPoint::PostUpgrade);
+
+        match (init, post_upgrade) {
+            (Some(init), Some(post_upgrade))
+                if !post_upgrade.arg_types.is_empty()
+                    && init.arg_types != post_upgrade.arg_types =>
+            {
+                return Err(Error::new(
+                    Span::call_site(),
+                    format!(
+                        "The arguments of the init method ({}) and the post_upgrade method ({}) must be the same.",
+                        init.arg_types.join(", "),
+                        post_upgrade.arg_types.join(", ")
+                    ),
+                ));
+            }
+            (Some(init), _) => init.arg_types,
+            (None, Some(post_upgrade)) => post_upgrade.arg_types,
+            (None, None) => Vec::new(),
         }
-    } else {
-        quote! { let actor = Some(ty); }
     };
 
-    let name = input.ident;
+    let init_args = init_arg_types
+        .iter()
+        .map(|t| generate_arg(quote! { init_args }, t))
+        .collect::<Vec<_>>();
 
-    let save_candid = if let Some(path) = save_candid_path {
-        quote! {
-            #[cfg(test)]
-            #[test]
-            fn ic_kit_save_candid() {
-                use ic_kit::KitCanister;
-                use std::env;
-                use std::fs;
-                use std::path::PathBuf;
-
-                let candid = #name::candid();
-                let mut path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
-                path.push(#path);
-                let dir = path.parent().unwrap();
-
-                fs::create_dir_all(dir).unwrap_or_else(|e| {
-                    panic!(
-                        "Failed to create the directory '{}': {}",
-                        dir.as_os_str().to_string_lossy(),
-                        e
-                    )
-                });
-
-                fs::write(&path, candid).unwrap_or_else(|e| {
-                    panic!(
-                        "Failed to write to the file '{}': {}",
-                        path.as_os_str().to_string_lossy(),
-                        e
-                    )
-                });
-
-                println!("Saved candid to: {}", path.as_os_str().to_string_lossy());
-            }
-        }
-    } else {
-        quote! {}
+    let actor = quote! {
+        let mut init_args = Vec::new();
+        #(#init_args)*
+        let actor = Some(Type::Class(init_args, Box::new(ty)));
+    };
+
+    Ok(Service {
+        rust_methods,
+        candid: quote! {
+            #service
+            #actor
+            let result = ic_kit::candid::bindings::candid::compile(&env.env, &actor);
+            format!("{}", result)
+        },
+    })
+}
+
+pub fn export_service(
+    input: DeriveInput,
+    save_candid_path: Option<syn::LitStr>,
+    wasm_path: Option<syn::LitStr>,
+) -> TokenStream {
+    let Service {
+        rust_methods,
+        candid,
+    } = match take_service() {
+        Ok(service) => service,
+        Err(e) => return e.to_compile_error(),
     };
 
+    let name = input.ident;
+
+    let save_candid = save_candid_path.map(|path| {
+        // Include the name of the canister so more than one canister can be defined in a module.
+        let test_name = Ident::new(
+            &format!("ic_kit_save_candid_{}", name.to_string().to_lowercase()),
+            Span::call_site(),
+        );
+
+        generate_save_candid(
+            &test_name,
+            quote! { <#name as ic_kit::KitCanister>::candid() },
+            &path,
+        )
+    });
+    let candid_query = generate_candid_query(quote! { <#name as ic_kit::KitCanister>::candid() });
+
+    let install_code = generate_install_code(&name, wasm_path);
     let metadata = generate_metadata();
 
     quote! {
         #metadata
+        #install_code
 
         impl ic_kit::KitCanister for #name {
             #[cfg(not(target_family = "wasm"))]
             fn build(canister_id: ic_kit::Principal) -> ic_kit::rt::Canister {
                 ic_kit::rt::Canister::new(canister_id)
+                    .with_method::<ic_kit::ic::CanisterGlobalTimer>()
                 #(
                     .with_method::<#rust_methods>()
                 )*
             }
 
             fn candid() -> String {
-                #service
-                #actor
-                let result = ic_kit::candid::bindings::candid::compile(&env.env, &actor);
-                format!("{}", result)
+                #candid
             }
         }
 
+        #candid_query
+        #save_candid
+    }
+}
+
+/// Generate the standalone candid export of the `export_candid!` macro, a `__export_service`
+/// function which returns the candid of every method declared before the macro.
+pub fn export_candid(save_candid_path: Option<syn::LitStr>) -> TokenStream {
+    let Service { candid, .. } = match take_service() {
+        Ok(service) => service,
+        Err(e) => return e.to_compile_error(),
+    };
+
+    let save_candid = save_candid_path.map(|path| {
+        generate_save_candid(
+            &Ident::new("ic_kit_save_candid", Span::call_site()),
+            quote! { __export_service() },
+            &path,
+        )
+    });
+    let candid_query = generate_candid_query(quote! { __export_service() });
+
+    quote! {
+        /// Returns the candid of the methods exported in this crate.
+        pub fn __export_service() -> String {
+            #candid
+        }
+
+        #candid_query
+        #save_candid
+    }
+}
+
+/// Generate the query which returns the candid of the canister on the IC.
+fn generate_candid_query(candid: TokenStream) -> TokenStream {
+    quote! {
         #[cfg(target_family = "wasm")]
         #[doc(hidden)]
         #[export_name = "canister_query __get_candid_interface_tmp_hack"]
         fn _ic_kit_canister_query___get_candid_interface_tmp_hack() {
-            let candid = #name::candid();
+            let candid = #candid;
             let bytes = ic_kit::candid::encode_one(candid)
                 .expect("Could not encode canister's response.");
             ic_kit::utils::reply(&bytes);
         }
+    }
+}
 
-        #save_candid
+/// Generate a test which writes the candid to the given path relative to the crate's manifest.
+fn generate_save_candid(test_name: &Ident, candid: TokenStream, path: &syn::LitStr) -> TokenStream {
+    quote! {
+        #[cfg(test)]
+        #[test]
+        fn #test_name() {
+            use std::env;
+            use std::fs;
+            use std::path::PathBuf;
+
+            let candid = #candid;
+            let mut path = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+            path.push(#path);
+            let dir = path.parent().unwrap();
+
+            fs::create_dir_all(dir).unwrap_or_else(|e| {
+                panic!(
+                    "Failed to create the directory '{}': {}",
+                    dir.as_os_str().to_string_lossy(),
+                    e
+                )
+            });
+
+            fs::write(&path, candid).unwrap_or_else(|e| {
+                panic!(
+                    "Failed to write to the file '{}': {}",
+                    path.as_os_str().to_string_lossy(),
+                    e
+                )
+            });
+
+            println!("Saved candid to: {}", path.as_os_str().to_string_lossy());
+        }
+    }
+}
+
+/// Generate the `install_code` method of the canister, when targeting wasm the module is included
+/// from the `wasm_path` and in the runtime the canister is registered as a fake module instead.
+fn generate_install_code(name: &Ident, wasm_path: Option<syn::LitStr>) -> TokenStream {
+    let wasm_body = match wasm_path {
+        Some(path) => quote! {
+            let wasm_module = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", #path)).to_vec();
+            config.install_code(canister_id, wasm_module).await
+        },
+        // Without a wasm module there is nothing to install on the IC.
+        None => quote! {
+            let _ = (canister_id, config);
+            Err(ic_kit::ic::CallError::Rejected(
+                ic_kit::ic::RejectionCode::CanisterError,
+                format!("The wasm_path of {} is not set.", stringify!(#name)),
+                0,
+            ))
+        },
+    };
+
+    // Each target gets its own method, so neither body refers to a binding that only exists on
+    // the other target.
+    quote! {
+        impl #name {
+            /// Install the code of this canister on the given canister id using the management
+            /// canister.
+            #[cfg(target_family = "wasm")]
+            pub async fn install_code(
+                canister_id: ic_kit::Principal,
+                config: ic_kit::InstallConfig,
+            ) -> Result<(), ic_kit::ic::CallError> {
+                #wasm_body
+            }
+
+            /// Install the code of this canister on the given canister id using the management
+            /// canister.
+            #[cfg(not(target_family = "wasm"))]
+            pub async fn install_code(
+                canister_id: ic_kit::Principal,
+                config: ic_kit::InstallConfig,
+            ) -> Result<(), ic_kit::ic::CallError> {
+                let wasm_module = concat!("ic-kit-runtime:", module_path!(), "::", stringify!(#name))
+                    .as_bytes()
+                    .to_vec();
+                ic_kit::rt::register_canister_code(
+                    wasm_module.clone(),
+                    <#name as ic_kit::KitCanister>::build,
+                );
+
+                config.install_code(canister_id, wasm_module).await
+            }
+        }
     }
 }
 
@@ -0,0 +1,192 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, Data, DeriveInput, Error, Field, Fields, GenericParam, Index, Type, TypePath,
+};
+
+/// Generate the `Label` implementation for a struct or an enum.
+///
+/// The encoding matches the tuple implementations in `ic-kit-certified`:
+///
+/// 1. A struct with a single field uses the label of that field.
+/// 2. For more fields, the labels of the fields are concatenated in the declaration order, every
+///    label except the last one is escaped and terminated by `append_escaped`.
+/// 3. An enum is encoded as the index of the variant as a single byte, followed by the fields of
+///    the variant encoded as in (2).
+///
+/// Types for which the ordering of the labels could disagree with a derived `Ord` are rejected.
+pub fn derive_label(mut input: DeriveInput) -> Result<TokenStream, Error> {
+    let name = input.ident.clone();
+
+    check_ordering(&input.data)?;
+
+    for param in input.generics.params.iter_mut() {
+        if let GenericParam::Type(param) = param {
+            param
+                .bounds
+                .push(parse_quote!(::ic_kit_certified::label::Label));
+        }
+    }
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = field_names(&data.fields);
+            let accessors = fields
+                .iter()
+                .map(|field| quote! { &self.#field })
+                .collect::<Vec<_>>();
+
+            match accessors.len() {
+                0 => quote! { ::std::borrow::Cow::Borrowed(&[]) },
+                1 => {
+                    let field = &accessors[0];
+                    quote! { ::ic_kit_certified::label::Label::as_label(#field) }
+                }
+                _ => {
+                    let append = append_fields(&accessors);
+                    quote! {
+                        let mut buffer = ::std::vec::Vec::new();
+                        #append
+                        ::std::borrow::Cow::Owned(buffer)
+                    }
+                }
+            }
+        }
+        Data::Enum(data) => {
+            if data.variants.len() > 256 {
+                return Err(Error::new(
+                    Span::call_site(),
+                    "Label can not be derived for an enum with more than 256 variants.",
+                ));
+            }
+
+            let arms = data.variants.iter().enumerate().map(|(index, variant)| {
+                let index = index as u8;
+                let ident = &variant.ident;
+                let bindings = (0..variant.fields.len())
+                    .map(|i| format_ident!("__field{}", i))
+                    .collect::<Vec<_>>();
+                let accessors = bindings.iter().map(|b| quote! { #b }).collect::<Vec<_>>();
+                let append = append_fields(&accessors);
+
+                let pattern = match &variant.fields {
+                    Fields::Named(fields) => {
+                        let names = fields.named.iter().map(|f| f.ident.as_ref().unwrap());
+                        quote! { Self::#ident { #(#names: #bindings),* } }
+                    }
+                    Fields::Unnamed(_) => quote! { Self::#ident(#(#bindings),*) },
+                    Fields::Unit => quote! { Self::#ident },
+                };
+
+                quote! {
+                    #pattern => {
+                        let mut buffer = ::std::vec![#index];
+                        #append
+                        ::std::borrow::Cow::Owned(buffer)
+                    }
+                }
+            });
+
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(Error::new(
+                Span::call_site(),
+                "Label can not be derived for unions.",
+            ));
+        }
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::ic_kit_certified::label::Label for #name #ty_generics #where_clause {
+            #[allow(unused_mut)]
+            fn as_label(&self) -> ::std::borrow::Cow<[u8]> {
+                #body
+            }
+        }
+    })
+}
+
+/// Return the tokens to access each of the fields, either the name or the index of the field.
+fn field_names(fields: &Fields) -> Vec<TokenStream> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = Index::from(i);
+                quote! { #index }
+            }
+        })
+        .collect()
+}
+
+/// Reject the types whose labels would not be ordered the same way as a derived `Ord` orders
+/// them: enums with explicit discriminants, since `Ord` follows the discriminants while the label
+/// uses the declaration order, and fields of a signed integer type, since the big-endian bytes of a
+/// negative number are larger than the ones of a positive number.
+fn check_ordering(data: &Data) -> Result<(), Error> {
+    let fields: Vec<&Field> = match data {
+        Data::Struct(data) => data.fields.iter().collect(),
+        Data::Enum(data) => {
+            if let Some(variant) = data.variants.iter().find(|v| v.discriminant.is_some()) {
+                return Err(Error::new_spanned(
+                    variant,
+                    "Label can not be derived for an enum with explicit discriminants.",
+                ));
+            }
+
+            data.variants.iter().flat_map(|v| v.fields.iter()).collect()
+        }
+        Data::Union(_) => return Ok(()),
+    };
+
+    for field in fields {
+        if let Type::Path(TypePath { qself: None, path }) = &field.ty {
+            let is_signed = path.segments.last().map_or(false, |segment| {
+                matches!(
+                    segment.ident.to_string().as_str(),
+                    "i8" | "i16" | "i32" | "i64" | "i128" | "isize"
+                )
+            });
+
+            if is_signed {
+                return Err(Error::new_spanned(
+                    &field.ty,
+                    "Label can not be derived for a type with a signed integer field, the labels \
+                     would not be ordered like the numbers.",
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Append the labels of the given fields to `buffer`, all but the last one are escaped.
+fn append_fields(fields: &[TokenStream]) -> TokenStream {
+    let appends = fields.iter().enumerate().map(|(i, field)| {
+        let is_last = i == fields.len() - 1;
+        let append = if is_last {
+            quote! { buffer.extend_from_slice(&label); }
+        } else {
+            quote! { ::ic_kit_certified::label::append_escaped(&mut buffer, &label); }
+        };
+
+        quote! {
+            {
+                let label = ::ic_kit_certified::label::Label::as_label(#field);
+                #append
+            }
+        }
+    });
+
+    quote! { #(#appends)* }
+}
@@ -7,6 +7,7 @@ use test::gen_test_code;
 
 mod entry;
 mod export_service;
+mod label;
 mod metadata;
 mod test;
 
@@ -51,18 +52,55 @@ pub fn heartbeat(attr: TokenStream, item: TokenStream) -> TokenStream {
 }
 
 /// Export an update method for the canister.
+///
+/// # Arguments
+///
+/// - `name`: The name of the method in the canister's interface, by default the name of the
+///   function is used.
+/// - `guard`: The path to a `fn() -> Result<(), String>` that is called before the arguments are
+///   decoded, the message is rejected with the returned error if it fails.
+/// - `hidden`: Do not include the method in the generated candid.
+/// - `manual_reply`: Do not reply with the return value of the function, the function must not
+///   have a return value and should reply using `ic::reply` or `ic::reply_raw` itself.
+///
+/// A function without `manual_reply` can still reply early using `ic::reply`, in which case its
+/// return value is dropped instead of being sent as a second reply.
 #[proc_macro_attribute]
 pub fn update(attr: TokenStream, item: TokenStream) -> TokenStream {
     process_entry_point(EntryPoint::Update, attr, item)
 }
 
 /// Export a query method for the canister.
+///
+/// Accepts the same arguments as [`macro@update`].
 #[proc_macro_attribute]
 pub fn query(attr: TokenStream, item: TokenStream) -> TokenStream {
     process_entry_point(EntryPoint::Query, attr, item)
 }
 
+/// Export a composite query method for the canister, unlike a query a composite query can
+/// call the query methods of other canisters.
+///
+/// Just like a query, any changes made to the state of the canister during a composite query
+/// are discarded on the IC, so it must not be used to mutate the state.
+///
+/// Accepts the same arguments as [`macro@update`].
+#[proc_macro_attribute]
+pub fn composite_query(attr: TokenStream, item: TokenStream) -> TokenStream {
+    process_entry_point(EntryPoint::CompositeQuery, attr, item)
+}
+
 /// A macro to generate IC-Kit tests.
+///
+/// The test function must be async and takes a `Replica` as its only argument, the starting
+/// time of the replica in nanoseconds and the starting cycle balance of the canisters can be set
+/// using the `time` and `cycles` arguments, the canisters then keep the cycles they spend and
+/// receive:
+///
+/// ```ignore
+/// #[kit_test(time = 1_700_000_000_000_000_000, cycles = 1_000_000_000)]
+/// async fn test(replica: Replica) {}
+/// ```
 #[proc_macro_attribute]
 pub fn kit_test(attr: TokenStream, item: TokenStream) -> TokenStream {
     gen_test_code(attr.into(), item.into())
@@ -70,27 +108,107 @@ pub fn kit_test(attr: TokenStream, item: TokenStream) -> TokenStream {
         .into()
 }
 
-#[proc_macro_derive(KitCanister, attributes(candid_path))]
+/// Derive the `KitCanister` trait for a struct, which can be used to create an instance of the
+/// canister in the runtime and to get the candid of the canister.
+///
+/// The canister consists of every method that is exported in the crate using the entry point
+/// macros before this derive, so it must come after all of the methods of the canister.
+///
+/// With the `candid_path` attribute a test is generated which writes the candid to the given
+/// path relative to the crate's manifest when `cargo test` is run:
+///
+/// ```ignore
+/// #[derive(KitCanister)]
+/// #[candid_path("candid.did")]
+/// pub struct CounterCanister;
+/// ```
+///
+/// The derive also generates an `install_code` method which installs the canister on a canister
+/// id created through the management canister. In the runtime the canister is registered as a fake
+/// wasm module, on the IC the module is included from the `wasm_path` attribute and the method
+/// returns an error without it:
+///
+/// ```ignore
+/// #[derive(KitCanister)]
+/// #[wasm_path("../../target/wasm32-unknown-unknown/release/counter.wasm")]
+/// pub struct CounterCanister;
+///
+/// CounterCanister::install_code(id, InstallConfig::new(InstallMode::Install)).await?;
+/// ```
+#[proc_macro_derive(KitCanister, attributes(candid_path, wasm_path))]
 pub fn kit_export(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as syn::DeriveInput);
-    let save_candid_path_result = get_save_candid_path(&input);
+    let save_candid_path_result = get_path_attribute(&input, "candid_path");
+    let wasm_path_result = get_path_attribute(&input, "wasm_path");
 
-    match save_candid_path_result {
-        Ok(save_candid_path) => export_service::export_service(input, save_candid_path).into(),
-        Err(e) => e.to_compile_error().into(),
+    match (save_candid_path_result, wasm_path_result) {
+        (Ok(save_candid_path), Ok(wasm_path)) => {
+            export_service::export_service(input, save_candid_path, wasm_path).into()
+        }
+        (Err(e), _) | (_, Err(e)) => e.to_compile_error().into(),
     }
 }
 
-fn get_save_candid_path(input: &syn::DeriveInput) -> syn::Result<Option<syn::LitStr>> {
-    let candid_path_helper_attribute_option = input
-        .attrs
-        .iter()
-        .find(|attr| attr.path.is_ident("candid_path"));
+/// Export the candid of the crate without a canister struct, this collects every method that
+/// is exported in the crate using the entry point macros before it, so it must be invoked at the
+/// crate root after all of the methods.
+///
+/// The macro defines a `__export_service` function which returns the candid and on the IC the
+/// `__get_candid_interface_tmp_hack` query, just like the `KitCanister` derive does. An optional path
+/// can be given to generate a test which writes the candid to the path relative to the crate's
+/// manifest when `cargo test` is run:
+///
+/// ```ignore
+/// #[query]
+/// fn name() -> String {
+///     "ic-kit".to_string()
+/// }
+///
+/// export_candid!("candid.did");
+/// ```
+///
+/// The methods collected by this macro are not part of a canister derived after it, so only one
+/// of them should be used in a crate.
+#[proc_macro]
+pub fn export_candid(input: TokenStream) -> TokenStream {
+    let save_candid_path = if input.is_empty() {
+        None
+    } else {
+        Some(parse_macro_input!(input as syn::LitStr))
+    };
+
+    export_service::export_candid(save_candid_path).into()
+}
+
+/// Derive the `Label` trait from `ic-kit-certified` for a struct or an enum.
+///
+/// The label is encoded the same way as a tuple of the fields:
+///
+/// - A struct with a single field (e.g. a newtype) has the same label as its field.
+/// - With more fields, the labels of the fields are concatenated in the declaration order and all
+///   of them except the last one are escaped and terminated in an order-preserving way.
+/// - An enum variant is encoded as its index in the declaration as a single byte, followed by its
+///   fields encoded as above, so an enum can have at most 256 variants.
+///
+/// So the ordering of the labels matches a derived `Ord`. The derive is rejected when that can not
+/// hold: for enums with explicit discriminants, and for fields of a signed integer type whose
+/// big-endian bytes do not sort like the numbers.
+#[proc_macro_derive(Label)]
+pub fn derive_label(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+
+    label::derive_label(input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn get_path_attribute(input: &syn::DeriveInput, name: &str) -> syn::Result<Option<syn::LitStr>> {
+    let path_helper_attribute_option = input.attrs.iter().find(|attr| attr.path.is_ident(name));
 
-    match candid_path_helper_attribute_option {
-        Some(candid_path_helper_attribute) => {
-            let custom_candid_path_lit: syn::LitStr = candid_path_helper_attribute.parse_args()?;
-            Ok(Some(custom_candid_path_lit))
+    match path_helper_attribute_option {
+        Some(path_helper_attribute) => {
+            let custom_path_lit: syn::LitStr = path_helper_attribute.parse_args()?;
+            Ok(Some(custom_path_lit))
         }
         None => Ok(None),
     }
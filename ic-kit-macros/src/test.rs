@@ -1,9 +1,53 @@
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+use serde_tokenstream::from_tokenstream;
 use syn::spanned::Spanned;
 use syn::{parse2, Error, ItemFn};
 
-pub fn gen_test_code(_: TokenStream, item: TokenStream) -> Result<TokenStream, Error> {
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// The time in nanoseconds that the replica's clock should start from.
+    time: Option<u64>,
+    /// The cycle balance of the canisters during each message.
+    #[serde(default, deserialize_with = "deserialize_cycles")]
+    cycles: Option<u128>,
+}
+
+/// Read the cycles as any integer, so the value does not depend on whether the literal is visited
+/// as a 64 or a 128 bit integer.
+fn deserialize_cycles<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u128>, D::Error> {
+    struct CyclesVisitor;
+
+    impl<'de> Visitor<'de> for CyclesVisitor {
+        type Value = u128;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("a non-negative number of cycles")
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<u128, E> {
+            Ok(v as u128)
+        }
+
+        fn visit_u128<E: de::Error>(self, v: u128) -> Result<u128, E> {
+            Ok(v)
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<u128, E> {
+            u128::try_from(v).map_err(|_| E::custom("The cycles can not be negative."))
+        }
+    }
+
+    deserializer.deserialize_any(CyclesVisitor).map(Some)
+}
+
+pub fn gen_test_code(attr: TokenStream, item: TokenStream) -> Result<TokenStream, Error> {
+    let config = from_tokenstream::<Config>(&attr)?;
     let fun: ItemFn = parse2::<ItemFn>(item.clone()).map_err(|e| {
         Error::new(
             item.span(),
@@ -26,6 +70,11 @@ pub fn gen_test_code(_: TokenStream, item: TokenStream) -> Result<TokenStream, E
         ));
     }
 
+    let time = config.time.map(|time| quote! { .with_time(#time) });
+    let cycles = config
+        .cycles
+        .map(|cycles| quote! { .with_initial_balance(#cycles) });
+
     Ok(quote! {
         #[test]
         #visibility fn #name() {
@@ -36,7 +85,9 @@ pub fn gen_test_code(_: TokenStream, item: TokenStream) -> Result<TokenStream, E
                 .expect("ic-kit: Could not build tokio runtime.");
 
             rt.block_on(async {
-                let replica = ic_kit::rt::replica::Replica::default();
+                let replica = ic_kit::rt::replica::Replica::default()
+                    #time
+                    #cycles;
                 #name(replica).await;
             });
         }
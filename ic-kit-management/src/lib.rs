@@ -1,7 +1,44 @@
-// TODO(qti3e Implement management interface and types.
+//! Typed interface of the IC management canister (`aaaaa-aa`).
+//!
+//! See:
+//! https://internetcomputer.org/docs/current/references/ic-interface-spec/#ic-management-canister
 
+use ic_kit::candid::types::{FuncMode, Function, Serializer, Type};
+use ic_kit::candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use ic_kit::ic::CallError;
 use ic_kit::prelude::*;
 
+/// A method of the management canister.
+pub trait Method {
+    /// The name of the method.
+    const NAME: &'static str;
+
+    /// The candid tuple of the method's arguments.
+    type Arguments: ArgumentEncoder;
+
+    /// The candid tuple of the method's response.
+    type Response: for<'de> ArgumentDecoder<'de>;
+
+    /// Create the call builder for calling this method with the given arguments.
+    fn build_call(args: Self::Arguments) -> CallBuilder {
+        CallBuilder::new(Principal::management_canister(), Self::NAME).with_args(args)
+    }
+}
+
+/// Call the given method on the management canister.
+pub async fn perform<M: Method>(args: M::Arguments) -> Result<M::Response, CallError> {
+    M::build_call(args).perform().await
+}
+
+/// Call the given method on the management canister and send the given amount of cycles with
+/// the call, this is needed for `create_canister`.
+pub async fn perform_with_payment<M: Method>(
+    args: M::Arguments,
+    payment: Cycles,
+) -> Result<M::Response, CallError> {
+    M::build_call(args).with_payment(payment).perform().await
+}
+
 #[derive(Deserialize, Debug, Clone, PartialOrd, PartialEq, CandidType)]
 pub struct CreateCanisterArgument {
     pub settings: Option<CanisterSettings>,
@@ -14,3 +51,233 @@ pub struct CanisterSettings {
     pub memory_allocation: Option<Nat>,
     pub freezing_threshold: Option<Nat>,
 }
+
+/// The settings of a canister as reported by `canister_status`.
+#[derive(Deserialize, Debug, Clone, PartialOrd, PartialEq, CandidType)]
+pub struct DefiniteCanisterSettings {
+    pub controllers: Vec<Principal>,
+    pub compute_allocation: Nat,
+    pub memory_allocation: Nat,
+    pub freezing_threshold: Nat,
+}
+
+/// The argument of the methods that only take the id of a canister.
+#[derive(Deserialize, Debug, Clone, Copy, PartialOrd, PartialEq, CandidType)]
+pub struct CanisterIdRecord {
+    pub canister_id: Principal,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialOrd, PartialEq, CandidType)]
+pub struct UpdateSettingsArgument {
+    pub canister_id: Principal,
+    pub settings: CanisterSettings,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialOrd, PartialEq, CandidType)]
+pub enum InstallMode {
+    #[serde(rename = "install")]
+    Install,
+    #[serde(rename = "reinstall")]
+    Reinstall,
+    #[serde(rename = "upgrade")]
+    Upgrade,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialOrd, PartialEq, CandidType)]
+pub struct InstallCodeArgument {
+    pub mode: InstallMode,
+    pub canister_id: Principal,
+    pub wasm_module: Vec<u8>,
+    pub arg: Vec<u8>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialOrd, PartialEq, CandidType)]
+pub enum CanisterStatusType {
+    #[serde(rename = "running")]
+    Running,
+    #[serde(rename = "stopping")]
+    Stopping,
+    #[serde(rename = "stopped")]
+    Stopped,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialOrd, PartialEq, CandidType)]
+pub struct CanisterStatusResponse {
+    pub status: CanisterStatusType,
+    pub settings: DefiniteCanisterSettings,
+    pub module_hash: Option<Vec<u8>>,
+    pub memory_size: Nat,
+    pub cycles: Nat,
+    pub idle_cycles_burned_per_day: Nat,
+}
+
+/// `create_canister : (record { settings : opt canister_settings }) -> (record { canister_id })`
+pub struct CreateCanister;
+
+impl Method for CreateCanister {
+    const NAME: &'static str = "create_canister";
+    type Arguments = (CreateCanisterArgument,);
+    type Response = (CanisterIdRecord,);
+}
+
+/// `update_settings : (record { canister_id; settings : canister_settings }) -> ()`
+pub struct UpdateSettings;
+
+impl Method for UpdateSettings {
+    const NAME: &'static str = "update_settings";
+    type Arguments = (UpdateSettingsArgument,);
+    type Response = ();
+}
+
+/// `install_code : (record { mode; canister_id; wasm_module : blob; arg : blob }) -> ()`
+pub struct InstallCode;
+
+impl Method for InstallCode {
+    const NAME: &'static str = "install_code";
+    type Arguments = (InstallCodeArgument,);
+    type Response = ();
+}
+
+/// `start_canister : (record { canister_id }) -> ()`
+pub struct StartCanister;
+
+impl Method for StartCanister {
+    const NAME: &'static str = "start_canister";
+    type Arguments = (CanisterIdRecord,);
+    type Response = ();
+}
+
+/// `stop_canister : (record { canister_id }) -> ()`
+pub struct StopCanister;
+
+impl Method for StopCanister {
+    const NAME: &'static str = "stop_canister";
+    type Arguments = (CanisterIdRecord,);
+    type Response = ();
+}
+
+/// `delete_canister : (record { canister_id }) -> ()`
+pub struct DeleteCanister;
+
+impl Method for DeleteCanister {
+    const NAME: &'static str = "delete_canister";
+    type Arguments = (CanisterIdRecord,);
+    type Response = ();
+}
+
+/// `canister_status : (record { canister_id }) -> (record { status; settings; ... })`
+pub struct CanisterStatus;
+
+impl Method for CanisterStatus {
+    const NAME: &'static str = "canister_status";
+    type Arguments = (CanisterIdRecord,);
+    type Response = (CanisterStatusResponse,);
+}
+
+/// `raw_rand : () -> (blob)`
+pub struct RawRand;
+
+impl Method for RawRand {
+    const NAME: &'static str = "raw_rand";
+    type Arguments = ();
+    type Response = (Vec<u8>,);
+}
+
+/// Return 32 bytes of randomness from the management canister, the runtime answers this call
+/// from a seeded random number generator, see `Replica::with_rng_seed`.
+pub async fn raw_rand() -> Result<Vec<u8>, CallError> {
+    perform::<RawRand>(()).await.map(|(bytes,)| bytes)
+}
+
+#[derive(Deserialize, Debug, Clone, PartialOrd, PartialEq, CandidType)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialOrd, PartialEq, CandidType)]
+pub enum HttpMethod {
+    #[serde(rename = "get")]
+    Get,
+    #[serde(rename = "head")]
+    Head,
+    #[serde(rename = "post")]
+    Post,
+}
+
+/// A HTTPS outcall made by the canister through the management canister, not to be confused
+/// with the ingress HTTP requests that are sent to a canister's `http_request` query.
+#[derive(Deserialize, Debug, Clone, PartialEq, CandidType)]
+pub struct CanisterHttpRequest {
+    pub url: String,
+    pub max_response_bytes: Option<u64>,
+    pub method: HttpMethod,
+    pub headers: Vec<HttpHeader>,
+    pub body: Option<Vec<u8>>,
+    pub transform: Option<TransformContext>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialOrd, PartialEq, CandidType)]
+pub struct CanisterHttpResponse {
+    pub status: Nat,
+    pub headers: Vec<HttpHeader>,
+    pub body: Vec<u8>,
+}
+
+/// The argument passed to the transform function of a HTTPS outcall.
+#[derive(Deserialize, Debug, Clone, PartialOrd, PartialEq, CandidType)]
+pub struct TransformArgs {
+    pub response: CanisterHttpResponse,
+    pub context: Vec<u8>,
+}
+
+/// A reference to a query method of the canister which is used to transform the response of a
+/// HTTPS outcall before consensus, it has the candid type
+/// `func (TransformArgs) -> (CanisterHttpResponse) query`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TransformFunc(pub ic_kit::candid::Func);
+
+impl CandidType for TransformFunc {
+    fn _ty() -> Type {
+        Type::Func(Function {
+            modes: vec![FuncMode::Query],
+            args: vec![TransformArgs::ty()],
+            rets: vec![CanisterHttpResponse::ty()],
+        })
+    }
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        self.0.idl_serialize(serializer)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, CandidType)]
+pub struct TransformContext {
+    pub function: TransformFunc,
+    pub context: Vec<u8>,
+}
+
+impl TransformContext {
+    /// Use the given query method of the current canister as the transform function.
+    pub fn new<S: Into<String>>(method: S, context: Vec<u8>) -> Self {
+        Self {
+            function: TransformFunc(ic_kit::candid::Func {
+                principal: ic::id(),
+                method: method.into(),
+            }),
+            context,
+        }
+    }
+}
+
+/// `http_request : (CanisterHttpRequest) -> (CanisterHttpResponse)`
+///
+/// The call must be paid for with cycles, so it should be performed using
+/// [`perform_with_payment`].
+pub struct HttpRequest;
+
+impl Method for HttpRequest {
+    const NAME: &'static str = "http_request";
+    type Arguments = (CanisterHttpRequest,);
+    type Response = (CanisterHttpResponse,);
+}
@@ -1,5 +1,8 @@
+use std::fmt;
+use std::time::Duration;
+
 use candid::utils::{ArgumentDecoder, ArgumentEncoder};
-use candid::{decode_args, decode_one, encode_args, encode_one, CandidType, Principal};
+use candid::{decode_args, decode_one, encode_args, encode_one, CandidType, IDLArgs, Principal};
 use serde::de::DeserializeOwned;
 
 use ic_kit_sys::types::{CallError, RejectionCode, CANDID_EMPTY_ARG};
@@ -16,6 +19,7 @@ pub struct CallBuilder<'a> {
     sender: Principal,
     payment: u128,
     arg: Option<Vec<u8>>,
+    entry_mode: EntryMode,
 }
 
 /// A reply by the canister.
@@ -32,6 +36,23 @@ pub enum CallReply {
     },
 }
 
+/// The error returned when the reply of a call can not be decoded, see [`CallReply::decode`].
+///
+/// Both the `Debug` and `Display` outputs contain the method name when it's known, the rejection
+/// message for a rejected call and the raw reply in hex otherwise, so an `unwrap()` gives enough
+/// information to find out what went wrong.
+#[derive(Clone)]
+pub struct DecodeError {
+    method_name: Option<String>,
+    kind: DecodeErrorKind,
+}
+
+#[derive(Clone)]
+enum DecodeErrorKind {
+    Rejected(RejectionCode, String, u128),
+    Deserialize { error: String, bytes: Vec<u8> },
+}
+
 impl<'a> CallBuilder<'a> {
     /// Create a new call builder for the given type.
     pub fn new(replica: &'a Replica, canister_id: Principal, method_name: String) -> Self {
@@ -42,6 +63,7 @@ impl<'a> CallBuilder<'a> {
             method_name,
             payment: 0,
             arg: None,
+            entry_mode: EntryMode::Update,
         }
     }
 
@@ -88,16 +110,206 @@ impl<'a> CallBuilder<'a> {
         self
     }
 
-    /// Make the call from this sender.
+    /// Make the call from this sender, this is the principal that the callee observes as
+    /// `ic::caller()`. Defaults to the anonymous principal.
+    ///
+    /// Calls that a canister makes to another canister always use the id of the calling canister
+    /// as the caller, this method is only meant to simulate calls coming from users or canisters
+    /// that are not part of the replica.
     pub fn with_caller<I: Into<Principal>>(mut self, caller: I) -> Self {
         self.sender = caller.into();
         self
     }
 
+    /// Send the call as a query, the method is executed with the restrictions of the
+    /// `canister_query` entry point, so any system api that is not available to queries traps.
+    ///
+    /// Unlike the IC the runtime does not discard the state changes made during a query call.
+    pub fn as_query(mut self) -> Self {
+        self.entry_mode = EntryMode::Query;
+        self
+    }
+
+    /// Send the call as a composite query, which can call the query methods of other canisters.
+    ///
+    /// Unlike the IC the runtime does not discard the state changes made during a composite
+    /// query call.
+    pub fn as_composite_query(mut self) -> Self {
+        self.entry_mode = EntryMode::CompositeQuery;
+        self
+    }
+
     /// Perform the call and returns the reply from the canister.
+    ///
+    /// Update calls made by the builder are treated as ingress messages, so they are first passed
+    /// to the canister's `canister_inspect_message` hook and the call is rejected if the canister
+    /// does not accept the message.
     pub async fn perform(&self) -> CallReply {
-        self.replica.perform_call(self.into()).await
+        if self.entry_mode == EntryMode::Update {
+            let inspect = Message::Request {
+                request_id: RequestId::new(),
+                env: Env::inspect_message(self.method_name.clone())
+                    .with_sender(self.sender)
+                    .with_raw_args(
+                        self.arg
+                            .clone()
+                            .unwrap_or_else(|| CANDID_EMPTY_ARG.to_vec()),
+                    ),
+            };
+
+            if let CallReply::Reject {
+                rejection_code,
+                rejection_message,
+                ..
+            } = self.replica.perform_call(self.canister_id, inspect).await
+            {
+                return CallReply::Reject {
+                    rejection_code,
+                    rejection_message,
+                    cycles_refunded: self.payment,
+                };
+            }
+        }
+
+        let message = Message::from(CanisterCall::from(self));
+        self.replica.perform_call(self.canister_id, message).await
+    }
+
+    /// Perform the call and decode the reply to a single value, the error contains the name of
+    /// the method.
+    pub async fn perform_one<T>(&self) -> Result<T, DecodeError>
+    where
+        T: DeserializeOwned + CandidType,
+    {
+        self.perform()
+            .await
+            .decode_with(Some(&self.method_name), |bytes| decode_one(bytes))
     }
+
+    /// Perform the call like [`CallBuilder::perform`] but give up waiting for the reply after the
+    /// given duration of real time, in which case a `SysTransient` rejection is returned.
+    ///
+    /// This is only meant to make the tests of a canister that never replies fail instead of
+    /// hanging, the IC itself does not time out the calls.
+    pub async fn perform_with_timeout(&self, timeout: Duration) -> CallReply {
+        match tokio::time::timeout(timeout, self.perform()).await {
+            Ok(reply) => reply,
+            Err(_) => CallReply::Reject {
+                rejection_code: RejectionCode::SysTransient,
+                rejection_message: format!(
+                    "ic-kit-runtime: The call did not finish in {:?}.",
+                    timeout
+                ),
+                cycles_refunded: self.payment,
+            },
+        }
+    }
+
+    /// Perform the call and assert that the reply decodes to the expected value, on a mismatch
+    /// the panic message contains the method, the arguments and both of the values. This is what
+    /// [`assert_reply!`](crate::assert_reply) expands to.
+    pub async fn expect_reply<T>(&self, expected: T)
+    where
+        T: DeserializeOwned + CandidType + PartialEq + fmt::Debug,
+    {
+        match self.perform_one::<T>().await {
+            Ok(value) if value == expected => {}
+            Ok(value) => panic!(
+                "The reply of '{}' with the arguments {} does not match.\n  expected: {:?}\n   decoded: {:?}",
+                self.method_name,
+                self.arguments_text(),
+                expected,
+                value
+            ),
+            Err(e) => panic!(
+                "Expected a reply from '{}' with the arguments {}, but {}",
+                self.method_name,
+                self.arguments_text(),
+                e
+            ),
+        }
+    }
+
+    /// Perform the call and assert that it is rejected with the given code, and when a message is
+    /// provided, that the rejection message contains it. This is what
+    /// [`assert_reject!`](crate::assert_reject) expands to.
+    pub async fn expect_reject(&self, code: RejectionCode, message: Option<&str>) {
+        match self.perform().await {
+            CallReply::Reply { data, .. } => panic!(
+                "Expected '{}' with the arguments {} to be rejected, but got the reply {}",
+                self.method_name,
+                self.arguments_text(),
+                candid_text(&data)
+            ),
+            CallReply::Reject {
+                rejection_code,
+                rejection_message,
+                ..
+            } => {
+                let matches = rejection_code == code
+                    && message.map_or(true, |m| rejection_message.contains(m));
+
+                assert!(
+                    matches,
+                    "The rejection of '{}' with the arguments {} does not match.\n  expected: {:?} {:?}\n  rejected: {:?} {:?}",
+                    self.method_name,
+                    self.arguments_text(),
+                    code,
+                    message.unwrap_or("*"),
+                    rejection_code,
+                    rejection_message
+                );
+            }
+        }
+    }
+
+    fn arguments_text(&self) -> String {
+        candid_text(self.arg.as_deref().unwrap_or(CANDID_EMPTY_ARG))
+    }
+}
+
+/// Format the candid encoded bytes in the candid text format, or in hex if they can not be
+/// decoded.
+fn candid_text(bytes: &[u8]) -> String {
+    match IDLArgs::from_bytes(bytes) {
+        Ok(args) => args.to_string(),
+        Err(_) => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
+
+/// Perform a call using a [`CallBuilder`] and assert that its reply decodes to the expected
+/// value, see [`CallBuilder::expect_reply`]. This must be used inside of an async context.
+///
+/// # Example
+///
+/// ```ignore
+/// assert_reply!(canister.new_call("add").with_args((1u64, 2u64)), 3u64);
+/// ```
+#[macro_export]
+macro_rules! assert_reply {
+    ($call:expr, $expected:expr $(,)?) => {
+        $call.expect_reply($expected).await
+    };
+}
+
+/// Perform a call using a [`CallBuilder`] and assert that it is rejected with the given code and
+/// optionally a message that contains the given string, see [`CallBuilder::expect_reject`]. This
+/// must be used inside of an async context.
+///
+/// # Example
+///
+/// ```ignore
+/// assert_reject!(canister.new_call("withdraw"), RejectionCode::CanisterReject);
+/// assert_reject!(canister.new_call("withdraw"), RejectionCode::CanisterReject, "not enough");
+/// ```
+#[macro_export]
+macro_rules! assert_reject {
+    ($call:expr, $code:expr $(,)?) => {
+        $call.expect_reject($code, None).await
+    };
+    ($call:expr, $code:expr, $message:expr $(,)?) => {
+        $call.expect_reject($code, Some($message)).await
+    };
 }
 
 impl CallReply {
@@ -134,25 +346,47 @@ impl CallReply {
         self.into()
     }
 
-    /// Try to decode the response to the provided candid tuple.
-    pub fn decode<T: for<'a> ArgumentDecoder<'a>>(&self) -> Result<T, CallError> {
-        let bytes = self.bytes()?;
-        match decode_args(bytes) {
-            Err(_) => Err(CallError::ResponseDeserializationError(bytes.to_vec())),
-            Ok(r) => Ok(r),
-        }
+    /// Try to decode the response to the provided candid tuple, a rejected call results in an
+    /// error so the rejection can be asserted on without a panic.
+    pub fn decode<T: for<'a> ArgumentDecoder<'a>>(&self) -> Result<T, DecodeError> {
+        self.decode_with(None, |bytes| decode_args(bytes))
     }
 
-    /// Tries to decode a single argument.
-    pub fn decode_one<T>(&self) -> Result<T, CallError>
+    /// Tries to decode a single argument, see [`CallReply::decode`].
+    pub fn decode_one<T>(&self) -> Result<T, DecodeError>
     where
         T: DeserializeOwned + CandidType,
     {
-        let bytes = self.bytes()?;
-        match decode_one(bytes) {
-            Err(_) => Err(CallError::ResponseDeserializationError(bytes.to_vec())),
-            Ok(r) => Ok(r),
-        }
+        self.decode_with(None, |bytes| decode_one(bytes))
+    }
+
+    fn decode_with<T, F>(&self, method_name: Option<&str>, f: F) -> Result<T, DecodeError>
+    where
+        F: FnOnce(&[u8]) -> candid::Result<T>,
+    {
+        let kind = match self {
+            CallReply::Reply { data, .. } => match f(data) {
+                Ok(r) => return Ok(r),
+                Err(e) => DecodeErrorKind::Deserialize {
+                    error: e.to_string(),
+                    bytes: data.clone(),
+                },
+            },
+            CallReply::Reject {
+                rejection_code,
+                rejection_message,
+                cycles_refunded,
+            } => DecodeErrorKind::Rejected(
+                *rejection_code,
+                rejection_message.clone(),
+                *cycles_refunded,
+            ),
+        };
+
+        Err(DecodeError {
+            method_name: method_name.map(String::from),
+            kind,
+        })
     }
 
     /// Return the rejection code from this call, returns `RejectionCode::NoError` when the call
@@ -202,9 +436,19 @@ impl CallReply {
         }
     }
 
-    /// Assert the response is okay.
+    /// Assert the response is okay, the panic message contains the rejection if it's not.
     pub fn assert_ok(&self) {
-        assert!(self.is_ok(), "The call was rejected.");
+        if let CallReply::Reject {
+            rejection_code,
+            rejection_message,
+            ..
+        } = self
+        {
+            panic!(
+                "The call was rejected (code={:?}): '{}'",
+                rejection_code, rejection_message
+            );
+        }
     }
 
     /// Assert the response is a rejection.
@@ -213,6 +457,89 @@ impl CallReply {
     }
 }
 
+impl DecodeError {
+    /// The name of the method that was called, only known when the call is performed using
+    /// [`CallBuilder::perform_one`].
+    pub fn method_name(&self) -> Option<&str> {
+        self.method_name.as_deref()
+    }
+
+    /// The rejection code of the call, or `None` if the call was not rejected.
+    pub fn rejection_code(&self) -> Option<RejectionCode> {
+        match &self.kind {
+            DecodeErrorKind::Rejected(code, _, _) => Some(*code),
+            DecodeErrorKind::Deserialize { .. } => None,
+        }
+    }
+
+    /// The rejection message of the call, or `None` if the call was not rejected.
+    pub fn rejection_message(&self) -> Option<&str> {
+        match &self.kind {
+            DecodeErrorKind::Rejected(_, message, _) => Some(message.as_str()),
+            DecodeErrorKind::Deserialize { .. } => None,
+        }
+    }
+
+    /// The raw reply that could not be decoded, or `None` if the call was rejected.
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match &self.kind {
+            DecodeErrorKind::Rejected(..) => None,
+            DecodeErrorKind::Deserialize { bytes, .. } => Some(bytes.as_slice()),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            DecodeErrorKind::Rejected(code, message, _) => {
+                match &self.method_name {
+                    Some(name) => write!(f, "call to '{}' was rejected", name)?,
+                    None => f.write_str("call was rejected")?,
+                }
+
+                write!(f, " (code={:?}): {}", code, message)
+            }
+            DecodeErrorKind::Deserialize { error, bytes } => {
+                f.write_str("could not decode the reply")?;
+
+                if let Some(name) = &self.method_name {
+                    write!(f, " of '{}'", name)?;
+                }
+
+                write!(f, ": {} (reply=", error)?;
+
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl fmt::Debug for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<DecodeError> for CallError {
+    fn from(e: DecodeError) -> Self {
+        match e.kind {
+            DecodeErrorKind::Rejected(code, message, refunded) => {
+                CallError::Rejected(code, message, refunded)
+            }
+            DecodeErrorKind::Deserialize { bytes, .. } => {
+                CallError::ResponseDeserializationError(bytes)
+            }
+        }
+    }
+}
+
 impl<'a> From<&'a CallReply> for Result<&'a [u8], CallError> {
     fn from(reply: &'a CallReply) -> Self {
         match reply {
@@ -220,10 +547,11 @@ impl<'a> From<&'a CallReply> for Result<&'a [u8], CallError> {
             CallReply::Reject {
                 rejection_code,
                 rejection_message,
-                ..
+                cycles_refunded,
             } => Err(CallError::Rejected(
                 *rejection_code,
                 rejection_message.clone(),
+                *cycles_refunded,
             )),
         }
     }
@@ -241,6 +569,51 @@ impl<'a> From<&'a CallBuilder<'a>> for CanisterCall {
                 .arg
                 .clone()
                 .unwrap_or_else(|| CANDID_EMPTY_ARG.to_vec()),
+            entry_mode: builder.entry_mode,
+            deadline: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_error_message() {
+        let reply = CallReply::Reject {
+            rejection_code: RejectionCode::CanisterError,
+            rejection_message: "Trapped.".into(),
+            cycles_refunded: 0,
+        };
+
+        let e = reply.decode_one::<u64>().unwrap_err();
+        assert_eq!(e.rejection_code(), Some(RejectionCode::CanisterError));
+        assert_eq!(
+            e.to_string(),
+            "call was rejected (code=CanisterError): Trapped."
+        );
+
+        let reply = CallReply::Reply {
+            data: encode_one("hello").unwrap(),
+            cycles_refunded: 0,
+        };
+
+        let e = reply
+            .decode_with(Some("greet"), |bytes| decode_one::<u64>(bytes))
+            .unwrap_err();
+        let message = format!("{:?}", e);
+        assert_eq!(e.method_name(), Some("greet"));
+        assert!(message.starts_with("could not decode the reply of 'greet': "));
+        assert!(message.ends_with("(reply=4449444c0001710568656c6c6f)"));
+        assert_eq!(reply.decode_one::<String>().unwrap(), "hello");
+    }
+
+    #[test]
+    fn candid_text_format() {
+        let bytes = encode_args((7u64, "hi")).unwrap();
+        assert_eq!(candid_text(&bytes), "(7 : nat64, \"hi\")");
+        assert_eq!(candid_text(CANDID_EMPTY_ARG), "()");
+        assert_eq!(candid_text(&[0, 1, 2]), "000102");
+    }
+}
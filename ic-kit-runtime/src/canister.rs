@@ -1,6 +1,7 @@
 use std::any::Any;
 use std::collections::{HashMap, HashSet};
 use std::panic::catch_unwind;
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 
 use candid::Principal;
@@ -16,10 +17,33 @@ use ic_kit_sys::ic0::runtime::Ic0CallHandlerProxy;
 use ic_kit_sys::types::RejectionCode;
 
 use crate::call::CallReply;
+use crate::certificate::fake_certificate;
 use crate::stable::{HeapStableMemory, StableMemoryBackend};
 use crate::types::*;
 
-const MAX_CYCLES_PER_RESPONSE: u128 = 12;
+/// The default number of cycles that a canister reserves for processing the response of each
+/// call it makes, this is about what the IC reserves for the largest possible response, the
+/// transmission of 2MiB and the execution of the callback. See
+/// [`Canister::with_max_cycles_per_response`].
+pub const DEFAULT_MAX_CYCLES_PER_RESPONSE: u128 = 4_000_000_000;
+
+/// The default maximum size of the stable memory of a canister in WebAssembly pages, this is the
+/// 400GiB limit of the IC. See [`Canister::with_stable_limit`].
+pub const DEFAULT_STABLE_LIMIT: u64 = 400 << 14;
+
+/// The number of WebAssembly pages that can be addressed using the 32-bit stable memory API.
+const MAX_STABLE32_PAGES: u64 = 1 << 16;
+
+/// The maximum length of a reject message in bytes, longer messages passed to `msg_reject` are
+/// truncated to this size, the same way the IC does.
+pub const MAX_REJECT_MESSAGE_LEN_BYTES: usize = 8 * 1024;
+
+/// The maximum timeout of a best-effort call in seconds, larger timeouts are capped to this value.
+pub const MAX_CALL_TIMEOUT_SECONDS: u32 = 300;
+
+/// A function that receives the messages a canister prints using `ic0::debug_print`, along with
+/// the id of the canister that printed them.
+pub type DebugSink = Arc<dyn Fn(Principal, String) + Send + Sync>;
 
 /// A canister that is being executed.
 pub struct Canister {
@@ -27,6 +51,9 @@ pub struct Canister {
     canister_id: Principal,
     /// Maps the name of each of exported methods to the task function.
     symbol_table: HashMap<String, fn()>,
+    /// The methods that reject the calls with the given code and message, along with the number
+    /// of the calls that are left to reject, see [`Canister::with_rejecting_method`].
+    rejecting_methods: HashMap<String, (RejectionCode, String, Option<u32>)>,
     /// The data reply that is being built for the current message. An interesting thing about the
     /// IC that I did not expect: The reply data is not preserved throughout the async context.
     /// And the reply is the first call to msg_reply that is inside a non-trapping task.
@@ -42,6 +69,8 @@ pub struct Canister {
     cycles_available_store: HashMap<IncomingRequestId, u128>,
     /// Amount of cycles accept during this message process.
     cycles_accepted: u128,
+    /// Set when `accept_message` is called during the current inspect message.
+    message_accepted: bool,
     /// Pending outgoing requests that have not been resolved yet. This is used so we know when
     /// an incoming request is finally finished so we can send the last trapping message as the
     /// response.
@@ -51,10 +80,34 @@ pub struct Canister {
     outgoing_calls: HashMap<OutgoingRequestId, RequestCallbacks>,
     /// The canister execution environment.
     env: Env,
+    /// The cycle balance of the canister once it is set via [`Canister::set_balance`], it then
+    /// takes the place of the balance in the env of every message and keeps the cycles that the
+    /// canister spends and receives.
+    balance: Option<u128>,
+    /// The number of cycles reserved for processing the response of each outgoing call.
+    max_cycles_per_response: u128,
+    /// Receives the debug messages of the canister, they are printed to the stdout if not set.
+    debug_sink: Option<DebugSink>,
     /// The stable storage backend for this canister.
     stable: Box<dyn StableMemoryBackend + Send>,
+    /// The maximum number of pages that the stable memory can grow to.
+    stable_limit: u64,
+    /// The data set by the canister via `certified_data_set`, at most 32 bytes.
+    certified_data: Vec<u8>,
+    /// The time in nanoseconds at which the global timer should be fired, zero if the timer is
+    /// not set.
+    global_timer: u64,
+    /// The current status of the canister.
+    status: CanisterStatus,
+    /// The controllers of the canister, used by `ic0::is_controller`.
+    controllers: HashSet<Principal>,
+    /// The functions used to save and load the heap state, set via [`Canister::with_snapshot`].
+    snapshot_hooks: Option<(fn() -> Vec<u8>, fn(&[u8]))>,
     /// The request id of the current incoming message.
     request_id: Option<IncomingRequestId>,
+    /// The incoming composite query calls that are not finished yet, the calls made while
+    /// processing them are sent as composite queries as well.
+    composite_queries: HashSet<IncomingRequestId>,
     /// The calls that are finalized and should be sent after this entry point's successful
     /// execution.
     call_queue: Vec<(Principal, String, RequestCallbacks, u128, Vec<u8>)>,
@@ -96,6 +149,60 @@ struct RequestCallbacks {
     reject: Callback,
     /// An optional cleanup callback.
     cleanup: Option<Callback>,
+    /// The timeout in seconds if this is a best-effort call.
+    timeout: Option<u32>,
+}
+
+impl RequestCallbacks {
+    /// Return true if this is a one-way call, which does not have a reply callback.
+    fn is_one_way(&self) -> bool {
+        self.reply.0 == -1
+    }
+}
+
+/// The status of a canister as reported by `ic0::canister_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanisterStatus {
+    Running,
+    /// The canister is stopped but it is still waiting for its pending calls to finish, it does
+    /// not accept any new calls in this state.
+    Stopping,
+    Stopped,
+}
+
+/// A snapshot of the bookkeeping a canister holds for calls that are not finished yet, a canister
+/// that is idle should have all of these empty.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CanisterPendingState {
+    /// Incoming requests that the canister has not responded to yet.
+    pub unanswered_requests: Vec<IncomingRequestId>,
+    /// Incoming requests that are still waiting on the result of an outgoing call.
+    pub pending_outgoing_requests: Vec<IncomingRequestId>,
+    /// Outgoing calls whose reply or reject callback has not been invoked yet.
+    pub outgoing_calls: Vec<OutgoingRequestId>,
+}
+
+/// The state of a canister at some point in time, see [`crate::handle::CanisterHandle::snapshot`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CanisterSnapshot {
+    /// The content of the stable memory, always a multiple of the page size.
+    pub stable: Vec<u8>,
+    /// The heap state returned by [`Snapshottable::snapshot`], `None` if the canister does not
+    /// implement it.
+    pub heap: Option<Vec<u8>>,
+    /// The data set by the canister via `certified_data_set`.
+    pub certified_data: Vec<u8>,
+    /// The time at which the global timer should be fired, zero if the timer is not set.
+    pub global_timer: u64,
+}
+
+impl CanisterPendingState {
+    /// Returns true if there is nothing pending.
+    pub fn is_empty(&self) -> bool {
+        self.unanswered_requests.is_empty()
+            && self.pending_outgoing_requests.is_empty()
+            && self.outgoing_calls.is_empty()
+    }
 }
 
 /// A method exported by the canister.
@@ -117,6 +224,20 @@ pub trait CanisterMethod {
     fn exported_method();
 }
 
+/// A canister that can save and load its heap state, this is used to include the heap in a
+/// [`CanisterSnapshot`], since the runtime can not access the canister's heap on its own.
+///
+/// Both functions are executed on the canister's execution thread, so they have access to the
+/// canister's storage.
+pub trait Snapshottable {
+    /// Serialize the current heap state of the canister.
+    fn snapshot() -> Vec<u8>;
+
+    /// Replace the heap state of the canister with the given bytes, which were returned by a call
+    /// to [`Snapshottable::snapshot`].
+    fn restore(bytes: &[u8]);
+}
+
 impl Canister {
     /// Create a new instance of this canister with the given id.
     pub fn new<T: Into<Principal>>(canister_id: T) -> Self {
@@ -159,16 +280,28 @@ impl Canister {
         Self {
             canister_id: canister_id.into(),
             symbol_table: HashMap::new(),
+            rejecting_methods: HashMap::new(),
             msg_reply_data: Vec::new(),
             msg_reply_senders: HashMap::new(),
             msg_reply: None,
             cycles_available_store: HashMap::new(),
             cycles_accepted: 0,
+            message_accepted: false,
             pending_outgoing_requests: HashMap::new(),
             outgoing_calls: HashMap::new(),
             env: Env::default(),
+            balance: None,
+            max_cycles_per_response: DEFAULT_MAX_CYCLES_PER_RESPONSE,
+            debug_sink: None,
             stable: Box::new(HeapStableMemory::default()),
+            stable_limit: DEFAULT_STABLE_LIMIT,
+            certified_data: Vec::new(),
+            global_timer: 0,
+            status: CanisterStatus::Running,
+            controllers: HashSet::new(),
+            snapshot_hooks: None,
             request_id: None,
+            composite_queries: HashSet::new(),
             call_queue: Vec::with_capacity(8),
             pending_call: None,
             _execution_thread_handle: execution_thread_handle,
@@ -179,30 +312,360 @@ impl Canister {
         }
     }
 
+    /// Change the id of the canister, the id determines the principal returned by `ic::id()`
+    /// and the address that the replica delivers the calls of this canister to.
+    pub fn with_id<T: Into<Principal>>(mut self, canister_id: T) -> Self {
+        self.canister_id = canister_id.into();
+        self
+    }
+
     /// Return the canister ID.
     pub fn id(&self) -> Principal {
         self.canister_id
     }
 
     /// Provide the canister with the definition of the given method.
+    ///
+    /// # Panics
+    ///
+    /// If the canister already has a method with the same export name.
     pub fn with_method<M: CanisterMethod + 'static>(mut self) -> Self {
+        self.add_method::<M>().unwrap_or_else(|e| panic!("{}", e));
+        self
+    }
+
+    /// Add the definition of the given method to the canister, returns an error if the canister
+    /// already has a method with the same export name.
+    pub fn add_method<M: CanisterMethod + 'static>(&mut self) -> Result<(), String> {
         let method_name = String::from(M::EXPORT_NAME);
         let task_fn = M::exported_method;
 
         if self.symbol_table.contains_key(&method_name) {
-            panic!("The canister already has a '{}' method.", method_name);
+            return Err(format!("The canister already has a '{}' method.", method_name));
         }
 
         self.symbol_table.insert(method_name, task_fn);
+        Ok(())
+    }
+
+    /// Provide the canister with a method that rejects every call with the given rejection code
+    /// and message, this can be used to mock the failures of another canister. The method takes
+    /// precedence over the methods defined using [`Canister::with_method`].
+    pub fn with_rejecting_method<S: Into<String>, M: Into<String>>(
+        mut self,
+        method_name: S,
+        rejection_code: RejectionCode,
+        rejection_message: M,
+    ) -> Self {
+        self.rejecting_methods.insert(
+            method_name.into(),
+            (rejection_code, rejection_message.into(), None),
+        );
+        self
+    }
+
+    /// Like [`Canister::with_rejecting_method`] but only the first `times` calls to the method are
+    /// rejected, the calls after that go to the method defined using [`Canister::with_method`].
+    /// This can be used to mock a canister that fails temporarily.
+    pub fn with_rejecting_method_times<S: Into<String>, M: Into<String>>(
+        mut self,
+        method_name: S,
+        times: u32,
+        rejection_code: RejectionCode,
+        rejection_message: M,
+    ) -> Self {
+        self.rejecting_methods.insert(
+            method_name.into(),
+            (rejection_code, rejection_message.into(), Some(times)),
+        );
+        self
+    }
+
+    /// Return the rejection for a call to the given method if it should be rejected, see
+    /// [`Canister::with_rejecting_method`].
+    fn take_rejection(&mut self, method_name: &str) -> Option<(RejectionCode, String)> {
+        let (rejection_code, rejection_message, remaining) =
+            self.rejecting_methods.get_mut(method_name)?;
+
+        match remaining {
+            Some(0) => return None,
+            Some(n) => *n -= 1,
+            None => {}
+        }
+
+        Some((*rejection_code, rejection_message.clone()))
+    }
+
+    /// Set the controllers of the canister, the canister does not have any controllers by default.
+    pub fn with_controllers(mut self, controllers: Vec<Principal>) -> Self {
+        self.controllers = controllers.into_iter().collect();
+        self
+    }
+
+    /// Return the controllers of the canister.
+    pub fn controllers(&self) -> Vec<Principal> {
+        self.controllers.iter().copied().collect()
+    }
+
+    /// Use the given type to save and load the heap state of the canister when a snapshot is
+    /// taken or restored, without it the snapshots only contain the system state.
+    pub fn with_snapshot<S: Snapshottable>(mut self) -> Self {
+        self.snapshot_hooks = Some((S::snapshot, S::restore));
         self
     }
 
+    /// Capture the current state of the canister, this must be called in between the messages.
+    pub(crate) async fn snapshot(&mut self) -> Result<CanisterSnapshot, String> {
+        let mut stable = vec![0u8; (self.stable.stable_size() << 16) as usize];
+        self.stable.stable_read(0, &mut stable);
+
+        let heap = match self.snapshot_hooks {
+            Some((snapshot, _)) => {
+                let output = Arc::new(Mutex::new(None));
+                let task_output = output.clone();
+
+                let task = Box::new(move || {
+                    *task_output.lock().unwrap() = Some(snapshot());
+                });
+
+                if let Completion::Panicked(e) = self.perform(task).await {
+                    return Err(format!("Canister panicked during snapshot: {}", e));
+                }
+
+                let bytes = output.lock().unwrap().take();
+                bytes
+            }
+            None => None,
+        };
+
+        Ok(CanisterSnapshot {
+            stable,
+            heap,
+            certified_data: self.certified_data.clone(),
+            global_timer: self.global_timer,
+        })
+    }
+
+    /// Replace the state of the canister with the given snapshot, this must be called in between
+    /// the messages. The stable memory is restored to a [`HeapStableMemory`] backend.
+    pub(crate) async fn restore(&mut self, snapshot: CanisterSnapshot) -> Result<(), String> {
+        if snapshot.stable.len() % (1 << 16) != 0 {
+            return Err("The size of the stable memory is not a multiple of the page size.".into());
+        }
+
+        let mut stable = HeapStableMemory::default();
+        let pages = (snapshot.stable.len() >> 16) as u64;
+        if stable.stable_grow(pages) < 0 {
+            return Err(format!(
+                "Could not grow the stable memory to {} pages.",
+                pages
+            ));
+        }
+        stable.stable_write(0, &snapshot.stable);
+
+        if let Some(heap) = snapshot.heap {
+            let restore = match self.snapshot_hooks {
+                Some((_, restore)) => restore,
+                None => return Err("The canister does not implement Snapshottable.".into()),
+            };
+
+            let task = Box::new(move || restore(&heap));
+
+            if let Completion::Panicked(e) = self.perform(task).await {
+                return Err(format!("Canister panicked during restore: {}", e));
+            }
+        }
+
+        self.stable = Box::new(stable);
+        self.certified_data = snapshot.certified_data;
+        self.global_timer = snapshot.global_timer;
+
+        Ok(())
+    }
+
+    /// Take the stable storage of this canister and leave it with an empty one, this is used to
+    /// move the stable memory to the new instance of the canister during an upgrade.
+    pub(crate) fn take_stable(&mut self) -> Box<dyn StableMemoryBackend + Send> {
+        std::mem::replace(&mut self.stable, Box::new(HeapStableMemory::default()))
+    }
+
     /// Provide the canister with this stable storage backend.
     pub fn with_stable(mut self, stable: Box<dyn StableMemoryBackend + Send>) -> Self {
         self.stable = stable;
         self
     }
 
+    /// Set the maximum size of the stable memory in WebAssembly pages, defaults to
+    /// [`DEFAULT_STABLE_LIMIT`]. Growing the stable memory past this size fails and returns `-1`
+    /// to the canister, which can be used to test how a canister handles running out of stable
+    /// memory. The 32-bit API is also limited to 4GiB regardless of this limit.
+    pub fn with_stable_limit(mut self, pages: u64) -> Self {
+        self.stable_limit = pages;
+        self
+    }
+
+    /// Return the maximum size of the stable memory in WebAssembly pages, see
+    /// [`Canister::with_stable_limit`].
+    pub fn stable_limit(&self) -> u64 {
+        self.stable_limit
+    }
+
+    /// Return the current size of the stable memory in WebAssembly pages.
+    pub fn stable_pages(&mut self) -> u64 {
+        self.stable.stable_size()
+    }
+
+    /// Grow the stable memory by the given number of pages unless it would exceed the given
+    /// limit, returns the previous size in pages or `-1` on failure.
+    fn grow_stable_within(&mut self, new_pages: u64, limit: u64) -> i64 {
+        let size = self.stable.stable_size();
+
+        match size.checked_add(new_pages) {
+            Some(new_size) if new_size <= limit => self.stable.stable_grow(new_pages),
+            _ => -1,
+        }
+    }
+
+    /// Read the given range of the stable memory directly from the backend, returns an error if
+    /// the range is out of the bounds of the stable memory.
+    pub fn stable_read_raw(&mut self, offset: u64, len: usize) -> Result<Vec<u8>, String> {
+        self.check_stable_range(offset, len)?;
+        let mut buf = vec![0u8; len];
+        self.stable.stable_read(offset, &mut buf);
+        Ok(buf)
+    }
+
+    /// Write the given bytes to the stable memory directly on the backend, returns an error if
+    /// the range is out of the bounds of the stable memory.
+    pub fn stable_write_raw(&mut self, offset: u64, bytes: &[u8]) -> Result<(), String> {
+        self.check_stable_range(offset, bytes.len())?;
+        self.stable.stable_write(offset, bytes);
+        Ok(())
+    }
+
+    fn check_stable_range(&mut self, offset: u64, len: usize) -> Result<(), String> {
+        let size = self.stable.stable_size() << 16;
+
+        match offset.checked_add(len as u64) {
+            Some(end) if end <= size => Ok(()),
+            _ => Err(format!(
+                "Stable memory access out of bounds (offset={}, len={}, size={}).",
+                offset, len, size
+            )),
+        }
+    }
+
+    /// Return the cycle balance of the canister. Unless it is set via [`Canister::set_balance`]
+    /// this is the balance of the env of the last message.
+    pub fn balance(&self) -> u128 {
+        self.balance.unwrap_or(self.env.balance)
+    }
+
+    /// Set the cycle balance of the canister, from now on the balance is kept by the canister
+    /// and overrides the balance of the env of the incoming messages.
+    pub fn set_balance(&mut self, balance: u128) {
+        self.balance = Some(balance);
+    }
+
+    /// Add the given amount of cycles to the canister's balance, see [`Canister::set_balance`].
+    pub fn add_cycles(&mut self, amount: u128) {
+        self.balance = Some(self.balance().saturating_add(amount));
+    }
+
+    /// Set the number of cycles that the canister reserves for processing the response of each
+    /// call it makes, defaults to [`DEFAULT_MAX_CYCLES_PER_RESPONSE`]. A call can not be made if
+    /// the balance is lower than this amount. The reservation of a one-way call is refunded once
+    /// the call is performed, the reservation of the other calls is refunded when their reply or
+    /// reject callback runs.
+    pub fn with_max_cycles_per_response(mut self, cycles: u128) -> Self {
+        self.max_cycles_per_response = cycles;
+        self
+    }
+
+    /// Return the number of cycles reserved for processing the response of each call, see
+    /// [`Canister::with_max_cycles_per_response`].
+    pub fn max_cycles_per_response(&self) -> u128 {
+        self.max_cycles_per_response
+    }
+
+    /// Send the messages printed by the canister using `ic0::debug_print` to the given function
+    /// instead of the stdout, this can be used to capture the logs of a canister in a test. This
+    /// takes precedence over the sink of the replica, see [`Replica::with_debug_sink`].
+    ///
+    /// [`Replica::with_debug_sink`]: crate::Replica::with_debug_sink
+    pub fn with_debug_sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(Principal, String) + Send + Sync + 'static,
+    {
+        self.debug_sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Return the debug sink of the canister if it has one.
+    pub(crate) fn debug_sink(&self) -> Option<DebugSink> {
+        self.debug_sink.clone()
+    }
+
+    /// Use the given debug sink unless the canister already has one.
+    pub(crate) fn inherit_debug_sink(&mut self, sink: DebugSink) {
+        self.debug_sink.get_or_insert(sink);
+    }
+
+    /// Return the current status of the canister.
+    pub fn status(&self) -> CanisterStatus {
+        self.status
+    }
+
+    /// Stop the canister, new calls to the canister are rejected from now on and the canister
+    /// goes to the `Stopped` status once all of its pending calls are finished.
+    pub fn stop(&mut self) {
+        if self.status == CanisterStatus::Running {
+            self.status = CanisterStatus::Stopping;
+        }
+
+        self.update_status();
+    }
+
+    /// Start the canister so it can accept new calls again.
+    pub fn start(&mut self) {
+        self.status = CanisterStatus::Running;
+    }
+
+    /// Move a stopping canister to the `Stopped` status if it has nothing pending.
+    pub(crate) fn update_status(&mut self) {
+        if self.status == CanisterStatus::Stopping && self.pending_state().is_empty() {
+            self.status = CanisterStatus::Stopped;
+        }
+    }
+
+    /// Deactivate the global timer and return true if it has expired by the given time.
+    pub(crate) fn take_expired_global_timer(&mut self, time: u64) -> bool {
+        if self.global_timer == 0 || self.global_timer > time {
+            return false;
+        }
+
+        self.global_timer = 0;
+        true
+    }
+
+    /// Return a snapshot of the requests and calls that are not finished on this canister.
+    pub fn pending_state(&self) -> CanisterPendingState {
+        let mut unanswered_requests: Vec<_> = self.msg_reply_senders.keys().copied().collect();
+        let mut pending_outgoing_requests: Vec<_> =
+            self.pending_outgoing_requests.keys().copied().collect();
+        let mut outgoing_calls: Vec<_> = self.outgoing_calls.keys().copied().collect();
+
+        unanswered_requests.sort();
+        pending_outgoing_requests.sort();
+        outgoing_calls.sort();
+
+        CanisterPendingState {
+            unanswered_requests,
+            pending_outgoing_requests,
+            outgoing_calls,
+        }
+    }
+
     pub async fn process_message(
         &mut self,
         message: Message,
@@ -213,6 +676,11 @@ impl Canister {
         self.discard_call_queue();
         self.request_id = None;
         self.cycles_accepted = 0;
+        self.message_accepted = false;
+
+        // The cycles reserved for processing the response of the call that this message is
+        // responding to, they are refunded since the runtime does not charge for the processing.
+        let mut reservation = 0;
 
         // Assign the request_id for this message.
         let (request_id, env, task) = match message {
@@ -246,6 +714,54 @@ impl Canister {
                         && env.entry_mode != EntryMode::CustomTask
                 );
 
+                // A canister that is not running only processes the replies to its own calls and
+                // the install/upgrade hooks.
+                if self.status != CanisterStatus::Running
+                    && env.entry_mode != EntryMode::Init
+                    && env.entry_mode != EntryMode::PreUpgrade
+                    && env.entry_mode != EntryMode::PostUpgrade
+                {
+                    let status = match self.status {
+                        CanisterStatus::Stopping => "stopping",
+                        _ => "stopped",
+                    };
+
+                    reply_sender
+                        .unwrap()
+                        .send(CallReply::Reject {
+                            rejection_code: RejectionCode::SysTransient,
+                            rejection_message: format!(
+                                "Canister {} is {}",
+                                self.canister_id, status
+                            ),
+                            cycles_refunded: env.cycles_available,
+                        })
+                        .expect("ic-kit-runtime: Could not send the message reply.");
+
+                    return Vec::new();
+                }
+
+                let rejection = match env.entry_mode {
+                    EntryMode::Update | EntryMode::Query | EntryMode::CompositeQuery => env
+                        .method_name
+                        .as_ref()
+                        .and_then(|name| self.take_rejection(name)),
+                    _ => None,
+                };
+
+                if let Some((rejection_code, rejection_message)) = rejection {
+                    reply_sender
+                        .unwrap()
+                        .send(CallReply::Reject {
+                            rejection_code,
+                            rejection_message,
+                            cycles_refunded: env.cycles_available,
+                        })
+                        .expect("ic-kit-runtime: Could not send the message reply.");
+
+                    return Vec::new();
+                }
+
                 let entry_point_name = env.get_entry_point_name();
                 let task = self
                     .symbol_table
@@ -265,6 +781,11 @@ impl Canister {
                     "ic-kit-runtime: No outgoing message with the given id on this canister.",
                 );
 
+                // The reservation of the one-way calls is already refunded in `call_perform`.
+                if !callbacks.is_one_way() {
+                    reservation = self.max_cycles_per_response;
+                }
+
                 let id = callbacks.message_id;
                 let _clean_callbacks = callbacks.cleanup;
 
@@ -298,6 +819,19 @@ impl Canister {
             }
         };
 
+        if task.is_none() && env.entry_mode == EntryMode::InspectMessage {
+            // A canister without an inspect message hook accepts every message.
+            reply_sender
+                .unwrap()
+                .send(CallReply::Reply {
+                    data: Vec::new(),
+                    cycles_refunded: 0,
+                })
+                .expect("ic-kit-runtime: Could not send the message reply.");
+
+            return Vec::new();
+        }
+
         if task.is_none() {
             let chan = reply_sender.unwrap();
 
@@ -318,17 +852,26 @@ impl Canister {
 
         self.request_id = Some(request_id);
         self.env = env;
+
+        if let Some(balance) = self.balance {
+            self.env.balance = balance;
+        }
+
         self.env.cycles_available = *self
             .cycles_available_store
             .entry(request_id)
             .or_insert(self.env.cycles_available);
-        self.env.balance += self.env.cycles_refunded;
+        self.env.balance += self.env.cycles_refunded + reservation;
 
         if let Some(sender) = reply_sender {
             self.msg_reply_senders
                 .insert(self.request_id.unwrap(), sender);
         }
 
+        if self.env.entry_mode == EntryMode::CompositeQuery {
+            self.composite_queries.insert(request_id);
+        }
+
         let completion = self.perform(task.unwrap()).await;
 
         match completion {
@@ -343,6 +886,24 @@ impl Canister {
                 self.maybe_final_reply(Some(m), self.env.cycles_available);
             }
             Completion::Ok => {
+                if self.env.entry_mode == EntryMode::InspectMessage {
+                    let reply = if self.message_accepted {
+                        CallReply::Reply {
+                            data: Vec::new(),
+                            cycles_refunded: 0,
+                        }
+                    } else {
+                        CallReply::Reject {
+                            rejection_code: RejectionCode::CanisterReject,
+                            rejection_message: "The message was not accepted by the canister."
+                                .to_string(),
+                            cycles_refunded: 0,
+                        }
+                    };
+
+                    self.msg_reply = Some(reply);
+                }
+
                 if let Some(reply) = self.msg_reply.take() {
                     let chan = self
                         .msg_reply_senders
@@ -357,10 +918,21 @@ impl Canister {
             }
         };
 
+        if self.balance.is_some() {
+            self.balance = Some(self.env.balance + self.cycles_accepted);
+        }
+
         let queue = std::mem::replace(&mut self.call_queue, Vec::new());
         let mut tmp = Vec::<CanisterCall>::with_capacity(queue.len());
+        let entry_mode = match self.request_id {
+            Some(id) if self.composite_queries.contains(&id) => EntryMode::CompositeQuery,
+            _ => EntryMode::Update,
+        };
         for (callee, method, cb, payment, arg) in queue {
             let request_id = RequestId::new();
+            let deadline = cb
+                .timeout
+                .map(|timeout| self.env.time + timeout as u64 * 1_000_000_000);
 
             // Insert the pending request id for the current call.
             self.pending_outgoing_requests
@@ -378,12 +950,56 @@ impl Canister {
                 method,
                 payment,
                 arg,
+                entry_mode,
+                deadline,
             });
         }
 
         tmp
     }
 
+    /// Run the init, pre_upgrade or post_upgrade hook of the canister with the given env and
+    /// return the trap message if it traps. The hooks can not reply, so unlike
+    /// [`Canister::process_message`] this reports whether the hook succeeded, which is what
+    /// the management canister's `install_code` needs. A missing hook is a no-op.
+    pub(crate) async fn run_lifecycle_hook(&mut self, env: Env) -> Result<(), String> {
+        assert!(matches!(
+            env.entry_mode,
+            EntryMode::Init | EntryMode::PreUpgrade | EntryMode::PostUpgrade
+        ));
+
+        let f = match self.symbol_table.get(&env.get_entry_point_name()) {
+            Some(f) => *f,
+            None => return Ok(()),
+        };
+
+        self.discard_pending_call();
+        self.discard_call_queue();
+        self.request_id = Some(RequestId::new());
+        self.cycles_accepted = 0;
+        self.env = env;
+
+        if let Some(balance) = self.balance {
+            self.env.balance = balance;
+        }
+
+        let completion = self.perform(Box::new(move || f())).await;
+
+        // The hooks can not make calls, but drop anything a trap left behind.
+        self.discard_pending_call();
+        self.discard_call_queue();
+        self.request_id = None;
+
+        if self.balance.is_some() {
+            self.balance = Some(self.env.balance);
+        }
+
+        match completion {
+            Completion::Ok => Ok(()),
+            Completion::Panicked(m) => Err(m),
+        }
+    }
+
     /// Execute the given task in the execution thread and return the completion status.
     async fn perform(&mut self, task: TaskFn) -> Completion {
         // make sure we clean the task_returned receiver. since we may have sent more than one
@@ -429,6 +1045,8 @@ impl Canister {
             return;
         }
 
+        self.composite_queries.remove(&id);
+
         let chan = match self.msg_reply_senders.remove(&id) {
             Some(c) => c,
             None => return,
@@ -445,15 +1063,25 @@ impl Canister {
         .expect("ic-kit-runtime: Could not send the message reply.")
     }
 
+    /// Return the fake data certificate for the current certified data.
+    fn data_certificate(&self) -> Vec<u8> {
+        fake_certificate(&self.canister_id, &self.certified_data, self.env.time)
+    }
+
     fn discard_pending_call(&mut self) {
         if let Some(pending_call) = self.pending_call.take() {
-            self.env.balance += MAX_CYCLES_PER_RESPONSE + pending_call.3;
+            self.env.balance += self.max_cycles_per_response + pending_call.3;
         }
     }
 
     fn discard_call_queue(&mut self) {
         while let Some(pending_call) = self.call_queue.pop() {
-            self.env.balance += MAX_CYCLES_PER_RESPONSE + pending_call.3;
+            // The reservation of the one-way calls is already refunded in `call_perform`.
+            if !pending_call.2.is_one_way() {
+                self.env.balance += self.max_cycles_per_response;
+            }
+
+            self.env.balance += pending_call.3;
         }
     }
 }
@@ -465,6 +1093,7 @@ impl Ic0CallHandlerProxy for Canister {
             | EntryMode::Init
             | EntryMode::Update
             | EntryMode::Query
+            | EntryMode::CompositeQuery
             | EntryMode::ReplyCallback
             | EntryMode::InspectMessage => Ok(self.env.args.len() as isize),
             _ => Err(format!(
@@ -481,6 +1110,7 @@ impl Ic0CallHandlerProxy for Canister {
             | EntryMode::PostUpgrade
             | EntryMode::Update
             | EntryMode::Query
+            | EntryMode::CompositeQuery
             | EntryMode::ReplyCallback
             | EntryMode::InspectMessage => {
                 let data = self.env.args.as_slice();
@@ -502,6 +1132,7 @@ impl Ic0CallHandlerProxy for Canister {
             | EntryMode::PreUpgrade
             | EntryMode::Update
             | EntryMode::Query
+            | EntryMode::CompositeQuery
             | EntryMode::InspectMessage => Ok(self.env.sender.as_slice().len() as isize),
             _ => Err(format!(
                 "msg_caller_size can not be called from '{}'",
@@ -518,6 +1149,7 @@ impl Ic0CallHandlerProxy for Canister {
             | EntryMode::PreUpgrade
             | EntryMode::Update
             | EntryMode::Query
+            | EntryMode::CompositeQuery
             | EntryMode::InspectMessage => {
                 let data = self.env.sender.as_slice();
                 copy_to_canister(dst, offset, size, data)?;
@@ -578,6 +1210,7 @@ impl Ic0CallHandlerProxy for Canister {
             EntryMode::CustomTask
             | EntryMode::Update
             | EntryMode::Query
+            | EntryMode::CompositeQuery
             | EntryMode::ReplyCallback
             | EntryMode::RejectCallback => {
                 // this should always be present when processing a call.
@@ -609,6 +1242,7 @@ impl Ic0CallHandlerProxy for Canister {
             EntryMode::CustomTask
             | EntryMode::Update
             | EntryMode::Query
+            | EntryMode::CompositeQuery
             | EntryMode::ReplyCallback
             | EntryMode::RejectCallback => {
                 // this should always be present when processing a call.
@@ -647,6 +1281,7 @@ impl Ic0CallHandlerProxy for Canister {
             EntryMode::CustomTask
             | EntryMode::Update
             | EntryMode::Query
+            | EntryMode::CompositeQuery
             | EntryMode::ReplyCallback
             | EntryMode::RejectCallback => {
                 // this should always be present when processing a call.
@@ -669,7 +1304,8 @@ impl Ic0CallHandlerProxy for Canister {
         }
 
         let cycles_refunded = self.env.cycles_available;
-        let rejection_message = String::from_utf8_lossy(copy_from_canister(src, size)).into();
+        let rejection_message =
+            truncate_reject_message(String::from_utf8_lossy(copy_from_canister(src, size)).into());
         self.env.cycles_available = 0;
         self.msg_reply = Some(CallReply::Reject {
             rejection_code: RejectionCode::CanisterReject,
@@ -762,7 +1398,7 @@ impl Ic0CallHandlerProxy for Canister {
             }
         };
 
-        let amount = self.env.cycles_available.min(max_amount as u128);
+        let amount = self.env.cycles_available.min(max_amount as u64 as u128);
         self.env.cycles_available -= amount;
         self.cycles_accepted += amount;
         self.cycles_available_store
@@ -792,9 +1428,9 @@ impl Ic0CallHandlerProxy for Canister {
             }
         };
 
-        let high = max_amount_high as u128;
-        let low = max_amount_low as u128;
-        let max_amount = high << 64 + low;
+        let high = max_amount_high as u64 as u128;
+        let low = max_amount_low as u64 as u128;
+        let max_amount = (high << 64) | low;
         let amount = self.env.cycles_available.min(max_amount);
         self.env.cycles_available -= amount;
         self.cycles_accepted += amount;
@@ -833,8 +1469,18 @@ impl Ic0CallHandlerProxy for Canister {
     }
 
     fn canister_status(&mut self) -> Result<i32, String> {
-        // TODO(qti3e) support stopping canisters.
-        Ok(1)
+        Ok(match self.status {
+            CanisterStatus::Running => 1,
+            CanisterStatus::Stopping => 2,
+            CanisterStatus::Stopped => 3,
+        })
+    }
+
+    fn is_controller(&mut self, src: isize, size: isize) -> Result<i32, String> {
+        let bytes = copy_from_canister(src, size);
+        let principal = Principal::try_from_slice(bytes)
+            .map_err(|e| format!("is_controller: Invalid principal: {}", e))?;
+        Ok(self.controllers.contains(&principal) as i32)
     }
 
     fn msg_method_name_size(&mut self) -> Result<isize, String> {
@@ -882,8 +1528,20 @@ impl Ic0CallHandlerProxy for Canister {
     }
 
     fn accept_message(&mut self) -> Result<(), String> {
-        // TODO(qti3e) Hmm.. this has room for some thoughts.
-        todo!()
+        if self.env.entry_mode != EntryMode::InspectMessage {
+            return Err(format!(
+                "accept_message can not be called from '{}'",
+                self.env.get_entry_point_name()
+            ));
+        }
+
+        if self.message_accepted {
+            return Err("accept_message can only be called once.".to_string());
+        }
+
+        self.message_accepted = true;
+
+        Ok(())
     }
 
     fn call_new(
@@ -900,9 +1558,11 @@ impl Ic0CallHandlerProxy for Canister {
         match self.env.entry_mode {
             EntryMode::CustomTask
             | EntryMode::Update
+            | EntryMode::CompositeQuery
             | EntryMode::ReplyCallback
             | EntryMode::RejectCallback
-            | EntryMode::Heartbeat => {}
+            | EntryMode::Heartbeat
+            | EntryMode::GlobalTimer => {}
             _ => {
                 return Err(format!(
                     "call_new can not be called from '{}'",
@@ -913,11 +1573,11 @@ impl Ic0CallHandlerProxy for Canister {
 
         self.discard_pending_call();
 
-        if self.env.balance < MAX_CYCLES_PER_RESPONSE {
+        if self.env.balance < self.max_cycles_per_response {
             return Err("Insufficient cycles balance to process canister response.".into());
         }
 
-        self.env.balance -= MAX_CYCLES_PER_RESPONSE;
+        self.env.balance -= self.max_cycles_per_response;
 
         let callee_bytes = copy_from_canister(callee_src, callee_size);
         let name_bytes = copy_from_canister(name_src, name_size);
@@ -930,6 +1590,7 @@ impl Ic0CallHandlerProxy for Canister {
             reply: (reply_fun, reply_env),
             reject: (reject_fun, reject_env),
             cleanup: None,
+            timeout: None,
         };
 
         self.pending_call = Some((callee, name, callbacks, 0, Vec::new()));
@@ -976,7 +1637,7 @@ impl Ic0CallHandlerProxy for Canister {
             ));
         }
 
-        let amount = amount as u128;
+        let amount = amount as u64 as u128;
 
         if self.env.balance < amount {
             return Err(format!("Insufficient cycles balance."));
@@ -995,9 +1656,9 @@ impl Ic0CallHandlerProxy for Canister {
             ));
         }
 
-        let high = amount_high as u128;
-        let low = amount_low as u128;
-        let amount = high << 64 + low;
+        let high = amount_high as u64 as u128;
+        let low = amount_low as u64 as u128;
+        let amount = (high << 64) | low;
 
         if self.env.balance < amount {
             return Err(format!("Insufficient cycles balance."));
@@ -1009,6 +1670,26 @@ impl Ic0CallHandlerProxy for Canister {
         Ok(())
     }
 
+    fn call_with_best_effort_response(&mut self, timeout_seconds: i32) -> Result<(), String> {
+        if self.pending_call.is_none() {
+            return Err(format!(
+                "call_with_best_effort_response cannot be called when there is no pending call."
+            ));
+        }
+
+        let timeout = &mut self.pending_call.as_mut().unwrap().2.timeout;
+
+        if timeout.is_some() {
+            return Err(format!(
+                "call_with_best_effort_response cannot be invoked more than once."
+            ));
+        }
+
+        *timeout = Some((timeout_seconds as u32).min(MAX_CALL_TIMEOUT_SECONDS));
+
+        Ok(())
+    }
+
     fn call_perform(&mut self) -> Result<i32, String> {
         if self.pending_call.is_none() {
             return Err(format!(
@@ -1019,7 +1700,15 @@ impl Ic0CallHandlerProxy for Canister {
         // TODO(qti3e) Implement the freezing threshold + system ability to perform call.
         // For now all of the calls go through.
 
-        self.call_queue.push(self.pending_call.take().unwrap());
+        let call = self.pending_call.take().unwrap();
+
+        // There is no callback to receive the response of a one-way call, so the cycles reserved
+        // for processing the response are refunded right away.
+        if call.2.is_one_way() {
+            self.env.balance += self.max_cycles_per_response;
+        }
+
+        self.call_queue.push(call);
         Ok(0)
     }
 
@@ -1031,14 +1720,8 @@ impl Ic0CallHandlerProxy for Canister {
     }
 
     fn stable_grow(&mut self, new_pages: i32) -> Result<i32, String> {
-        let size = self.stable.stable_size() as i32;
-        let max_size = i32::max_value();
-
-        if size + new_pages > max_size {
-            Ok(-1)
-        } else {
-            Ok(self.stable.stable_grow(new_pages as u64) as i32)
-        }
+        let limit = self.stable_limit.min(MAX_STABLE32_PAGES);
+        Ok(self.grow_stable_within(new_pages as u32 as u64, limit) as i32)
     }
 
     fn stable_write(&mut self, _offset: i32, _src: isize, _size: isize) -> Result<(), String> {
@@ -1051,7 +1734,7 @@ impl Ic0CallHandlerProxy for Canister {
     fn stable_read(&mut self, dst: isize, offset: i32, size: isize) -> Result<(), String> {
         let mut buf = vec![0u8; size as usize];
         self.stable.stable_read(offset as u64, &mut buf);
-        copy_to_canister(dst, offset as isize, size, &buf)?;
+        copy_to_canister(dst, 0, size, &buf)?;
         Ok(())
     }
 
@@ -1060,7 +1743,7 @@ impl Ic0CallHandlerProxy for Canister {
     }
 
     fn stable64_grow(&mut self, new_pages: i64) -> Result<i64, String> {
-        Ok(self.stable.stable_grow(new_pages as u64) as i64)
+        Ok(self.grow_stable_within(new_pages as u64, self.stable_limit))
     }
 
     fn stable64_write(&mut self, offset: i64, src: i64, size: i64) -> Result<(), String> {
@@ -1073,43 +1756,113 @@ impl Ic0CallHandlerProxy for Canister {
     fn stable64_read(&mut self, dst: i64, offset: i64, size: i64) -> Result<(), String> {
         let mut buf = vec![0u8; size as usize];
         self.stable.stable_read(offset as u64, &mut buf);
-        copy_to_canister(dst as isize, offset as isize, size as isize, &buf)?;
+        copy_to_canister(dst as isize, 0, size as isize, &buf)?;
         Ok(())
     }
 
-    fn certified_data_set(&mut self, _src: isize, _size: isize) -> Result<(), String> {
-        todo!()
+    fn certified_data_set(&mut self, src: isize, size: isize) -> Result<(), String> {
+        match self.env.entry_mode {
+            EntryMode::CustomTask
+            | EntryMode::Init
+            | EntryMode::PreUpgrade
+            | EntryMode::PostUpgrade
+            | EntryMode::Heartbeat
+            | EntryMode::GlobalTimer
+            | EntryMode::Update
+            | EntryMode::ReplyCallback
+            | EntryMode::RejectCallback => {}
+            _ => {
+                return Err(format!(
+                    "certified_data_set can not be called from '{}'",
+                    self.env.get_entry_point_name()
+                ));
+            }
+        }
+
+        if size > 32 {
+            return Err("Certified data can not be larger than 32 bytes.".into());
+        }
+
+        self.certified_data = copy_from_canister(src, size).to_vec();
+        Ok(())
     }
 
     fn data_certificate_present(&mut self) -> Result<i32, String> {
-        todo!()
+        // Certificates are only available in non-replicated query calls.
+        match self.env.entry_mode {
+            EntryMode::Query => Ok(1),
+            _ => Ok(0),
+        }
     }
 
     fn data_certificate_size(&mut self) -> Result<isize, String> {
-        todo!()
+        match self.env.entry_mode {
+            EntryMode::Query => Ok(self.data_certificate().len() as isize),
+            _ => Err(format!(
+                "data_certificate_size can not be called from '{}'",
+                self.env.get_entry_point_name()
+            )),
+        }
     }
 
     fn data_certificate_copy(
         &mut self,
-        _dst: isize,
-        _offset: isize,
-        _size: isize,
+        dst: isize,
+        offset: isize,
+        size: isize,
     ) -> Result<(), String> {
-        todo!()
+        match self.env.entry_mode {
+            EntryMode::Query => {
+                let data = self.data_certificate();
+                copy_to_canister(dst, offset, size, &data)?;
+                Ok(())
+            }
+            _ => Err(format!(
+                "data_certificate_copy can not be called from '{}'",
+                self.env.get_entry_point_name()
+            )),
+        }
     }
 
     fn time(&mut self) -> Result<i64, String> {
         Ok(self.env.time as i64)
     }
 
-    fn performance_counter(&mut self, _counter_type: i32) -> Result<i64, String> {
+    fn global_timer_set(&mut self, timestamp: i64) -> Result<i64, String> {
+        match self.env.entry_mode {
+            EntryMode::CustomTask
+            | EntryMode::Init
+            | EntryMode::PostUpgrade
+            | EntryMode::Update
+            | EntryMode::ReplyCallback
+            | EntryMode::RejectCallback
+            | EntryMode::CleanupCallback
+            | EntryMode::Heartbeat
+            | EntryMode::GlobalTimer => {
+                let previous = self.global_timer;
+                self.global_timer = timestamp as u64;
+                Ok(previous as i64)
+            }
+            _ => Err(format!(
+                "global_timer_set can not be called from '{}'",
+                self.env.get_entry_point_name()
+            )),
+        }
+    }
+
+    fn performance_counter(&mut self, _counter_type: i32) -> Result<i64, String> {
         todo!()
     }
 
     fn debug_print(&mut self, src: isize, size: isize) -> Result<(), String> {
         let bytes = copy_from_canister(src, size);
         let message = String::from_utf8_lossy(bytes).to_string();
-        println!("canister: {}", message);
+
+        match &self.debug_sink {
+            Some(sink) => sink(self.canister_id, message),
+            None => println!("canister: {}", message),
+        }
+
         Ok(())
     }
 
@@ -1141,6 +1894,20 @@ fn copy_from_canister<'a>(src: isize, size: isize) -> &'a [u8] {
     unsafe { std::slice::from_raw_parts(src as *const u8, size) }
 }
 
+/// Truncate the given reject message to [`MAX_REJECT_MESSAGE_LEN_BYTES`], without splitting a
+/// multi-byte character.
+fn truncate_reject_message(mut message: String) -> String {
+    if message.len() > MAX_REJECT_MESSAGE_LEN_BYTES {
+        let mut len = MAX_REJECT_MESSAGE_LEN_BYTES;
+        while !message.is_char_boundary(len) {
+            len -= 1;
+        }
+        message.truncate(len);
+    }
+
+    message
+}
+
 fn downcast_panic_payload(payload: &Box<dyn Any + Send>) -> String {
     payload
         .downcast_ref::<&'static str>()
@@ -1149,3 +1916,1152 @@ fn downcast_panic_payload(payload: &Box<dyn Any + Send>) -> String {
         .or_else(|| payload.downcast_ref::<String>().cloned())
         .unwrap_or_else(|| String::from("Box<Any>"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::block_on;
+    use crate::Replica;
+    use helpers::*;
+
+    /// The fixtures shared by the tests that make calls between the canisters.
+    mod helpers {
+        use super::*;
+
+        /// An update method that accepts all of the cycles sent to it, so the response of a call
+        /// to it does not refund anything.
+        pub struct Foo;
+
+        impl CanisterMethod for Foo {
+            const EXPORT_NAME: &'static str = "canister_update foo";
+
+            fn exported_method() {
+                unsafe {
+                    let mut recv = 0u128;
+                    ic0::msg_cycles_accept128(-1, -1, &mut recv as *mut u128 as isize);
+                    ic0::msg_reply();
+                }
+            }
+        }
+
+        /// Return the cycle balance of the current canister.
+        pub fn balance() -> u128 {
+            let mut recv = 0u128;
+            unsafe {
+                ic0::canister_cycle_balance128(&mut recv as *mut u128 as isize);
+            }
+            u128::from_le(recv)
+        }
+
+        /// Reply with the rejection code of the call, zero if the call succeeds.
+        fn reply_with_code(code: u8) {
+            unsafe {
+                ic0::msg_reply_data_append(&code as *const u8 as isize, 1);
+                ic0::msg_reply();
+            }
+        }
+
+        fn on_reply(_env: isize) {
+            reply_with_code(0);
+        }
+
+        fn on_reject(_env: isize) {
+            reply_with_code(unsafe { ic0::msg_reject_code() } as u8);
+        }
+
+        /// Start a call to the `foo` method of the callee with the given payment, the current
+        /// message is replied with the rejection code of the call once its response arrives, zero
+        /// if the call succeeds.
+        pub fn new_call_foo(callee: Principal, cycles: u128) {
+            let method = "foo";
+
+            unsafe {
+                ic0::call_new(
+                    callee.as_slice().as_ptr() as isize,
+                    callee.as_slice().len() as isize,
+                    method.as_ptr() as isize,
+                    method.len() as isize,
+                    on_reply as fn(isize) as isize,
+                    0,
+                    on_reject as fn(isize) as isize,
+                    0,
+                );
+                ic0::call_cycles_add128((cycles >> 64) as u64 as i64, cycles as u64 as i64);
+            }
+        }
+
+        /// Call the `foo` method of the callee with the given payment and return the result of
+        /// `call_perform`, see [`new_call_foo`].
+        pub fn call_foo(callee: Principal, cycles: u128) -> i32 {
+            new_call_foo(callee, cycles);
+            unsafe { ic0::call_perform() }
+        }
+
+        /// Make a best-effort call to the `foo` method of [`Foo`] on the canister `2` with a 10
+        /// seconds timeout, see [`new_call_foo`].
+        pub fn best_effort_call() {
+            new_call_foo(Principal::from_slice(&[2]), 0);
+
+            unsafe {
+                ic0::call_with_best_effort_response(10);
+                ic0::call_perform();
+            }
+        }
+    }
+
+    #[test]
+    fn msg_cycles_accept128_above_u64() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+            let amount = u64::MAX as u128 * 3;
+
+            let reply = canister
+                .custom(
+                    move || unsafe {
+                        let mut recv = 0u128;
+                        ic0::msg_cycles_accept128(
+                            (amount >> 64) as u64 as i64,
+                            amount as u64 as i64,
+                            &mut recv as *mut u128 as isize,
+                        );
+                        assert_eq!(u128::from_le(recv), amount);
+                        ic0::msg_reply();
+                    },
+                    Env::default().with_cycles_available(amount + 5),
+                )
+                .await;
+
+            reply.assert_ok();
+            assert_eq!(reply.cycles_refunded(), 5);
+        });
+    }
+
+    #[test]
+    fn call_cycles_add128_above_u64() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+            let balance = u128::MAX >> 1;
+            let amount = u64::MAX as u128 + 1;
+
+            let reply = canister
+                .custom(
+                    move || unsafe {
+                        let callee = Principal::from_slice(&[2]);
+                        let method = "foo";
+                        ic0::call_new(
+                            callee.as_slice().as_ptr() as isize,
+                            callee.as_slice().len() as isize,
+                            method.as_ptr() as isize,
+                            method.len() as isize,
+                            -1,
+                            -1,
+                            -1,
+                            -1,
+                        );
+                        ic0::call_cycles_add128((amount >> 64) as u64 as i64, amount as u64 as i64);
+
+                        let mut recv = 0u128;
+                        ic0::canister_cycle_balance128(&mut recv as *mut u128 as isize);
+                        assert_eq!(
+                            u128::from_le(recv),
+                            balance - amount - DEFAULT_MAX_CYCLES_PER_RESPONSE
+                        );
+                        ic0::msg_reply();
+                    },
+                    Env::default().with_balance(balance),
+                )
+                .await;
+
+            reply.assert_ok();
+        });
+    }
+
+    #[test]
+    fn max_cycles_per_response() {
+        fn call_new() {
+            let callee = Principal::from_slice(&[2]);
+            let method = "foo";
+
+            unsafe {
+                ic0::call_new(
+                    callee.as_slice().as_ptr() as isize,
+                    callee.as_slice().len() as isize,
+                    method.as_ptr() as isize,
+                    method.len() as isize,
+                    0,
+                    0,
+                    0,
+                    0,
+                );
+            }
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(
+                Canister::new(Principal::from_slice(&[1])).with_max_cycles_per_response(50),
+            );
+
+            canister
+                .custom(
+                    || {
+                        call_new();
+                        assert_eq!(balance(), 950);
+                        unsafe { ic0::msg_reply() };
+                    },
+                    Env::default().with_balance(1000),
+                )
+                .await
+                .assert_ok();
+
+            let canister = replica.add_canister(
+                Canister::new(Principal::from_slice(&[3])).with_max_cycles_per_response(2000),
+            );
+
+            let reply = canister
+                .custom(call_new, Env::default().with_balance(1000))
+                .await;
+
+            assert!(reply
+                .rejection_message()
+                .unwrap()
+                .contains("Insufficient cycles balance to process canister response."));
+        });
+    }
+
+    #[test]
+    fn max_cycles_per_response_refund() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(
+                Canister::new(Principal::from_slice(&[1])).with_max_cycles_per_response(50),
+            );
+            replica.add_canister(Canister::new(Principal::from_slice(&[2])).with_method::<Foo>());
+            canister.set_cycles(1000).await;
+
+            let reply = canister
+                .custom(
+                    || {
+                        assert_eq!(call_foo(Principal::from_slice(&[2]), 100), 0);
+                        assert_eq!(balance(), 850);
+                    },
+                    Env::default(),
+                )
+                .await;
+            assert_eq!(reply.bytes().unwrap(), &[0]);
+
+            // The payment is kept by the callee and the unused reservation is refunded.
+            assert_eq!(canister.cycle_balance().await, 900);
+        });
+    }
+
+    #[test]
+    fn best_effort_call_timeout() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+            replica.add_canister(Canister::new(Principal::from_slice(&[2])).with_method::<Foo>());
+
+            let reply = canister.custom(best_effort_call, Env::default()).await;
+            assert_eq!(reply.bytes().unwrap(), &[0]);
+
+            // The clock moves past the deadline before the response is delivered.
+            let call = canister.custom(best_effort_call, Env::default());
+            let advance = async { replica.advance_time(std::time::Duration::from_secs(20)) };
+            let (reply, _) = tokio::join!(call, advance);
+            assert_eq!(reply.bytes().unwrap(), &[RejectionCode::SysUnknown as u8]);
+        });
+    }
+
+    #[test]
+    fn best_effort_call_without_response() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+            replica.add_unresponsive_canister(Principal::from_slice(&[2]));
+
+            let call = canister.custom(best_effort_call, Env::default());
+            let advance = async {
+                // The call is made by the time the caller runs a task queued after the request.
+                replica.with_canister(canister.canister_id, |_| ()).await;
+                replica.advance_time(std::time::Duration::from_secs(20));
+            };
+
+            let (reply, _) = tokio::join!(call, advance);
+            assert_eq!(reply.bytes().unwrap(), &[RejectionCode::SysUnknown as u8]);
+        });
+    }
+
+    #[test]
+    fn debug_sink() {
+        fn print_hello() {
+            let message = "hello";
+
+            unsafe {
+                ic0::debug_print(message.as_ptr() as isize, message.len() as isize);
+                ic0::msg_reply();
+            }
+        }
+
+        block_on(async {
+            let logs = Arc::new(Mutex::new(Vec::new()));
+            let replica_logs = logs.clone();
+            let replica = Replica::default().with_debug_sink(move |canister_id, message| {
+                replica_logs.lock().unwrap().push((canister_id, message))
+            });
+
+            let own_logs = Arc::new(Mutex::new(Vec::new()));
+            let canister_logs = own_logs.clone();
+
+            let a = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+            let b = Canister::new(Principal::from_slice(&[2]))
+                .with_debug_sink(move |_, message| canister_logs.lock().unwrap().push(message));
+            let b = replica.add_canister(b);
+
+            for canister in [&a, &b] {
+                canister
+                    .custom(print_hello, Env::default())
+                    .await
+                    .assert_ok();
+            }
+
+            // The sink of the canister takes precedence over the sink of the replica.
+            assert_eq!(
+                *logs.lock().unwrap(),
+                vec![(Principal::from_slice(&[1]), "hello".to_string())]
+            );
+            assert_eq!(*own_logs.lock().unwrap(), vec!["hello".to_string()]);
+        });
+    }
+
+    #[test]
+    fn one_way_call_refunds_response_reservation() {
+        fn one_way_call(payment: u128) {
+            let callee = Principal::from_slice(&[2]);
+            let method = "foo";
+
+            unsafe {
+                ic0::call_new(
+                    callee.as_slice().as_ptr() as isize,
+                    callee.as_slice().len() as isize,
+                    method.as_ptr() as isize,
+                    method.len() as isize,
+                    -1,
+                    -1,
+                    -1,
+                    -1,
+                );
+                ic0::call_cycles_add128((payment >> 64) as u64 as i64, payment as u64 as i64);
+                assert_eq!(ic0::call_perform(), 0);
+            }
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+            replica.add_canister(Canister::new(Principal::from_slice(&[2])).with_method::<Foo>());
+            let initial = 10 * DEFAULT_MAX_CYCLES_PER_RESPONSE;
+            canister.set_cycles(initial).await;
+
+            canister
+                .custom(
+                    move || {
+                        one_way_call(100);
+                        assert_eq!(balance(), initial - 100);
+                        unsafe { ic0::msg_reply() };
+                    },
+                    Env::default(),
+                )
+                .await
+                .assert_ok();
+
+            // A trap discards the call and refunds the payment without refunding the reservation
+            // for the second time.
+            let reply = canister
+                .custom(
+                    || {
+                        one_way_call(100);
+                        panic!("Trap after the call.");
+                    },
+                    Env::default(),
+                )
+                .await;
+
+            reply.assert_error();
+            assert_eq!(canister.cycle_balance().await, initial - 100);
+        });
+    }
+
+    #[test]
+    fn stable_limit() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica
+                .add_canister(Canister::new(Principal::from_slice(&[1])).with_stable_limit(3));
+
+            canister
+                .custom(
+                    || unsafe {
+                        assert_eq!(ic0::stable64_grow(2), 0);
+                        assert_eq!(ic0::stable64_grow(2), -1);
+                        assert_eq!(ic0::stable_grow(1), 2);
+                        assert_eq!(ic0::stable_grow(1), -1);
+                        assert_eq!(ic0::stable64_grow(0), 3);
+                        ic0::msg_reply();
+                    },
+                    Env::default(),
+                )
+                .await
+                .assert_ok();
+
+            assert_eq!(canister.stable_size().await, 3);
+        });
+    }
+
+    #[test]
+    fn stable_read_at_offset() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+
+            canister
+                .custom(
+                    || unsafe {
+                        let data = b"hello";
+                        ic0::stable_grow(1);
+                        ic0::stable_write(100, data.as_ptr() as isize, data.len() as isize);
+
+                        let mut buf = [0u8; 5];
+                        ic0::stable_read(buf.as_mut_ptr() as isize, 100, buf.len() as isize);
+                        assert_eq!(&buf, data);
+
+                        let mut buf = [0u8; 4];
+                        ic0::stable64_read(buf.as_mut_ptr() as i64, 101, buf.len() as i64);
+                        assert_eq!(&buf, b"ello");
+
+                        ic0::msg_reply();
+                    },
+                    Env::default(),
+                )
+                .await
+                .assert_ok();
+        });
+    }
+
+    #[test]
+    fn stable_raw_access() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+
+            canister
+                .custom(
+                    || unsafe {
+                        let data = b"hello";
+                        ic0::stable_grow(1);
+                        ic0::stable_write(0, data.as_ptr() as isize, data.len() as isize);
+                        ic0::msg_reply();
+                    },
+                    Env::default(),
+                )
+                .await
+                .assert_ok();
+
+            assert_eq!(canister.stable_read_raw(0, 5).await, b"hello".to_vec());
+
+            // Corrupt the second byte behind the canister's back.
+            canister.stable_write_raw(1, b"X".to_vec()).await;
+            assert_eq!(canister.stable_read_raw(0, 5).await, b"hXllo".to_vec());
+
+            let overflow = replica
+                .with_canister(canister.canister_id, |canister| {
+                    canister.stable_read_raw(u64::MAX, 2)
+                })
+                .await;
+            assert!(overflow.unwrap_err().contains("out of bounds"));
+
+            canister
+                .custom(
+                    || unsafe {
+                        let mut data = [0u8; 5];
+                        ic0::stable_read(data.as_mut_ptr() as isize, 0, data.len() as isize);
+                        assert_eq!(&data, b"hXllo");
+                        ic0::msg_reply();
+                    },
+                    Env::default(),
+                )
+                .await
+                .assert_ok();
+        });
+    }
+
+    #[test]
+    fn snapshot_restore() {
+        use std::cell::Cell;
+        use std::panic::{RefUnwindSafe, UnwindSafe};
+
+        thread_local! {
+            static COUNTER: Cell<u64> = Cell::new(0);
+        }
+
+        struct Counter;
+
+        impl Snapshottable for Counter {
+            fn snapshot() -> Vec<u8> {
+                COUNTER.with(|c| c.get()).to_le_bytes().to_vec()
+            }
+
+            fn restore(bytes: &[u8]) {
+                let value = u64::from_le_bytes(bytes.try_into().unwrap());
+                COUNTER.with(|c| c.set(value));
+            }
+        }
+
+        fn increment() {
+            COUNTER.with(|c| c.set(c.get() + 1));
+            unsafe { ic0::msg_reply() };
+        }
+
+        fn assert_counter(expected: u64) -> impl FnOnce() + Send + RefUnwindSafe + UnwindSafe {
+            move || {
+                assert_eq!(COUNTER.with(|c| c.get()), expected);
+                unsafe { ic0::msg_reply() };
+            }
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            let mut stable = HeapStableMemory::default();
+            stable.stable_grow(1);
+            let canister = replica.add_canister(
+                Canister::new(Principal::from_slice(&[1]))
+                    .with_stable(Box::new(stable))
+                    .with_snapshot::<Counter>(),
+            );
+
+            canister.custom(increment, Env::default()).await.assert_ok();
+            canister.stable_write_raw(0, b"A".to_vec()).await;
+
+            let snapshot = canister.snapshot().await;
+            assert_eq!(snapshot.stable.len(), 1 << 16);
+            assert_eq!(snapshot.heap, Some(1u64.to_le_bytes().to_vec()));
+
+            canister.custom(increment, Env::default()).await.assert_ok();
+            canister.stable_write_raw(0, b"B".to_vec()).await;
+            canister
+                .custom(assert_counter(2), Env::default())
+                .await
+                .assert_ok();
+
+            canister.restore(snapshot).await;
+            assert_eq!(canister.stable_read_raw(0, 1).await, b"A".to_vec());
+            canister
+                .custom(assert_counter(1), Env::default())
+                .await
+                .assert_ok();
+        });
+    }
+
+    #[test]
+    fn truncate_reject_message_at_limit() {
+        let message = "a".repeat(MAX_REJECT_MESSAGE_LEN_BYTES);
+        assert_eq!(truncate_reject_message(message.clone()), message);
+
+        let message = "a".repeat(MAX_REJECT_MESSAGE_LEN_BYTES + 1);
+        assert_eq!(
+            truncate_reject_message(message).len(),
+            MAX_REJECT_MESSAGE_LEN_BYTES
+        );
+    }
+
+    #[test]
+    fn truncate_reject_message_char_boundary() {
+        // The 2-byte character crosses the limit, so it should be dropped entirely.
+        let mut message = "a".repeat(MAX_REJECT_MESSAGE_LEN_BYTES - 1);
+        message.push('é');
+
+        let truncated = truncate_reject_message(message);
+        assert_eq!(truncated.len(), MAX_REJECT_MESSAGE_LEN_BYTES - 1);
+        assert!(truncated.chars().all(|c| c == 'a'));
+    }
+
+    #[test]
+    fn query_call_can_not_call_new() {
+        struct Foo;
+
+        impl CanisterMethod for Foo {
+            const EXPORT_NAME: &'static str = "canister_query foo";
+
+            fn exported_method() {
+                let callee = Principal::from_slice(&[2]);
+                let method = "bar";
+
+                unsafe {
+                    ic0::call_new(
+                        callee.as_slice().as_ptr() as isize,
+                        callee.as_slice().len() as isize,
+                        method.as_ptr() as isize,
+                        method.len() as isize,
+                        -1,
+                        -1,
+                        -1,
+                        -1,
+                    );
+                    ic0::msg_reply();
+                }
+            }
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica
+                .add_canister(Canister::new(Principal::from_slice(&[1])).with_method::<Foo>());
+
+            let reply = canister.new_call("foo").as_query().perform().await;
+            assert!(matches!(
+                reply.rejection_code(),
+                RejectionCode::CanisterError
+            ));
+            assert!(reply
+                .rejection_message()
+                .unwrap()
+                .contains("call_new can not be called from 'canister_query foo'"));
+
+            // The same method is allowed to perform calls when it is called as an update.
+            canister.new_call("foo").perform().await.assert_ok();
+        });
+    }
+
+    #[test]
+    fn cycle_balance() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+
+            canister
+                .set_cycles(DEFAULT_MAX_CYCLES_PER_RESPONSE - 1)
+                .await;
+            assert_eq!(
+                canister.cycle_balance().await,
+                DEFAULT_MAX_CYCLES_PER_RESPONSE - 1
+            );
+
+            let reply = canister
+                .custom(
+                    || {
+                        call_foo(Principal::from_slice(&[2]), 0);
+                    },
+                    Env::default(),
+                )
+                .await;
+            assert!(reply
+                .rejection_message()
+                .unwrap()
+                .contains("Insufficient cycles balance to process canister response."));
+
+            // Nothing is charged since the call could not be made.
+            assert_eq!(
+                canister.cycle_balance().await,
+                DEFAULT_MAX_CYCLES_PER_RESPONSE - 1
+            );
+
+            canister.add_cycles(1).await;
+            assert_eq!(
+                canister.cycle_balance().await,
+                DEFAULT_MAX_CYCLES_PER_RESPONSE
+            );
+
+            canister
+                .custom(
+                    || {
+                        assert_eq!(balance(), DEFAULT_MAX_CYCLES_PER_RESPONSE);
+                        unsafe { ic0::msg_reply() };
+                    },
+                    Env::default(),
+                )
+                .await
+                .assert_ok();
+        });
+    }
+
+    #[test]
+    fn replica_initial_balance() {
+        block_on(async {
+            let initial = 10 * DEFAULT_MAX_CYCLES_PER_RESPONSE;
+            let replica = Replica::default().with_initial_balance(initial);
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+            let callee = replica
+                .add_canister(Canister::new(Principal::from_slice(&[2])).with_method::<Foo>());
+
+            let reply = canister
+                .custom(
+                    || {
+                        assert_eq!(call_foo(Principal::from_slice(&[2]), 1000), 0);
+                    },
+                    Env::default(),
+                )
+                .await;
+            assert_eq!(reply.bytes().unwrap(), &[0]);
+
+            // The balance is only set when the canister is added, the spent cycles stay spent.
+            canister
+                .custom(
+                    move || {
+                        assert_eq!(balance(), initial - 1000);
+                        unsafe { ic0::msg_reply() };
+                    },
+                    Env::default(),
+                )
+                .await
+                .assert_ok();
+
+            assert_eq!(canister.cycle_balance().await, initial - 1000);
+            assert_eq!(callee.cycle_balance().await, initial + 1000);
+        });
+    }
+
+    #[test]
+    fn rejecting_method() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(
+                Canister::new(Principal::from_slice(&[1]))
+                    .with_rejecting_method("foo", RejectionCode::SysTransient, "Try again.")
+                    .with_rejecting_method("bar", RejectionCode::DestinationInvalid, "Gone."),
+            );
+
+            let reply = canister.new_call("foo").with_payment(10).perform().await;
+            assert!(matches!(
+                reply.rejection_code(),
+                RejectionCode::SysTransient
+            ));
+            assert_eq!(reply.rejection_message(), Some("Try again."));
+            assert_eq!(reply.cycles_refunded(), 10);
+
+            let reply = canister.new_call("bar").as_query().perform().await;
+            assert!(matches!(
+                reply.rejection_code(),
+                RejectionCode::DestinationInvalid
+            ));
+            assert_eq!(reply.rejection_message(), Some("Gone."));
+        });
+    }
+
+    #[test]
+    fn stop_and_start() {
+        struct Ping;
+
+        impl CanisterMethod for Ping {
+            const EXPORT_NAME: &'static str = "canister_update ping";
+
+            fn exported_method() {
+                unsafe {
+                    ic0::msg_reply();
+                }
+            }
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica
+                .add_canister(Canister::new(Principal::from_slice(&[1])).with_method::<Ping>());
+
+            canister.new_call("ping").perform().await.assert_ok();
+
+            canister.stop().await;
+            assert_eq!(canister.status().await, CanisterStatus::Stopped);
+
+            let reply = canister.new_call("ping").perform().await;
+            assert!(matches!(
+                reply.rejection_code(),
+                RejectionCode::SysTransient
+            ));
+
+            canister
+                .custom(
+                    || unsafe {
+                        assert_eq!(ic0::canister_status(), 3);
+                        ic0::msg_reply();
+                    },
+                    Env::default(),
+                )
+                .await
+                .assert_ok();
+
+            canister.start().await;
+            assert_eq!(canister.status().await, CanisterStatus::Running);
+            canister.new_call("ping").perform().await.assert_ok();
+        });
+    }
+
+    #[test]
+    fn inspect_message() {
+        struct Inspect;
+
+        impl CanisterMethod for Inspect {
+            const EXPORT_NAME: &'static str = "canister_inspect_message";
+
+            fn exported_method() {
+                unsafe {
+                    let len = ic0::msg_caller_size() as usize;
+                    let mut bytes = vec![0u8; len];
+                    ic0::msg_caller_copy(bytes.as_mut_ptr() as isize, 0, len as isize);
+
+                    if Principal::from_slice(&bytes) != Principal::anonymous() {
+                        ic0::accept_message();
+                    }
+                }
+            }
+        }
+
+        struct Ping;
+
+        impl CanisterMethod for Ping {
+            const EXPORT_NAME: &'static str = "canister_update ping";
+
+            fn exported_method() {
+                unsafe {
+                    ic0::msg_reply();
+                }
+            }
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(
+                Canister::new(Principal::from_slice(&[1]))
+                    .with_method::<Inspect>()
+                    .with_method::<Ping>(),
+            );
+
+            let reply = canister.new_call("ping").with_payment(10).perform().await;
+            assert!(matches!(
+                reply.rejection_code(),
+                RejectionCode::CanisterReject
+            ));
+            assert_eq!(reply.cycles_refunded(), 10);
+
+            canister
+                .new_call("ping")
+                .with_caller(Principal::from_slice(&[7]))
+                .perform()
+                .await
+                .assert_ok();
+
+            // Queries are not inspected.
+            canister.new_call("ping").as_query().perform().await.assert_ok();
+        });
+    }
+
+    #[test]
+    fn accept_message_outside_inspect() {
+        struct Inspect;
+
+        impl CanisterMethod for Inspect {
+            const EXPORT_NAME: &'static str = "canister_inspect_message";
+
+            fn exported_method() {
+                unsafe {
+                    ic0::accept_message();
+                    ic0::accept_message();
+                }
+            }
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+
+            let reply = canister
+                .custom(
+                    || unsafe {
+                        ic0::accept_message();
+                        ic0::msg_reply();
+                    },
+                    Env::default(),
+                )
+                .await;
+
+            assert!(reply
+                .rejection_message()
+                .unwrap()
+                .contains("accept_message can not be called from"));
+
+            let canister = replica
+                .add_canister(Canister::new(Principal::from_slice(&[2])).with_method::<Inspect>());
+
+            let reply = canister.new_call("ping").perform().await;
+            assert!(reply
+                .rejection_message()
+                .unwrap()
+                .contains("accept_message can only be called once."));
+        });
+    }
+
+    #[test]
+    fn is_controller() {
+        block_on(async {
+            let replica = Replica::default();
+            let controller = Principal::from_slice(&[7]);
+            let canister = replica.add_canister(
+                Canister::new(Principal::from_slice(&[1])).with_controllers(vec![controller]),
+            );
+
+            let reply = canister
+                .custom(
+                    move || unsafe {
+                        let is_controller = |p: Principal| {
+                            let bytes = p.as_slice();
+                            ic0::is_controller(bytes.as_ptr() as isize, bytes.len() as isize)
+                        };
+
+                        assert_eq!(is_controller(controller), 1);
+                        assert_eq!(is_controller(Principal::anonymous()), 0);
+                        ic0::msg_reply();
+                    },
+                    Env::default(),
+                )
+                .await;
+
+            reply.assert_ok();
+        });
+    }
+
+    #[test]
+    fn add_canister_with_id() {
+        block_on(async {
+            let replica = Replica::default();
+            assert_eq!(replica.create_canister().await, crate::canister_id(0));
+
+            let canister_id = crate::canister_id(1);
+            let canister =
+                replica.add_canister_with_id(Canister::new(Principal::anonymous()), canister_id);
+
+            canister
+                .custom(
+                    move || unsafe {
+                        let len = ic0::canister_self_size() as usize;
+                        let mut bytes = vec![0u8; len];
+                        ic0::canister_self_copy(bytes.as_mut_ptr() as isize, 0, len as isize);
+                        assert_eq!(Principal::from_slice(&bytes), canister_id);
+                        ic0::msg_reply();
+                    },
+                    Env::default(),
+                )
+                .await
+                .assert_ok();
+
+            // The ids that are already used are skipped.
+            assert_eq!(replica.create_canister().await, crate::canister_id(2));
+        });
+    }
+
+    #[test]
+    fn replica_install_code() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister_id = replica.create_canister().await;
+            let canister = replica.install_code(Canister::new(canister_id));
+
+            canister
+                .custom(|| unsafe { ic0::msg_reply() }, Env::default())
+                .await
+                .assert_ok();
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "was not created using Replica::create_canister")]
+    fn replica_install_code_not_created() {
+        block_on(async {
+            let replica = Replica::default();
+            replica.install_code(Canister::new(crate::canister_id(0)));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "already has code installed")]
+    fn replica_install_code_twice() {
+        block_on(async {
+            let replica = Replica::default();
+            let canister_id = replica.create_canister().await;
+            replica.install_code(Canister::new(canister_id));
+            replica.install_code(Canister::new(canister_id));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "already defined")]
+    fn add_canister_duplicate_id() {
+        block_on(async {
+            let replica = Replica::default();
+            replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+            replica.add_canister_with_id(
+                Canister::new(Principal::anonymous()),
+                Principal::from_slice(&[1]),
+            );
+        });
+    }
+
+    #[test]
+    fn add_method() {
+        struct Ping;
+
+        impl CanisterMethod for Ping {
+            const EXPORT_NAME: &'static str = "canister_update ping";
+
+            fn exported_method() {
+                unsafe {
+                    ic0::msg_reply();
+                }
+            }
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+
+            canister.new_call("ping").perform().await.assert_error();
+
+            replica
+                .with_canister(Principal::from_slice(&[1]), |canister| {
+                    canister.add_method::<Ping>().unwrap();
+                    assert!(canister.add_method::<Ping>().is_err());
+                })
+                .await;
+
+            canister.new_call("ping").perform().await.assert_ok();
+        });
+    }
+
+    #[test]
+    fn rejection_refunds_cycles() {
+        use ic_kit_sys::types::CallError;
+
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+
+            let reply = canister.new_call("missing").with_payment(10).perform().await;
+            assert!(matches!(
+                reply.bytes(),
+                Err(CallError::Rejected(RejectionCode::DestinationInvalid, _, 10))
+            ));
+        });
+    }
+
+    #[test]
+    fn heartbeat_tick() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        static BEATS: AtomicU64 = AtomicU64::new(0);
+
+        struct Heartbeat;
+
+        impl CanisterMethod for Heartbeat {
+            const EXPORT_NAME: &'static str = "canister_heartbeat";
+
+            fn exported_method() {
+                BEATS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            replica.add_canister(
+                Canister::new(Principal::from_slice(&[1])).with_method::<Heartbeat>(),
+            );
+            // Canisters without a heartbeat are skipped.
+            replica.add_canister(Canister::new(Principal::from_slice(&[2])));
+
+            replica.tick().await;
+            assert_eq!(BEATS.load(Ordering::SeqCst), 1);
+
+            replica.tick_n(3).await;
+            assert_eq!(BEATS.load(Ordering::SeqCst), 4);
+        });
+    }
+
+    #[test]
+    fn install_code_requires_created_canister() {
+        #[derive(candid::CandidType, candid::Deserialize)]
+        enum InstallMode {
+            #[serde(rename = "install")]
+            Install,
+        }
+
+        #[derive(candid::CandidType)]
+        struct InstallCodeArgument {
+            mode: InstallMode,
+            canister_id: Principal,
+            wasm_module: Vec<u8>,
+            arg: Vec<u8>,
+        }
+
+        let wasm_module = b"ic-kit-runtime:install_code_requires_created_canister".to_vec();
+        crate::register_canister_code(wasm_module.clone(), Canister::new::<Principal>);
+
+        let install = |canister_id| InstallCodeArgument {
+            mode: InstallMode::Install,
+            canister_id,
+            wasm_module: wasm_module.clone(),
+            arg: Vec::new(),
+        };
+
+        block_on(async {
+            let replica = Replica::default();
+            let management = Principal::management_canister();
+
+            let reply = replica
+                .new_call(management, "install_code")
+                .with_arg(install(crate::canister_id(100)))
+                .perform()
+                .await;
+            assert!(matches!(
+                reply.rejection_code(),
+                RejectionCode::DestinationInvalid
+            ));
+
+            let canister_id = replica.create_canister().await;
+            replica
+                .new_call(management, "install_code")
+                .with_arg(install(canister_id))
+                .perform()
+                .await
+                .assert_ok();
+
+            let reply = replica
+                .new_call(management, "install_code")
+                .with_arg(install(canister_id))
+                .perform()
+                .await;
+            assert!(matches!(
+                reply.rejection_code(),
+                RejectionCode::CanisterReject
+            ));
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Found canisters with pending requests")]
+    fn assert_no_pending_with_call_in_flight() {
+        fn call() {
+            call_foo(Principal::from_slice(&[2]), 0);
+        }
+
+        block_on(async {
+            let replica = Replica::default();
+            let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+            replica.add_canister(Canister::new(Principal::from_slice(&[2])).with_method::<Foo>());
+
+            canister.custom(call, Env::default()).await.assert_ok();
+            replica.assert_no_pending().await;
+
+            // The inspection reaches the canister before the response of its outgoing call.
+            let request = canister.custom(call, Env::default());
+            let _ = tokio::join!(request, replica.assert_no_pending());
+        });
+    }
+}
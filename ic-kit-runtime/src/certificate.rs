@@ -0,0 +1,182 @@
+//! A fake implementation of the IC's certificates, the certificates generated here have the same
+//! CBOR structure as the ones issued by the IC, but they are signed with a dummy signature and
+//! should only be used in tests.
+//!
+//! See:
+//! https://internetcomputer.org/docs/current/references/ic-interface-spec/#certificate
+
+use candid::Principal;
+
+/// The CBOR self-describing tag.
+const CBOR_SELF_DESCRIBING_TAG: u64 = 55799;
+
+/// The length of a BLS signature in bytes.
+const SIGNATURE_SIZE: usize = 48;
+
+/// Create a fake certificate that certifies the given certified data for the canister at the
+/// provided time.
+pub fn fake_certificate(canister_id: &Principal, certified_data: &[u8], time: u64) -> Vec<u8> {
+    let mut time_leb = Vec::with_capacity(10);
+    write_leb128(&mut time_leb, time);
+
+    let mut out = Vec::with_capacity(256);
+    write_head(&mut out, 6, CBOR_SELF_DESCRIBING_TAG);
+    write_head(&mut out, 5, 2);
+
+    write_text(&mut out, "tree");
+    // fork(labeled("canister", ...), labeled("time", ...))
+    write_head(&mut out, 4, 3);
+    write_head(&mut out, 0, 1);
+    {
+        write_head(&mut out, 4, 3);
+        write_head(&mut out, 0, 2);
+        write_bytes(&mut out, b"canister");
+        write_head(&mut out, 4, 3);
+        write_head(&mut out, 0, 2);
+        write_bytes(&mut out, canister_id.as_slice());
+        write_head(&mut out, 4, 3);
+        write_head(&mut out, 0, 2);
+        write_bytes(&mut out, b"certified_data");
+        write_leaf(&mut out, certified_data);
+    }
+    {
+        write_head(&mut out, 4, 3);
+        write_head(&mut out, 0, 2);
+        write_bytes(&mut out, b"time");
+        write_leaf(&mut out, &time_leb);
+    }
+
+    write_text(&mut out, "signature");
+    write_bytes(&mut out, &[0; SIGNATURE_SIZE]);
+
+    out
+}
+
+/// Write a leaf node of the hash tree.
+fn write_leaf(out: &mut Vec<u8>, data: &[u8]) {
+    write_head(out, 4, 2);
+    write_head(out, 0, 3);
+    write_bytes(out, data);
+}
+
+fn write_bytes(out: &mut Vec<u8>, data: &[u8]) {
+    write_head(out, 2, data.len() as u64);
+    out.extend_from_slice(data);
+}
+
+fn write_text(out: &mut Vec<u8>, text: &str) {
+    write_head(out, 3, text.len() as u64);
+    out.extend_from_slice(text.as_bytes());
+}
+
+/// Write the head of a CBOR data item with the given major type and argument.
+fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+
+    if value < 24 {
+        out.push(major | value as u8);
+    } else if value <= u8::MAX as u64 {
+        out.push(major | 24);
+        out.push(value as u8);
+    } else if value <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn write_leb128(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{EntryMode, Env};
+    use crate::{Canister, Replica, TokioRuntimeBuilder};
+    use ic_kit_sys::ic0;
+
+    /// The env of a query made at the given time, only a query can read the data certificate.
+    fn query_env(time: u64) -> Env {
+        Env::default()
+            .with_entry_mode(EntryMode::Query)
+            .with_time(time)
+    }
+
+    #[test]
+    fn certified_data() {
+        TokioRuntimeBuilder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let canister_id = Principal::from_slice(&[1]);
+                let replica = Replica::default();
+                let canister = replica.add_canister(Canister::new(canister_id));
+
+                canister
+                    .custom(
+                        || unsafe {
+                            let data = [7u8; 32];
+                            ic0::certified_data_set(data.as_ptr() as isize, data.len() as isize);
+                            ic0::msg_reply();
+                        },
+                        Env::default(),
+                    )
+                    .await
+                    .assert_ok();
+
+                let certificate = || unsafe {
+                    assert_eq!(ic0::data_certificate_present(), 1);
+                    let size = ic0::data_certificate_size();
+                    let mut bytes = vec![0u8; size as usize];
+                    ic0::data_certificate_copy(bytes.as_mut_ptr() as isize, 0, size);
+                    ic0::msg_reply_data_append(bytes.as_ptr() as isize, size);
+                    ic0::msg_reply();
+                };
+
+                let reply = canister.custom(certificate, query_env(5)).await;
+                assert_eq!(
+                    reply.bytes().unwrap(),
+                    fake_certificate(&canister_id, &[7; 32], 5).as_slice()
+                );
+
+                let reply = canister
+                    .custom(
+                        || unsafe {
+                            let data = [8u8; 33];
+                            ic0::certified_data_set(data.as_ptr() as isize, data.len() as isize);
+                            ic0::msg_reply();
+                        },
+                        Env::default(),
+                    )
+                    .await;
+
+                assert!(reply
+                    .rejection_message()
+                    .unwrap()
+                    .contains("Certified data can not be larger than 32 bytes."));
+
+                // The trap leaves the certified data as is.
+                let reply = canister.custom(certificate, query_env(5)).await;
+                assert_eq!(
+                    reply.bytes().unwrap(),
+                    fake_certificate(&canister_id, &[7; 32], 5).as_slice()
+                );
+            });
+    }
+}
@@ -1,9 +1,11 @@
 use std::panic::{RefUnwindSafe, UnwindSafe};
 
+use candid::utils::ArgumentEncoder;
 use candid::Principal;
 use tokio::sync::oneshot;
 
 use crate::call::{CallBuilder, CallReply};
+use crate::canister::{CanisterSnapshot, CanisterStatus};
 use crate::types::{Env, Message, RequestId};
 use crate::Replica;
 
@@ -55,22 +57,39 @@ impl<'a> CanisterHandle<'a> {
         rx.await.unwrap()
     }
 
-    /// Runs the init hook of the canister. For more customization use [`CanisterHandle::run_env`]
-    /// with [`Env::init()`].
+    /// Runs the init hook of the canister, the reply is empty if the hook ran and is a rejection
+    /// if it trapped or could not decode its arguments.
     pub async fn init(&self) -> CallReply {
-        self.run_env(Env::init()).await
+        self.run_lifecycle_hook(Env::init()).await
     }
 
-    /// Runs the pre_upgrade hook of the canister. For more customization use
-    /// [`CanisterHandle::run_env`] with [`Env::pre_upgrade()`].
+    /// Runs the init hook of the canister with the given candid encoded arguments.
+    pub async fn init_with_args<T: ArgumentEncoder>(&self, arguments: T) -> CallReply {
+        self.run_lifecycle_hook(Env::init().with_args(arguments))
+            .await
+    }
+
+    /// Runs the pre_upgrade hook of the canister, the reply is empty if the hook ran and is a
+    /// rejection if it trapped.
     pub async fn pre_upgrade(&self) -> CallReply {
-        self.run_env(Env::pre_upgrade()).await
+        self.run_lifecycle_hook(Env::pre_upgrade()).await
     }
 
-    /// Runs the post_upgrade hook of the canister. For more customization use
-    /// [`CanisterHandle::run_env`] with [`Env::post_upgrade()`].
+    /// Runs the post_upgrade hook of the canister, the reply is empty if the hook ran and is a
+    /// rejection if it trapped or could not decode its arguments.
     pub async fn post_upgrade(&self) -> CallReply {
-        self.run_env(Env::post_upgrade()).await
+        self.run_lifecycle_hook(Env::post_upgrade()).await
+    }
+
+    /// Runs the post_upgrade hook of the canister with the given candid encoded arguments.
+    pub async fn post_upgrade_with_args<T: ArgumentEncoder>(&self, arguments: T) -> CallReply {
+        self.run_lifecycle_hook(Env::post_upgrade().with_args(arguments))
+            .await
+    }
+
+    /// Run the lifecycle hook described by the env, a missing hook is reported as a success.
+    async fn run_lifecycle_hook(&self, env: Env) -> CallReply {
+        self.replica.run_lifecycle_hook(self.canister_id, env).await
     }
 
     /// Runs the post_upgrade hook of the canister. For more customization use
@@ -78,4 +97,120 @@ impl<'a> CanisterHandle<'a> {
     pub async fn heartbeat(&self) -> CallReply {
         self.run_env(Env::heartbeat()).await
     }
+
+    /// Return the current size of the canister's stable memory in WebAssembly pages.
+    pub async fn stable_size(&self) -> u64 {
+        self.replica
+            .with_canister(self.canister_id, |canister| canister.stable_pages())
+            .await
+    }
+
+    /// Read a range of the canister's stable memory, this goes directly to the stable memory
+    /// backend and does not execute any code on the canister.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of the bounds of the stable memory.
+    pub async fn stable_read_raw(&self, offset: u64, len: usize) -> Vec<u8> {
+        self.replica
+            .with_canister(self.canister_id, move |canister| {
+                canister.stable_read_raw(offset, len)
+            })
+            .await
+            .unwrap_or_else(|e| panic!("ic-kit-runtime: {}", e))
+    }
+
+    /// Overwrite a range of the canister's stable memory, this goes directly to the stable memory
+    /// backend and can be used to simulate corruption.
+    ///
+    /// # Panics
+    ///
+    /// If the range is out of the bounds of the stable memory.
+    pub async fn stable_write_raw<B: Into<Vec<u8>>>(&self, offset: u64, bytes: B) {
+        let bytes = bytes.into();
+        self.replica
+            .with_canister(self.canister_id, move |canister| {
+                canister.stable_write_raw(offset, &bytes)
+            })
+            .await
+            .unwrap_or_else(|e| panic!("ic-kit-runtime: {}", e))
+    }
+
+    /// Stop the canister, from now on new calls to the canister are rejected with
+    /// `SysTransient`, the replies to the canister's own outgoing calls are still delivered and
+    /// once there is nothing pending the canister's status becomes `Stopped`.
+    pub async fn stop(&self) {
+        self.replica
+            .with_canister(self.canister_id, |canister| canister.stop())
+            .await
+    }
+
+    /// Start a stopped canister.
+    pub async fn start(&self) {
+        self.replica
+            .with_canister(self.canister_id, |canister| canister.start())
+            .await
+    }
+
+    /// Return the current status of the canister.
+    pub async fn status(&self) -> CanisterStatus {
+        self.replica
+            .with_canister(self.canister_id, |canister| canister.status())
+            .await
+    }
+
+    /// Return the cycle balance of the canister.
+    pub async fn cycle_balance(&self) -> u128 {
+        self.replica
+            .with_canister(self.canister_id, |canister| canister.balance())
+            .await
+    }
+
+    /// Set the cycle balance of the canister, from now on the canister keeps its own balance
+    /// which overrides the balance of the env of the messages sent to it.
+    pub async fn set_cycles(&self, amount: u128) {
+        self.replica
+            .with_canister(self.canister_id, move |canister| {
+                canister.set_balance(amount)
+            })
+            .await
+    }
+
+    /// Add the given amount of cycles to the canister's balance, see
+    /// [`CanisterHandle::set_cycles`].
+    pub async fn add_cycles(&self, amount: u128) {
+        self.replica
+            .with_canister(self.canister_id, move |canister| {
+                canister.add_cycles(amount)
+            })
+            .await
+    }
+
+    /// Capture the state of the canister, the snapshot contains the stable memory and the system
+    /// state of the canister, and the heap state if the canister was built with
+    /// [`crate::Canister::with_snapshot`].
+    ///
+    /// # Panics
+    ///
+    /// If the canister traps while serializing its heap.
+    pub async fn snapshot(&self) -> CanisterSnapshot {
+        self.replica
+            .snapshot(self.canister_id)
+            .await
+            .unwrap_or_else(|e| panic!("ic-kit-runtime: {}", e))
+    }
+
+    /// Bring the canister back to the state captured by [`CanisterHandle::snapshot`], this can be
+    /// used to run several tests from the same starting state.
+    ///
+    /// # Panics
+    ///
+    /// If the snapshot contains a heap state but the canister was not built with
+    /// [`crate::Canister::with_snapshot`], or if the canister traps while loading it.
+    pub async fn restore(&self, snapshot: CanisterSnapshot) {
+        self.replica
+            .restore(self.canister_id, snapshot)
+            .await
+            .unwrap_or_else(|e| panic!("ic-kit-runtime: {}", e))
+    }
 }
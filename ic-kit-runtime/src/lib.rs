@@ -6,20 +6,31 @@ cfg_if::cfg_if! {
         compile_error!("IC-Kit runtime does not support builds for WASM.");
     } else {
         pub mod call;
+        pub mod certificate;
         pub mod canister;
         pub mod replica;
         pub mod stable;
         pub mod types;
         pub mod users;
         pub mod handle;
+        mod management;
+        #[cfg(test)]
+        mod test_utils;
 
-        pub use canister::{Canister, CanisterMethod};
-        pub use replica::Replica;
+        pub use canister::{
+            Canister, CanisterMethod, CanisterPendingState, CanisterSnapshot, CanisterStatus,
+            DebugSink, Snapshottable,
+        };
+        pub use management::{
+            register_canister_code, CanisterBuilder, CanisterHttpResponse, HttpHeader,
+        };
+        pub use replica::{canister_id, Replica};
         pub use tokio::runtime::Builder as TokioRuntimeBuilder;
 
         pub mod prelude {
             pub use crate::replica::Replica;
             pub use crate::users;
+            pub use crate::{assert_reject, assert_reply};
         }
     }
 }
@@ -0,0 +1,276 @@
+//! A simulation of the IC's management canister (`aaaaa-aa`), calls made to the management
+//! canister are handled by the replica itself instead of being routed to a canister.
+//!
+//! See:
+//! https://internetcomputer.org/docs/current/references/ic-interface-spec/#ic-management-canister
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use candid::{decode_one, encode_one, CandidType, Nat, Principal};
+use lazy_static::lazy_static;
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Deserialize;
+
+use ic_kit_sys::types::RejectionCode;
+
+use crate::canister::Canister;
+
+/// The seed used for the random number generator unless the replica is given another one.
+pub(crate) const DEFAULT_RNG_SEED: u64 = 0;
+
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct HttpHeader {
+    pub name: String,
+    pub value: String,
+}
+
+/// The response to a HTTPS outcall, this is what the management canister's `http_request`
+/// returns to the canister.
+#[derive(CandidType, Deserialize, Debug, Clone, PartialEq)]
+pub struct CanisterHttpResponse {
+    pub status: Nat,
+    pub headers: Vec<HttpHeader>,
+    pub body: Vec<u8>,
+}
+
+impl CanisterHttpResponse {
+    /// Create a response with the given status code and body and no headers.
+    pub fn new<B: Into<Vec<u8>>>(status: u16, body: B) -> Self {
+        Self {
+            status: Nat::from(status as u64),
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// Add the given header to the response.
+    pub fn with_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.push(HttpHeader {
+            name: name.into(),
+            value: value.into(),
+        });
+        self
+    }
+}
+
+/// The part of the `http_request` argument that is used by the mock, candid skips the rest of
+/// the fields.
+#[derive(Deserialize, CandidType)]
+struct CanisterHttpRequest {
+    url: String,
+}
+
+/// A function that creates the canister with the given id, in the runtime this takes the place
+/// of a wasm module.
+pub type CanisterBuilder = fn(Principal) -> Canister;
+
+lazy_static! {
+    /// Map each of the registered fake wasm modules to the builder of the canister.
+    static ref CANISTER_CODE: Mutex<HashMap<Vec<u8>, CanisterBuilder>> = Mutex::new(HashMap::new());
+}
+
+/// Register the builder of a canister, so calling the management canister's `install_code` with
+/// the given bytes as the `wasm_module` installs the canister that is created by the builder.
+pub fn register_canister_code(wasm_module: Vec<u8>, builder: CanisterBuilder) {
+    CANISTER_CODE.lock().unwrap().insert(wasm_module, builder);
+}
+
+/// Return the builder of the canister that is registered for the given module.
+pub(crate) fn get_canister_code(wasm_module: &[u8]) -> Option<CanisterBuilder> {
+    CANISTER_CODE.lock().unwrap().get(wasm_module).copied()
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InstallMode {
+    #[serde(rename = "install")]
+    Install,
+    #[serde(rename = "reinstall")]
+    Reinstall,
+    #[serde(rename = "upgrade")]
+    Upgrade,
+}
+
+/// The argument of the management canister's `install_code`.
+#[derive(Deserialize)]
+pub(crate) struct InstallCodeArgument {
+    pub mode: InstallMode,
+    pub canister_id: Principal,
+    pub wasm_module: Vec<u8>,
+    pub arg: Vec<u8>,
+}
+
+/// The response of the management canister's `create_canister`.
+#[derive(CandidType)]
+pub(crate) struct CanisterIdRecord {
+    pub canister_id: Principal,
+}
+
+/// The state of the management canister.
+pub(crate) struct ManagementCanister {
+    /// The random number generator used to answer `raw_rand`, it is seeded so the tests are
+    /// reproducible.
+    rng: ChaCha20Rng,
+    /// The canned responses to HTTPS outcalls mapped by the url.
+    http_responses: HashMap<String, CanisterHttpResponse>,
+}
+
+impl Default for ManagementCanister {
+    fn default() -> Self {
+        Self {
+            rng: ChaCha20Rng::seed_from_u64(DEFAULT_RNG_SEED),
+            http_responses: HashMap::new(),
+        }
+    }
+}
+
+impl ManagementCanister {
+    /// Reset the random number generator with the given seed.
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = ChaCha20Rng::seed_from_u64(seed);
+    }
+
+    /// Use the given response for every HTTPS outcall to the url.
+    pub fn set_http_response(&mut self, url: String, response: CanisterHttpResponse) {
+        self.http_responses.insert(url, response);
+    }
+
+    /// Execute the given method and return the candid encoded response.
+    pub fn process(
+        &mut self,
+        method: &str,
+        args: &[u8],
+    ) -> Result<Vec<u8>, (RejectionCode, String)> {
+        match method {
+            "raw_rand" => Ok(encode_one(self.raw_rand().to_vec()).unwrap()),
+            "http_request" => self.http_request(args),
+            _ => Err((
+                RejectionCode::DestinationInvalid,
+                format!("The management canister does not have a '{}' method.", method),
+            )),
+        }
+    }
+
+    /// Answer a HTTPS outcall with the canned response registered for the url, the transform
+    /// function of the request is not applied.
+    fn http_request(&mut self, args: &[u8]) -> Result<Vec<u8>, (RejectionCode, String)> {
+        let request = decode_one::<CanisterHttpRequest>(args).map_err(|e| {
+            (
+                RejectionCode::CanisterReject,
+                format!("Could not decode the http_request argument: {}", e),
+            )
+        })?;
+
+        match self.http_responses.get(&request.url) {
+            Some(response) => Ok(encode_one(response).unwrap()),
+            None => Err((
+                RejectionCode::SysTransient,
+                format!("No response is registered for the url '{}'.", request.url),
+            )),
+        }
+    }
+
+    /// Return 32 random bytes.
+    fn raw_rand(&mut self) -> [u8; 32] {
+        let mut bytes = [0; 32];
+        self.rng.fill_bytes(&mut bytes);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::block_on;
+    use crate::Replica;
+    use candid::Principal;
+
+    fn raw_rand(management: &mut ManagementCanister) -> Vec<u8> {
+        let bytes = management.process("raw_rand", &[]).unwrap();
+        decode_one::<Vec<u8>>(&bytes).unwrap()
+    }
+
+    #[test]
+    fn raw_rand_is_deterministic() {
+        let mut a = ManagementCanister::default();
+        let mut b = ManagementCanister::default();
+
+        let first = raw_rand(&mut a);
+        assert_eq!(first.len(), 32);
+        assert_eq!(first, raw_rand(&mut b));
+        assert_ne!(first, raw_rand(&mut a));
+
+        b.set_rng_seed(42);
+        a.set_rng_seed(42);
+        assert_eq!(raw_rand(&mut a), raw_rand(&mut b));
+    }
+
+    #[test]
+    fn raw_rand_through_replica() {
+        block_on(async {
+            let replica = Replica::default().with_rng_seed(7);
+
+            let mut expected = ManagementCanister::default();
+            expected.set_rng_seed(7);
+
+            let bytes = replica
+                .new_call(Principal::management_canister(), "raw_rand")
+                .perform()
+                .await
+                .decode_one::<Vec<u8>>()
+                .unwrap();
+
+            assert_eq!(bytes, raw_rand(&mut expected));
+        });
+    }
+
+    #[test]
+    fn http_request() {
+        #[derive(CandidType)]
+        struct Request {
+            url: String,
+            max_response_bytes: Option<u64>,
+            headers: Vec<HttpHeader>,
+            body: Option<Vec<u8>>,
+        }
+
+        block_on(async {
+            let url = "https://example.com/price";
+            let replica = Replica::default().with_http_response(
+                url,
+                CanisterHttpResponse::new(200, "42").with_header("content-type", "text/plain"),
+            );
+
+            let request = |url: &str| Request {
+                url: url.to_string(),
+                max_response_bytes: None,
+                headers: Vec::new(),
+                body: None,
+            };
+
+            let response = replica
+                .new_call(Principal::management_canister(), "http_request")
+                .with_arg(request(url))
+                .perform()
+                .await
+                .decode_one::<CanisterHttpResponse>()
+                .unwrap();
+
+            assert_eq!(response.status, Nat::from(200u64));
+            assert_eq!(response.body, b"42".to_vec());
+            assert_eq!(response.headers[0].name, "content-type");
+
+            let reply = replica
+                .new_call(Principal::management_canister(), "http_request")
+                .with_arg(request("https://example.com/unknown"))
+                .perform()
+                .await;
+
+            assert!(matches!(
+                reply.rejection_code(),
+                RejectionCode::SysTransient
+            ));
+        });
+    }
+}
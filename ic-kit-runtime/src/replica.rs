@@ -13,18 +13,25 @@
 //! This also allows the canister event loops to have accesses to the replica without any borrows by
 //! just sending their request to the same channel, causing the replica to process the messages.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use candid::Principal;
+use candid::{decode_one, encode_one, Principal};
 use tokio::sync::{mpsc, oneshot};
 
-use ic_kit_sys::types::RejectionCode;
+use ic_kit_sys::types::{RejectionCode, CANDID_EMPTY_ARG};
 
 use crate::call::{CallBuilder, CallReply};
-use crate::canister::Canister;
+use crate::canister::{Canister, CanisterPendingState, CanisterSnapshot, DebugSink};
 use crate::handle::CanisterHandle;
+use crate::management::{
+    get_canister_code, CanisterHttpResponse, CanisterIdRecord, InstallCodeArgument, InstallMode,
+    ManagementCanister,
+};
 use crate::types::*;
 
 /// A local replica that contains one or several canisters.
@@ -32,6 +39,19 @@ pub struct Replica {
     // The current implementation uses a `tokio::spawn` to run an event loop for the replica,
     // the state of the replica is store in that event loop.
     sender: mpsc::UnboundedSender<ReplicaMessage>,
+    /// The ids of the canisters, shared with the event loop so they can be checked right away.
+    ids: Arc<Mutex<CanisterIds>>,
+    /// The messages that are not processed by the canisters yet, see [`Replica::run_until_idle`].
+    in_flight: InFlight,
+}
+
+/// The ids that are in use in a replica.
+#[derive(Default)]
+struct CanisterIds {
+    /// The ids of the canisters that are added to the replica.
+    installed: HashSet<Principal>,
+    /// The canister ids that are created but no code is installed on them yet.
+    reserved: HashSet<Principal>,
 }
 
 /// The state of the replica, it does not live inside the replica itself, but an instance of it
@@ -40,19 +60,89 @@ pub struct Replica {
 #[derive(Default)]
 struct ReplicaState {
     /// Map each of the current canisters to the receiver of that canister's event loop.
-    canisters: HashMap<Principal, mpsc::UnboundedSender<ReplicaCanisterRequest>>,
+    canisters: HashMap<Principal, mpsc::UnboundedSender<CanisterWorkerMessage>>,
+    /// The ids of the canisters, this is shared with the [`Replica`].
+    ids: Arc<Mutex<CanisterIds>>,
+    /// The messages that are not processed by the canisters yet, shared with the [`Replica`] and
+    /// the event loops of the canisters.
+    in_flight: InFlight,
+    /// The index used to generate the next canister id.
+    next_canister_index: u64,
+    /// The amount of time in nanoseconds that the replica's clock is ahead of the system time,
+    /// this is added to the time of every message that goes through the replica.
+    time_offset: u64,
+    /// If set the replica's clock starts from this time instead of following the system time.
+    time: Option<u64>,
+    /// If set the balance of the canisters during every message is set to this value.
+    balance: Option<u128>,
+    /// If set the canisters that are added to the replica start with this balance.
+    initial_balance: Option<u128>,
+    /// The state of the management canister.
+    management: ManagementCanister,
+    /// The debug sink of the canisters that do not have their own.
+    debug_sink: Option<DebugSink>,
+    /// The best-effort calls that are waiting for a response, mapped to the caller and the
+    /// deadline of the call.
+    best_effort_calls: HashMap<RequestId, (Principal, u64)>,
+    /// The sender to the replica's own event loop, used to start the event loop of the canisters
+    /// that are installed through the management canister.
+    sender: Option<mpsc::UnboundedSender<ReplicaMessage>>,
 }
 
+/// The number of messages that are sent to the event loop of a canister to run its code and are
+/// not processed yet.
+#[derive(Clone, Default)]
+struct InFlight(Arc<AtomicUsize>);
+
+/// Marks a message that was sent with [`InFlight::send`] as processed once it is dropped.
+struct InFlightGuard(InFlight);
+
 /// A message that Replica wants to send to a canister to be processed.
 struct ReplicaCanisterRequest {
     message: Message,
     reply_sender: Option<oneshot::Sender<CallReply>>,
 }
 
+/// A message that is delivered to a canister's event loop.
+enum CanisterWorkerMessage {
+    /// A message that should be executed on the canister.
+    Request(ReplicaCanisterRequest),
+    /// Ask the event loop for a snapshot of the canister's pending state.
+    InspectPending(oneshot::Sender<CanisterPendingState>),
+    /// Run the given function with a mutable reference to the canister, outside of any message
+    /// execution.
+    WithCanister(CanisterTask),
+    /// Capture the state of the canister in between the messages.
+    Snapshot(oneshot::Sender<Result<CanisterSnapshot, String>>),
+    /// Replace the state of the canister with the given snapshot in between the messages.
+    Restore {
+        snapshot: CanisterSnapshot,
+        reply_sender: oneshot::Sender<Result<(), String>>,
+    },
+    /// Execute the canister's global timer if it has expired by the given time.
+    GlobalTimer { time: u64 },
+    /// Run the init, pre_upgrade or post_upgrade hook of the canister using the given env.
+    LifecycleHook {
+        env: Env,
+        reply_sender: oneshot::Sender<CallReply>,
+    },
+    /// Replace the canister with a new instance and run its init or post_upgrade hook using the
+    /// given env, the stable memory is moved to the new instance when upgrading.
+    InstallCode {
+        canister: Canister,
+        upgrade: bool,
+        env: Env,
+        reply_sender: oneshot::Sender<CallReply>,
+    },
+}
+
+/// A function that is executed on the canister's event loop with access to the canister itself.
+type CanisterTask = Box<dyn FnOnce(&mut Canister) + Send>;
+
 enum ReplicaMessage {
     CanisterAdded {
         canister_id: Principal,
-        channel: mpsc::UnboundedSender<ReplicaCanisterRequest>,
+        channel: mpsc::UnboundedSender<CanisterWorkerMessage>,
     },
     CanisterRequest {
         canister_id: Principal,
@@ -62,6 +152,64 @@ enum ReplicaMessage {
     CanisterReply {
         canister_id: Principal,
         message: Message,
+        /// The deadline of the call if it was a best-effort call.
+        deadline: Option<u64>,
+    },
+    /// A best-effort call made by the canister, sent before the call itself so the replica can
+    /// reject it once the deadline expires.
+    BestEffortCall {
+        canister_id: Principal,
+        request_id: RequestId,
+        deadline: u64,
+    },
+    InspectPending {
+        reply_sender: oneshot::Sender<Vec<(Principal, CanisterPendingState)>>,
+    },
+    CreateCanister {
+        reply_sender: oneshot::Sender<Principal>,
+    },
+    WithCanister {
+        canister_id: Principal,
+        task: CanisterTask,
+    },
+    LifecycleHook {
+        canister_id: Principal,
+        env: Env,
+        reply_sender: oneshot::Sender<CallReply>,
+    },
+    Snapshot {
+        canister_id: Principal,
+        reply_sender: oneshot::Sender<Result<CanisterSnapshot, String>>,
+    },
+    Restore {
+        canister_id: Principal,
+        snapshot: CanisterSnapshot,
+        reply_sender: oneshot::Sender<Result<(), String>>,
+    },
+    AdvanceTime {
+        duration: u64,
+    },
+    Tick {
+        reply_sender: oneshot::Sender<()>,
+    },
+    SetRngSeed {
+        seed: u64,
+    },
+    SetTime {
+        time: u64,
+    },
+    SetBalance {
+        balance: u128,
+    },
+    SetInitialBalance {
+        balance: u128,
+    },
+    SetHttpResponse {
+        url: String,
+        response: CanisterHttpResponse,
+    },
+    SetDebugSink {
+        sink: DebugSink,
     },
 }
 
@@ -78,9 +226,27 @@ impl Replica {
     }
 
     /// Add the given canister to this replica.
+    ///
+    /// # Panics
+    ///
+    /// If there is already a canister with the same id in the replica.
     pub fn add_canister(&self, canister: Canister) -> CanisterHandle {
         let canister_id = canister.id();
 
+        if !self.ids.lock().unwrap().install(canister_id) {
+            panic!(
+                "Canister '{}' is already defined in the replica.",
+                canister_id
+            )
+        }
+
+        self.start_canister(canister)
+    }
+
+    /// Start the event loop of a canister whose id is already added to the replica's ids.
+    fn start_canister(&self, canister: Canister) -> CanisterHandle {
+        let canister_id = canister.id();
+
         // Create a execution queue for the canister so we can send messages to the canister
         // asynchronously
         let replica = self.sender.clone();
@@ -94,7 +260,12 @@ impl Replica {
             .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
 
         // Start the event loop for the canister.
-        tokio::spawn(canister_worker(rx, replica, canister));
+        tokio::spawn(canister_worker(
+            rx,
+            replica,
+            canister,
+            self.in_flight.clone(),
+        ));
 
         CanisterHandle {
             replica: self,
@@ -102,6 +273,82 @@ impl Replica {
         }
     }
 
+    /// Add a canister whose event loop never runs, the messages sent to it are queued and never
+    /// get a response. Used to test the calls to an unresponsive canister.
+    #[cfg(test)]
+    pub(crate) fn add_unresponsive_canister(&self, canister_id: Principal) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        // Keep the channel open without ever receiving from it.
+        std::mem::forget(rx);
+
+        self.sender
+            .send(ReplicaMessage::CanisterAdded {
+                canister_id,
+                channel: tx,
+            })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+    }
+
+    /// Add the given canister to this replica using the provided id instead of the id the canister
+    /// was created with, this is useful when another canister has the id hardcoded.
+    ///
+    /// # Panics
+    ///
+    /// If there is already a canister with the same id in the replica.
+    pub fn add_canister_with_id(
+        &self,
+        canister: Canister,
+        canister_id: Principal,
+    ) -> CanisterHandle {
+        self.add_canister(canister.with_id(canister_id))
+    }
+
+    /// Create a new canister id that is not used by any other canister in this replica, the id
+    /// is reserved until a canister is installed on it using [`Replica::install_code`].
+    pub fn create_canister(&self) -> impl Future<Output = Principal> {
+        let (tx, rx) = oneshot::channel();
+
+        self.sender
+            .send(ReplicaMessage::CreateCanister { reply_sender: tx })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+
+        async {
+            rx.await
+                .expect("ic-kit-runtime: Could not retrieve the id of the created canister.")
+        }
+    }
+
+    /// Install the given canister in this replica, the id of the canister must be created
+    /// using [`Replica::create_canister`]. Just like [`Replica::add_canister`] this does not run
+    /// the init hook of the canister, use [`CanisterHandle::init`] for that.
+    ///
+    /// # Panics
+    ///
+    /// If the id was not created by [`Replica::create_canister`] or a canister is already
+    /// installed on it.
+    pub fn install_code(&self, canister: Canister) -> CanisterHandle {
+        let canister_id = canister.id();
+
+        {
+            let mut ids = self.ids.lock().unwrap();
+
+            if ids.installed.contains(&canister_id) {
+                panic!("Canister '{}' already has code installed.", canister_id)
+            }
+
+            if !ids.reserved.contains(&canister_id) {
+                panic!(
+                    "Canister '{}' was not created using Replica::create_canister.",
+                    canister_id
+                )
+            }
+
+            ids.install(canister_id);
+        }
+
+        self.start_canister(canister)
+    }
+
     /// Return the handle to a canister.
     pub fn get_canister(&self, canister_id: Principal) -> CanisterHandle {
         CanisterHandle {
@@ -128,9 +375,11 @@ impl Replica {
 
     /// Perform the given call in this replica and return a future that will be resolved once the
     /// call is executed.
-    pub(crate) fn perform_call(&self, call: CanisterCall) -> impl Future<Output = CallReply> {
-        let canister_id = call.callee;
-        let message = Message::from(call);
+    pub(crate) fn perform_call(
+        &self,
+        canister_id: Principal,
+        message: Message,
+    ) -> impl Future<Output = CallReply> {
         let (tx, rx) = oneshot::channel();
         self.enqueue_request(canister_id, message, Some(tx));
         async {
@@ -144,20 +393,298 @@ impl Replica {
     pub fn new_call<S: Into<String>>(&self, id: Principal, method: S) -> CallBuilder {
         CallBuilder::new(&self, id, method.into())
     }
+
+    /// Move the replica's clock forward by the given duration and fire the global timer of the
+    /// canisters whose timer has expired by the new time.
+    ///
+    /// The timers are only checked when the time is advanced, messages that are sent after this
+    /// call are executed after the expired timers.
+    pub fn advance_time(&self, duration: Duration) {
+        self.sender
+            .send(ReplicaMessage::AdvanceTime {
+                duration: duration.as_nanos() as u64,
+            })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+    }
+
+    /// Execute a single round of the replica, every canister fires its global timer if it has
+    /// expired and then executes its heartbeat. The canisters are processed one by one in the
+    /// order of their ids, and the returned future resolves once every heartbeat is finished,
+    /// including the calls made by it.
+    pub fn tick(&self) -> impl Future<Output = ()> {
+        let (tx, rx) = oneshot::channel();
+
+        self.sender
+            .send(ReplicaMessage::Tick { reply_sender: tx })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+
+        async {
+            rx.await
+                .expect("ic-kit-runtime: Could not retrieve the result of the tick.")
+        }
+    }
+
+    /// Execute the given number of rounds, see [`Replica::tick`].
+    pub async fn tick_n(&self, count: usize) {
+        for _ in 0..count {
+            self.tick().await;
+        }
+    }
+
+    /// Start the replica's clock from the given time in nanoseconds instead of the system time,
+    /// the clock then only moves forward with [`Replica::advance_time`].
+    pub fn with_time(self, time: u64) -> Self {
+        self.sender
+            .send(ReplicaMessage::SetTime { time })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+        self
+    }
+
+    /// Set the cycle balance of the canisters in this replica, this overrides the balance of
+    /// the env of every message that goes through the replica.
+    pub fn with_balance(self, balance: u128) -> Self {
+        self.sender
+            .send(ReplicaMessage::SetBalance { balance })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+        self
+    }
+
+    /// Set the starting cycle balance of the canisters that are added to this replica from now
+    /// on. Unlike [`Replica::with_balance`] the balance is only set once, see
+    /// [`Canister::set_balance`], so the canisters keep the cycles they spend and receive.
+    pub fn with_initial_balance(self, balance: u128) -> Self {
+        self.sender
+            .send(ReplicaMessage::SetInitialBalance { balance })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+        self
+    }
+
+    /// Send the messages that the canisters print using `ic0::debug_print` to the given function
+    /// instead of the stdout, the function also receives the id of the canister that printed the
+    /// message. This applies to the canisters that do not have their own sink, see
+    /// [`Canister::with_debug_sink`].
+    pub fn with_debug_sink<F>(self, sink: F) -> Self
+    where
+        F: Fn(Principal, String) + Send + Sync + 'static,
+    {
+        self.sender
+            .send(ReplicaMessage::SetDebugSink {
+                sink: Arc::new(sink),
+            })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+        self
+    }
+
+    /// Seed the random number generator which is used to answer the `raw_rand` calls to the
+    /// management canister, by default a fixed seed is used so the tests are reproducible.
+    pub fn with_rng_seed(self, seed: u64) -> Self {
+        self.sender
+            .send(ReplicaMessage::SetRngSeed { seed })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+        self
+    }
+
+    /// Register a canned response for the HTTPS outcalls to the given url, canisters calling the
+    /// management canister's `http_request` get this response instead of an actual request being
+    /// made. Outcalls to a url with no registered response are rejected.
+    pub fn with_http_response<S: Into<String>>(
+        self,
+        url: S,
+        response: CanisterHttpResponse,
+    ) -> Self {
+        self.sender
+            .send(ReplicaMessage::SetHttpResponse {
+                url: url.into(),
+                response,
+            })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+        self
+    }
+
+    /// Run the given function on the canister's event loop with a mutable reference to the
+    /// canister and return the result. The function runs in between the messages and not as
+    /// part of one, so it bypasses the canister's code entirely.
+    ///
+    /// # Panics
+    ///
+    /// If the canister does not exist.
+    pub(crate) async fn with_canister<T, F>(&self, canister_id: Principal, f: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Canister) -> T + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+
+        self.sender
+            .send(ReplicaMessage::WithCanister {
+                canister_id,
+                task: Box::new(move |canister| {
+                    let _ = tx.send(f(canister));
+                }),
+            })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+
+        rx.await.unwrap_or_else(|_| {
+            panic!("ic-kit-runtime: Canister '{}' does not exists", canister_id)
+        })
+    }
+
+    /// Run the lifecycle hook of the given canister, the reply is empty if the hook ran and is a
+    /// rejection if it trapped.
+    ///
+    /// # Panics
+    ///
+    /// If the canister does not exist.
+    pub(crate) async fn run_lifecycle_hook(&self, canister_id: Principal, env: Env) -> CallReply {
+        let (tx, rx) = oneshot::channel();
+
+        self.sender
+            .send(ReplicaMessage::LifecycleHook {
+                canister_id,
+                env,
+                reply_sender: tx,
+            })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+
+        rx.await.unwrap_or_else(|_| {
+            panic!("ic-kit-runtime: Canister '{}' does not exists", canister_id)
+        })
+    }
+
+    /// Capture the state of the given canister, see [`CanisterHandle::snapshot`].
+    pub(crate) async fn snapshot(
+        &self,
+        canister_id: Principal,
+    ) -> Result<CanisterSnapshot, String> {
+        let (tx, rx) = oneshot::channel();
+
+        self.sender
+            .send(ReplicaMessage::Snapshot {
+                canister_id,
+                reply_sender: tx,
+            })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+
+        rx.await.unwrap_or_else(|_| {
+            panic!("ic-kit-runtime: Canister '{}' does not exists", canister_id)
+        })
+    }
+
+    /// Restore the state of the given canister, see [`CanisterHandle::restore`].
+    pub(crate) async fn restore(
+        &self,
+        canister_id: Principal,
+        snapshot: CanisterSnapshot,
+    ) -> Result<(), String> {
+        let (tx, rx) = oneshot::channel();
+
+        self.sender
+            .send(ReplicaMessage::Restore {
+                canister_id,
+                snapshot,
+                reply_sender: tx,
+            })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+
+        rx.await.unwrap_or_else(|_| {
+            panic!("ic-kit-runtime: Canister '{}' does not exists", canister_id)
+        })
+    }
+
+    /// Return the pending state of every canister in this replica, sorted by the canister id.
+    pub async fn pending_state(&self) -> Vec<(Principal, CanisterPendingState)> {
+        let (tx, rx) = oneshot::channel();
+
+        self.sender
+            .send(ReplicaMessage::InspectPending { reply_sender: tx })
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: could not send message to replica"));
+
+        rx.await
+            .expect("ic-kit-runtime: Could not retrieve the pending state of the canisters.")
+    }
+
+    /// Wait until every canister in this replica is idle, that is until no canister has an
+    /// unanswered incoming request or an outgoing call that has not been resolved yet and every
+    /// message sent to the canisters is processed.
+    ///
+    /// This can be used after a call whose downstream calls are not awaited, for example one-way
+    /// calls, to let them settle before checking the state of the canisters. The returned future
+    /// never resolves if the canisters keep calling each other.
+    pub async fn run_until_idle(&self) {
+        loop {
+            // A call that is on its way to another canister is still pending on the caller, and
+            // the messages that are queued for a canister are counted as in flight.
+            let idle = self
+                .pending_state()
+                .await
+                .iter()
+                .all(|(_, state)| state.is_empty());
+
+            if idle && self.in_flight.is_empty() {
+                return;
+            }
+
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Assert that no canister in this replica has an unanswered incoming request or an
+    /// outgoing call that has not been resolved yet.
+    ///
+    /// # Panics
+    ///
+    /// If any canister has pending state, with a report listing each of those canisters.
+    pub async fn assert_no_pending(&self) {
+        let report = self
+            .pending_state()
+            .await
+            .into_iter()
+            .filter(|(_, state)| !state.is_empty())
+            .map(|(canister_id, state)| format!("  {}: {:?}", canister_id, state))
+            .collect::<Vec<_>>();
+
+        if !report.is_empty() {
+            panic!(
+                "ic-kit-runtime: Found canisters with pending requests:\n{}",
+                report.join("\n")
+            );
+        }
+    }
 }
 
 impl Default for Replica {
     /// Create an empty replica and run the start the event loop.
     fn default() -> Self {
         let (sender, rx) = mpsc::unbounded_channel::<ReplicaMessage>();
-        tokio::spawn(replica_worker(rx));
-        Replica { sender }
+        let ids = Arc::new(Mutex::new(CanisterIds::default()));
+        let in_flight = InFlight::default();
+        tokio::spawn(replica_worker(
+            rx,
+            sender.clone(),
+            ids.clone(),
+            in_flight.clone(),
+        ));
+        Replica {
+            sender,
+            ids,
+            in_flight,
+        }
     }
 }
 
 /// Run replica's event loop, gets ReplicaMessages and performs the state transition accordingly.
-async fn replica_worker(mut rx: mpsc::UnboundedReceiver<ReplicaMessage>) {
-    let mut state = ReplicaState::default();
+async fn replica_worker(
+    mut rx: mpsc::UnboundedReceiver<ReplicaMessage>,
+    sender: mpsc::UnboundedSender<ReplicaMessage>,
+    ids: Arc<Mutex<CanisterIds>>,
+    in_flight: InFlight,
+) {
+    let mut state = ReplicaState {
+        sender: Some(sender),
+        ids,
+        in_flight,
+        ..Default::default()
+    };
 
     while let Some(message) = rx.recv().await {
         match message {
@@ -173,17 +700,76 @@ async fn replica_worker(mut rx: mpsc::UnboundedReceiver<ReplicaMessage>) {
             ReplicaMessage::CanisterReply {
                 canister_id,
                 message,
-            } => state.canister_reply(canister_id, message),
+                deadline,
+            } => state.canister_reply(canister_id, message, deadline),
+            ReplicaMessage::BestEffortCall {
+                canister_id,
+                request_id,
+                deadline,
+            } => {
+                state
+                    .best_effort_calls
+                    .insert(request_id, (canister_id, deadline));
+            }
+            ReplicaMessage::InspectPending { reply_sender } => {
+                state.inspect_pending(reply_sender)
+            }
+            ReplicaMessage::CreateCanister { reply_sender } => {
+                // The receiver might have been dropped, which is fine.
+                let _ = reply_sender.send(state.create_canister());
+            }
+            ReplicaMessage::WithCanister { canister_id, task } => {
+                state.with_canister(canister_id, task)
+            }
+            ReplicaMessage::LifecycleHook {
+                canister_id,
+                env,
+                reply_sender,
+            } => state.lifecycle_hook(canister_id, env, reply_sender),
+            ReplicaMessage::Snapshot {
+                canister_id,
+                reply_sender,
+            } => state.send_to_canister(canister_id, CanisterWorkerMessage::Snapshot(reply_sender)),
+            ReplicaMessage::Restore {
+                canister_id,
+                snapshot,
+                reply_sender,
+            } => state.send_to_canister(
+                canister_id,
+                CanisterWorkerMessage::Restore {
+                    snapshot,
+                    reply_sender,
+                },
+            ),
+            ReplicaMessage::AdvanceTime { duration } => state.advance_time(duration),
+            ReplicaMessage::Tick { reply_sender } => state.tick(reply_sender),
+            ReplicaMessage::SetRngSeed { seed } => state.management.set_rng_seed(seed),
+            ReplicaMessage::SetTime { time } => state.time = Some(time),
+            ReplicaMessage::SetBalance { balance } => state.balance = Some(balance),
+            ReplicaMessage::SetInitialBalance { balance } => state.initial_balance = Some(balance),
+            ReplicaMessage::SetDebugSink { sink } => state.set_debug_sink(sink),
+            ReplicaMessage::SetHttpResponse { url, response } => {
+                state.management.set_http_response(url, response)
+            }
         }
     }
 }
 
+/// Return the id of the n-th canister created by [`Replica::create_canister`], this uses the same
+/// format as the canister ids on the IC, a 64-bit big-endian index followed by `0x01 0x01`.
+pub fn canister_id(index: u64) -> Principal {
+    let mut bytes = index.to_be_bytes().to_vec();
+    bytes.extend_from_slice(&[1, 1]);
+    Principal::from_slice(&bytes)
+}
+
 /// Start a dedicated event loop for a canister, this will get CanisterMessage messages from a tokio
 /// channel and perform
 async fn canister_worker(
-    mut rx: mpsc::UnboundedReceiver<ReplicaCanisterRequest>,
+    mut rx: mpsc::UnboundedReceiver<CanisterWorkerMessage>,
     mut replica: mpsc::UnboundedSender<ReplicaMessage>,
     mut canister: Canister,
+    in_flight: InFlight,
 ) {
     let canister_id = canister.id();
 
@@ -191,6 +777,100 @@ async fn canister_worker(
     let mut canister = canister;
 
     while let Some(message) = rx.recv().await {
+        // The message is processed once this iteration is over, by then the calls made by the
+        // canister are pending on the canister itself.
+        let _processed = message
+            .is_counted()
+            .then(|| InFlightGuard(in_flight.clone()));
+
+        let message = match message {
+            CanisterWorkerMessage::Request(message) => message,
+            CanisterWorkerMessage::InspectPending(reply_sender) => {
+                // The receiver might have been dropped, which is fine.
+                let _ = reply_sender.send(canister.pending_state());
+                continue;
+            }
+            CanisterWorkerMessage::WithCanister(task) => {
+                task(&mut canister);
+                continue;
+            }
+            CanisterWorkerMessage::Snapshot(reply_sender) => {
+                let _ = reply_sender.send(canister.snapshot().await);
+                continue;
+            }
+            CanisterWorkerMessage::Restore {
+                snapshot,
+                reply_sender,
+            } => {
+                let _ = reply_sender.send(canister.restore(snapshot).await);
+                continue;
+            }
+            CanisterWorkerMessage::GlobalTimer { time } => {
+                if !canister.take_expired_global_timer(time) {
+                    continue;
+                }
+
+                // Nobody is waiting for the result of the timer, but the canister expects the
+                // channel to be open.
+                let (tx, rx) = oneshot::channel();
+                tokio::spawn(async move {
+                    let _ = rx.await;
+                });
+
+                ReplicaCanisterRequest {
+                    message: Message::Request {
+                        request_id: RequestId::new(),
+                        env: Env::global_timer().with_time(time),
+                    },
+                    reply_sender: Some(tx),
+                }
+            }
+            CanisterWorkerMessage::LifecycleHook { env, reply_sender } => {
+                let result = canister.run_lifecycle_hook(env).await;
+                let _ = reply_sender.send(lifecycle_hook_reply(result));
+                continue;
+            }
+            CanisterWorkerMessage::InstallCode {
+                canister: mut new_canister,
+                upgrade,
+                env,
+                reply_sender,
+            } => {
+                if upgrade {
+                    let pre_upgrade = Env::pre_upgrade().with_time(env.time);
+
+                    if let Err(rejection_message) = canister.run_lifecycle_hook(pre_upgrade).await {
+                        let _ = reply_sender.send(CallReply::Reject {
+                            rejection_code: RejectionCode::CanisterError,
+                            rejection_message: format!(
+                                "Canister {} trapped in pre_upgrade: {}",
+                                canister_id, rejection_message
+                            ),
+                            cycles_refunded: 0,
+                        });
+                        continue;
+                    }
+
+                    new_canister = new_canister.with_stable(canister.take_stable());
+                }
+
+                new_canister = new_canister
+                    .with_controllers(canister.controllers())
+                    .with_max_cycles_per_response(canister.max_cycles_per_response())
+                    .with_stable_limit(canister.stable_limit());
+
+                if let Some(sink) = canister.debug_sink() {
+                    new_canister.inherit_debug_sink(sink);
+                }
+
+                canister = new_canister;
+
+                let result = canister.run_lifecycle_hook(env).await;
+                let _ = reply_sender.send(lifecycle_hook_reply(result));
+                continue;
+            }
+        };
+
         // Perform the message on the canister's thread, the result containing a list of
         // inter-canister call requests is returned here, so we can send each call back to
         // replica.
@@ -198,6 +878,8 @@ async fn canister_worker(
             .process_message(message.message, message.reply_sender)
             .await;
 
+        canister.update_status();
+
         for call in canister_requested_calls {
             // For each call a oneshot channel is created that is used to receive the response
             // from the target canister. We then await for the response in a `tokio::spawn` to not
@@ -208,8 +890,21 @@ async fn canister_worker(
             // TODO(qti3e) Do the optimization - we don't need to send the result to the replica
             // just so that it queues to our own `rx`.
             let request_id = call.request_id;
+            let deadline = call.deadline;
             let (tx, rx) = oneshot::channel();
 
+            if let Some(deadline) = deadline {
+                replica
+                    .send(ReplicaMessage::BestEffortCall {
+                        canister_id,
+                        request_id,
+                        deadline,
+                    })
+                    .unwrap_or_else(|_| {
+                        panic!("ic-kit-runtime: could not send message to replica")
+                    });
+            }
+
             replica
                 .send(ReplicaMessage::CanisterRequest {
                     canister_id: call.callee,
@@ -235,6 +930,7 @@ async fn canister_worker(
                     .send(ReplicaMessage::CanisterReply {
                         canister_id,
                         message,
+                        deadline,
                     })
                     .unwrap_or_else(|_| {
                         panic!("ic-kit-runtime: could not send message to replica")
@@ -244,11 +940,93 @@ async fn canister_worker(
     }
 }
 
+/// The rejection that is delivered to the caller of a best-effort call once its deadline expires.
+fn deadline_expired(request_id: RequestId) -> Message {
+    CallReply::Reject {
+        rejection_code: RejectionCode::SysUnknown,
+        rejection_message: "The deadline of the best-effort call has expired.".to_string(),
+        cycles_refunded: 0,
+    }
+    .to_message(request_id)
+}
+
+/// The reply of the management canister's `install_code` once the init or post_upgrade hook of
+/// the canister is executed, see [`Canister::run_lifecycle_hook`].
+fn lifecycle_hook_reply(result: Result<(), String>) -> CallReply {
+    match result {
+        Ok(()) => CallReply::Reply {
+            data: CANDID_EMPTY_ARG.to_vec(),
+            cycles_refunded: 0,
+        },
+        Err(rejection_message) => CallReply::Reject {
+            rejection_code: RejectionCode::CanisterError,
+            rejection_message,
+            cycles_refunded: 0,
+        },
+    }
+}
+
+impl InFlight {
+    /// Send a message that runs the code of the canister, it is counted until the event loop of
+    /// the canister is done with it.
+    fn send(
+        &self,
+        chan: &mpsc::UnboundedSender<CanisterWorkerMessage>,
+        message: CanisterWorkerMessage,
+    ) -> Result<(), mpsc::error::SendError<CanisterWorkerMessage>> {
+        debug_assert!(message.is_counted());
+        self.0.fetch_add(1, Ordering::SeqCst);
+
+        chan.send(message).map_err(|e| {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+            e
+        })
+    }
+
+    /// Return true if every message that was sent is processed.
+    fn is_empty(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == 0
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        (self.0).0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl CanisterWorkerMessage {
+    /// Return true if the message runs the code of the canister, these messages are counted as
+    /// in flight until they are processed, see [`Replica::run_until_idle`].
+    fn is_counted(&self) -> bool {
+        matches!(
+            self,
+            CanisterWorkerMessage::Request(_)
+                | CanisterWorkerMessage::GlobalTimer { .. }
+                | CanisterWorkerMessage::LifecycleHook { .. }
+                | CanisterWorkerMessage::InstallCode { .. }
+        )
+    }
+}
+
+impl CanisterIds {
+    /// Mark the id as installed, returns false if there is already a canister with this id.
+    fn install(&mut self, canister_id: Principal) -> bool {
+        if !self.installed.insert(canister_id) {
+            return false;
+        }
+
+        self.reserved.remove(&canister_id);
+        true
+    }
+}
+
 impl ReplicaState {
+    /// Register the channel of a canister whose id is already added to the ids.
     pub fn canister_added(
         &mut self,
         canister_id: Principal,
-        channel: mpsc::UnboundedSender<ReplicaCanisterRequest>,
+        channel: mpsc::UnboundedSender<CanisterWorkerMessage>,
     ) {
         if self.canisters.contains_key(&canister_id) {
             panic!(
@@ -257,21 +1035,71 @@ impl ReplicaState {
             )
         }
 
+        if let Some(sink) = self.debug_sink.clone() {
+            channel
+                .send(CanisterWorkerMessage::WithCanister(Box::new(
+                    move |canister| canister.inherit_debug_sink(sink),
+                )))
+                .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+        }
+
+        if let Some(balance) = self.initial_balance {
+            channel
+                .send(CanisterWorkerMessage::WithCanister(Box::new(
+                    move |canister| canister.set_balance(balance),
+                )))
+                .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+        }
+
         self.canisters.insert(canister_id, channel);
     }
 
+    fn set_debug_sink(&mut self, sink: DebugSink) {
+        for chan in self.canisters.values() {
+            let sink = sink.clone();
+            chan.send(CanisterWorkerMessage::WithCanister(Box::new(
+                move |canister| canister.inherit_debug_sink(sink),
+            )))
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+        }
+
+        self.debug_sink = Some(sink);
+    }
+
+    /// Generate a new canister id that is not used or reserved and reserve it.
+    fn create_canister(&mut self) -> Principal {
+        let mut ids = self.ids.lock().unwrap();
+
+        loop {
+            let canister_id = canister_id(self.next_canister_index);
+            self.next_canister_index += 1;
+
+            if !ids.installed.contains(&canister_id) && ids.reserved.insert(canister_id) {
+                return canister_id;
+            }
+        }
+    }
+
     pub fn canister_request(
         &mut self,
         canister_id: Principal,
-        message: Message,
+        mut message: Message,
         reply_sender: Option<oneshot::Sender<CallReply>>,
     ) {
-        if let Some(chan) = self.canisters.get(&canister_id) {
-            chan.send(ReplicaCanisterRequest {
-                message,
-                reply_sender,
-            })
-            .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+        self.update_env(message.env_mut());
+
+        if canister_id == Principal::management_canister() {
+            self.management_request(message, reply_sender);
+        } else if let Some(chan) = self.canisters.get(&canister_id) {
+            self.in_flight
+                .send(
+                    chan,
+                    CanisterWorkerMessage::Request(ReplicaCanisterRequest {
+                        message,
+                        reply_sender,
+                    }),
+                )
+                .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
         } else {
             let cycles_refunded = match message {
                 Message::CustomTask { env, .. } => env.cycles_available,
@@ -290,12 +1118,389 @@ impl ReplicaState {
         }
     }
 
-    fn canister_reply(&mut self, canister_id: Principal, message: Message) {
+    fn management_request(
+        &mut self,
+        message: Message,
+        reply_sender: Option<oneshot::Sender<CallReply>>,
+    ) {
+        let reply = match message {
+            Message::Request { env, .. } if env.method_name.as_deref() == Some("install_code") => {
+                // The reply of a one-way call is dropped.
+                let reply_sender = reply_sender.unwrap_or_else(|| oneshot::channel().0);
+                return self.install_code(env, reply_sender);
+            }
+            Message::Request { env, .. }
+                if env.method_name.as_deref() == Some("create_canister")
+                    && env.entry_mode != EntryMode::InspectMessage =>
+            {
+                let canister_id = self.create_canister();
+
+                CallReply::Reply {
+                    data: encode_one(CanisterIdRecord { canister_id }).unwrap(),
+                    cycles_refunded: env.cycles_available,
+                }
+            }
+            // Calls to the management canister are never inspected.
+            Message::Request { env, .. } if env.entry_mode == EntryMode::InspectMessage => {
+                CallReply::Reply {
+                    data: Vec::new(),
+                    cycles_refunded: 0,
+                }
+            }
+            Message::Request { env, .. } => {
+                let method = env.method_name.unwrap_or_default();
+
+                match self.management.process(&method, &env.args) {
+                    Ok(data) => CallReply::Reply {
+                        data,
+                        cycles_refunded: env.cycles_available,
+                    },
+                    Err((rejection_code, rejection_message)) => CallReply::Reject {
+                        rejection_code,
+                        rejection_message,
+                        cycles_refunded: env.cycles_available,
+                    },
+                }
+            }
+            Message::CustomTask { env, .. } => CallReply::Reject {
+                rejection_code: RejectionCode::DestinationInvalid,
+                rejection_message: "Can not run a custom task on the management canister."
+                    .to_string(),
+                cycles_refunded: env.cycles_available,
+            },
+            Message::Reply { .. } => {
+                panic!("ic-kit-runtime: The management canister does not make calls.")
+            }
+        };
+
+        reply_sender
+            .unwrap()
+            .send(reply)
+            .expect("ic-kit-runtime: Could not send the response.");
+    }
+
+    /// Handle the management canister's `install_code`, the wasm module must be registered using
+    /// [`crate::register_canister_code`]. The reply is sent once the init or post_upgrade hook
+    /// of the installed canister is executed.
+    fn install_code(&mut self, env: Env, reply_sender: oneshot::Sender<CallReply>) {
+        let reject = |rejection_code, rejection_message| CallReply::Reject {
+            rejection_code,
+            rejection_message,
+            cycles_refunded: env.cycles_available,
+        };
+
+        // Calls to the management canister are never inspected.
+        if env.entry_mode == EntryMode::InspectMessage {
+            let _ = reply_sender.send(CallReply::Reply {
+                data: Vec::new(),
+                cycles_refunded: 0,
+            });
+            return;
+        }
+
+        let argument = match decode_one::<InstallCodeArgument>(&env.args) {
+            Ok(argument) => argument,
+            Err(e) => {
+                let _ = reply_sender.send(reject(
+                    RejectionCode::CanisterReject,
+                    format!("Could not decode the install_code argument: {}", e),
+                ));
+                return;
+            }
+        };
+
+        let canister_id = argument.canister_id;
+        // Like `create_canister`, the caller becomes the controller of the new canister.
+        let canister = match get_canister_code(&argument.wasm_module) {
+            Some(builder) => builder(canister_id).with_controllers(vec![env.sender]),
+            None => {
+                let _ = reply_sender.send(reject(
+                    RejectionCode::CanisterReject,
+                    "The wasm module is not registered in the runtime.".to_string(),
+                ));
+                return;
+            }
+        };
+
+        let mut hook_env = match argument.mode {
+            InstallMode::Upgrade => Env::post_upgrade(),
+            _ => Env::init(),
+        }
+        .with_sender(env.sender)
+        .with_raw_args(argument.arg);
+        self.update_env(&mut hook_env);
+
+        match (argument.mode, self.canisters.get(&canister_id)) {
+            (InstallMode::Install, None)
+                if !self.ids.lock().unwrap().reserved.contains(&canister_id) =>
+            {
+                let _ = reply_sender.send(reject(
+                    RejectionCode::DestinationInvalid,
+                    format!("Canister {} does not exist.", canister_id),
+                ));
+            }
+            (InstallMode::Install, None) => {
+                let sender = self
+                    .sender
+                    .clone()
+                    .expect("ic-kit-runtime: The replica's sender is not set.");
+                let (tx, rx) = mpsc::unbounded_channel();
+                self.ids.lock().unwrap().install(canister_id);
+                self.canister_added(canister_id, tx.clone());
+                tokio::spawn(canister_worker(
+                    rx,
+                    sender,
+                    canister,
+                    self.in_flight.clone(),
+                ));
+
+                self.in_flight
+                    .send(
+                        &tx,
+                        CanisterWorkerMessage::LifecycleHook {
+                            env: hook_env,
+                            reply_sender,
+                        },
+                    )
+                    .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+            }
+            (InstallMode::Install, Some(_)) => {
+                let _ = reply_sender.send(reject(
+                    RejectionCode::CanisterReject,
+                    format!("Canister {} already has code installed.", canister_id),
+                ));
+            }
+            (_, None) => {
+                let _ = reply_sender.send(reject(
+                    RejectionCode::DestinationInvalid,
+                    format!("Canister {} does not exist.", canister_id),
+                ));
+            }
+            (mode, Some(chan)) => {
+                self.in_flight
+                    .send(
+                        chan,
+                        CanisterWorkerMessage::InstallCode {
+                            canister,
+                            upgrade: mode == InstallMode::Upgrade,
+                            env: hook_env,
+                            reply_sender,
+                        },
+                    )
+                    .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+            }
+        }
+    }
+
+    fn canister_reply(
+        &mut self,
+        canister_id: Principal,
+        mut message: Message,
+        deadline: Option<u64>,
+    ) {
+        self.update_env(message.env_mut());
+
+        if let (Some(deadline), Message::Reply { reply_to, env }) = (deadline, &message) {
+            let reply_to = *reply_to;
+
+            // The call was already rejected when the clock moved past its deadline, so the late
+            // response is dropped.
+            if self.best_effort_calls.remove(&reply_to).is_none() {
+                return;
+            }
+
+            // The clock can also be moved by `set_time`, which does not expire the calls.
+            if env.time > deadline {
+                message = deadline_expired(reply_to);
+                self.update_env(message.env_mut());
+            }
+        }
+
         let chan = self.canisters.get(&canister_id).unwrap();
-        chan.send(ReplicaCanisterRequest {
-            message,
-            reply_sender: None,
-        })
-        .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the response request."));
+        self.in_flight
+            .send(
+                chan,
+                CanisterWorkerMessage::Request(ReplicaCanisterRequest {
+                    message,
+                    reply_sender: None,
+                }),
+            )
+            .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the response request."));
+    }
+
+    /// Apply the replica's clock and balance to the env of a message.
+    fn update_env(&self, env: &mut Env) {
+        if let Some(time) = self.time {
+            env.time = time;
+        }
+
+        env.time += self.time_offset;
+
+        if let Some(balance) = self.balance {
+            env.balance = balance;
+        }
+    }
+
+    /// Reject the best-effort calls whose deadline has expired by the given time, the response of
+    /// the callee is dropped if it arrives afterwards.
+    fn expire_best_effort_calls(&mut self, time: u64) {
+        let mut expired = Vec::new();
+
+        self.best_effort_calls
+            .retain(|request_id, (canister_id, deadline)| {
+                if *deadline < time {
+                    expired.push((*request_id, *canister_id));
+                    false
+                } else {
+                    true
+                }
+            });
+
+        // Deliver the rejections in the order the calls were made.
+        expired.sort();
+
+        for (request_id, canister_id) in expired {
+            let mut message = deadline_expired(request_id);
+            self.update_env(message.env_mut());
+
+            if let Some(chan) = self.canisters.get(&canister_id) {
+                self.in_flight
+                    .send(
+                        chan,
+                        CanisterWorkerMessage::Request(ReplicaCanisterRequest {
+                            message,
+                            reply_sender: None,
+                        }),
+                    )
+                    .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+            }
+        }
+    }
+
+    fn advance_time(&mut self, duration: u64) {
+        self.time_offset += duration;
+        let time = self.time.unwrap_or_else(now) + self.time_offset;
+        self.expire_best_effort_calls(time);
+
+        for chan in self.canisters.values() {
+            self.in_flight
+                .send(chan, CanisterWorkerMessage::GlobalTimer { time })
+                .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+        }
+    }
+
+    fn tick(&mut self, reply_sender: oneshot::Sender<()>) {
+        let time = self.time.unwrap_or_else(now) + self.time_offset;
+        self.expire_best_effort_calls(time);
+
+        let balance = self.balance;
+        let in_flight = self.in_flight.clone();
+
+        let mut canisters = self
+            .canisters
+            .iter()
+            .map(|(canister_id, chan)| (*canister_id, chan.clone()))
+            .collect::<Vec<_>>();
+        canisters.sort_by_key(|(canister_id, _)| *canister_id);
+
+        // The heartbeats can make calls which need the replica's event loop, so they are awaited
+        // on a separate task.
+        tokio::spawn(async move {
+            for (_, chan) in canisters {
+                let (tx, rx) = oneshot::channel();
+                let mut env = Env::heartbeat().with_time(time);
+
+                if let Some(balance) = balance {
+                    env.balance = balance;
+                }
+
+                let sent = in_flight
+                    .send(&chan, CanisterWorkerMessage::GlobalTimer { time })
+                    .is_ok()
+                    && in_flight
+                        .send(
+                            &chan,
+                            CanisterWorkerMessage::Request(ReplicaCanisterRequest {
+                                message: Message::Request {
+                                    request_id: RequestId::new(),
+                                    env,
+                                },
+                                reply_sender: Some(tx),
+                            }),
+                        )
+                        .is_ok();
+
+                // A canister without a heartbeat method rejects the message, which is fine.
+                if sent {
+                    let _ = rx.await;
+                }
+            }
+
+            // The receiver might have been dropped, which is fine.
+            let _ = reply_sender.send(());
+        });
+    }
+
+    fn with_canister(&mut self, canister_id: Principal, task: CanisterTask) {
+        self.send_to_canister(canister_id, CanisterWorkerMessage::WithCanister(task));
+    }
+
+    fn lifecycle_hook(
+        &mut self,
+        canister_id: Principal,
+        mut env: Env,
+        reply_sender: oneshot::Sender<CallReply>,
+    ) {
+        self.update_env(&mut env);
+
+        // If the canister does not exist, the message is dropped and the caller is notified by
+        // the closed channel.
+        if let Some(chan) = self.canisters.get(&canister_id) {
+            self.in_flight
+                .send(
+                    chan,
+                    CanisterWorkerMessage::LifecycleHook { env, reply_sender },
+                )
+                .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+        }
+    }
+
+    /// Send the message to the event loop of the given canister.
+    fn send_to_canister(&mut self, canister_id: Principal, message: CanisterWorkerMessage) {
+        // If the canister does not exist, the message is dropped and the caller is notified by
+        // the closed channel.
+        if let Some(chan) = self.canisters.get(&canister_id) {
+            chan.send(message)
+                .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+        }
+    }
+
+    fn inspect_pending(
+        &mut self,
+        reply_sender: oneshot::Sender<Vec<(Principal, CanisterPendingState)>>,
+    ) {
+        let mut receivers = Vec::with_capacity(self.canisters.len());
+
+        for (canister_id, chan) in &self.canisters {
+            let (tx, rx) = oneshot::channel();
+            chan.send(CanisterWorkerMessage::InspectPending(tx))
+                .unwrap_or_else(|_| panic!("ic-kit-runtime: Could not enqueue the request."));
+            receivers.push((*canister_id, rx));
+        }
+
+        // Collect the results in a task so we don't block the replica's event loop.
+        tokio::spawn(async move {
+            let mut result = Vec::with_capacity(receivers.len());
+
+            for (canister_id, rx) in receivers {
+                let state = rx
+                    .await
+                    .expect("ic-kit-runtime: Could not retrieve the canister's pending state.");
+                result.push((canister_id, state));
+            }
+
+            result.sort_by(|a, b| a.0.cmp(&b.0));
+            let _ = reply_sender.send(result);
+        });
     }
 }
@@ -0,0 +1,12 @@
+//! Helpers that are shared by the tests of this crate.
+
+use std::future::Future;
+
+/// Run the future to completion on a new single-threaded tokio runtime, the replica and the
+/// canisters spawn their event loops on it.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
@@ -33,9 +33,11 @@ pub enum EntryMode {
     PreUpgrade,
     PostUpgrade,
     Heartbeat,
+    GlobalTimer,
     InspectMessage,
     Update,
     Query,
+    CompositeQuery,
     ReplyCallback,
     RejectCallback,
     CleanupCallback,
@@ -109,6 +111,11 @@ pub struct CanisterCall {
     pub method: String,
     pub payment: u128,
     pub arg: Vec<u8>,
+    /// The entry mode the call is executed with on the callee.
+    pub entry_mode: EntryMode,
+    /// The time in nanoseconds after which the caller no longer waits for the response of a
+    /// best-effort call, `None` for the other calls.
+    pub deadline: Option<u64>,
 }
 
 impl From<CanisterCall> for Message {
@@ -116,7 +123,7 @@ impl From<CanisterCall> for Message {
         Message::Request {
             request_id: call.request_id,
             env: Env::default()
-                .with_entry_mode(EntryMode::Update)
+                .with_entry_mode(call.entry_mode)
                 .with_sender(call.sender)
                 .with_method_name(call.method)
                 .with_cycles_available(call.payment)
@@ -157,6 +164,20 @@ impl Env {
             .with_method_name(method_name)
     }
 
+    /// Create a new env for a composite query call.
+    pub fn composite_query<S: Into<String>>(method_name: S) -> Self {
+        Self::default()
+            .with_entry_mode(EntryMode::CompositeQuery)
+            .with_method_name(method_name)
+    }
+
+    /// Create a new env for inspecting an ingress message to the given method.
+    pub fn inspect_message<S: Into<String>>(method_name: S) -> Self {
+        Self::default()
+            .with_entry_mode(EntryMode::InspectMessage)
+            .with_method_name(method_name)
+    }
+
     /// Create a new env for a call to the init function.
     pub fn init() -> Self {
         Self::default().with_entry_mode(EntryMode::Init)
@@ -177,6 +198,11 @@ impl Env {
         Self::default().with_entry_mode(EntryMode::Heartbeat)
     }
 
+    /// Create a new env for a call to the global timer function.
+    pub fn global_timer() -> Self {
+        Self::default().with_entry_mode(EntryMode::GlobalTimer)
+    }
+
     /// Determines the canister's cycle balance for this call.
     pub fn with_balance(mut self, balance: u128) -> Self {
         self.balance = balance;
@@ -261,6 +287,7 @@ impl Env {
             EntryMode::PreUpgrade => "canister_pre_upgrade".to_string(),
             EntryMode::PostUpgrade => "canister_post_upgrade".to_string(),
             EntryMode::Heartbeat => "canister_heartbeat".to_string(),
+            EntryMode::GlobalTimer => "canister_global_timer".to_string(),
             EntryMode::InspectMessage => "canister_inspect_message".to_string(),
             EntryMode::Update => {
                 format!(
@@ -272,6 +299,10 @@ impl Env {
                 "canister_query {}",
                 self.method_name.as_ref().unwrap_or(&String::new())
             ),
+            EntryMode::CompositeQuery => format!(
+                "canister_composite_query {}",
+                self.method_name.as_ref().unwrap_or(&String::new())
+            ),
             EntryMode::ReplyCallback => "reply callback".to_string(),
             EntryMode::RejectCallback => "reject callback".to_string(),
             EntryMode::CleanupCallback => "cleanup callback".to_string(),
@@ -292,12 +323,29 @@ impl Env {
                 "canister_update {}",
                 self.method_name.as_ref().unwrap_or(&String::new())
             ),
+            // A composite query can call the plain query methods as well.
+            EntryMode::CompositeQuery => format!(
+                "canister_query {}",
+                self.method_name.as_ref().unwrap_or(&String::new())
+            ),
             _ => self.get_entry_point_name(),
         }
     }
 }
 
-fn now() -> u64 {
+impl Message {
+    /// Return a mutable reference to the env of this message.
+    pub fn env_mut(&mut self) -> &mut Env {
+        match self {
+            Message::CustomTask { env, .. } => env,
+            Message::Request { env, .. } => env,
+            Message::Reply { env, .. } => env,
+        }
+    }
+}
+
+/// Return the current system time in nanoseconds.
+pub(crate) fn now() -> u64 {
     let now = SystemTime::now();
     let unix = now
         .duration_since(UNIX_EPOCH)
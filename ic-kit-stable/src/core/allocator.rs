@@ -1,7 +1,8 @@
 use crate::core::checksum::CheckedU40;
-use crate::core::hole::HoleList;
+use crate::core::error::StableError;
+use crate::core::hole::{AllocationStrategy, HoleList};
 use crate::core::memory::{DefaultMemory, IcMemory, Memory};
-use crate::core::utils::read_struct;
+use crate::core::utils::{read_struct, write_struct};
 use ic_kit::stable::StableMemoryError;
 
 /// An address to a block.
@@ -16,16 +17,51 @@ pub type BlockSize = u64;
 /// If the node is used then next is overwritten by content.
 pub const MIN_ALLOCATION_SIZE: BlockSize = 16;
 
-// TODO(qti3e) next steps:
-// write the HoleList root to stable storage at the first block.
-// load the HoleList from stable storage if present.
-
 /// An allocator over the stable storage. This allocator assumes that it owns the entire stable
 /// storage if there are already data in the stable storage.
 pub struct StableAllocator<M: Memory = DefaultMemory> {
     hole_list: HoleList<M>,
 }
 
+/// The state of a [`StableAllocator`] that can be persisted across upgrades, the holes themselves
+/// already live on the stable storage, so this only contains the head of each of the hole lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct AllocatorState {
+    roots: [BlockAddress; 36],
+}
+
+/// Marks the page that holds the state written by [`StableAllocator::save_to_stable`].
+const SAVED_STATE_MAGIC: u64 = u64::from_le_bytes(*b"ICKALLOC");
+
+/// A summary of how the stable storage is used, see [`StableAllocator::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    /// The total size of the stable storage in bytes.
+    pub total_size: u64,
+    /// The number of bytes that are in use, including the size header of each block.
+    pub allocated: u64,
+    /// The number of bytes in the free holes.
+    pub free: u64,
+    /// The size of the largest free hole in bytes.
+    pub largest_free_hole: u64,
+    /// The number of free holes.
+    pub holes: u64,
+}
+
+impl AllocatorStats {
+    /// The part of the free space that is not in the largest hole, a value between `0.0` and
+    /// `1.0`. This is zero when all of the free space is contiguous and approaches one as the
+    /// free space is split into more holes.
+    pub fn fragmentation(&self) -> f64 {
+        if self.free == 0 {
+            return 0.0;
+        }
+
+        1.0 - self.largest_free_hole as f64 / self.free as f64
+    }
+}
+
 impl<M: Memory> StableAllocator<M> {
     pub fn new() -> Self {
         Self {
@@ -33,6 +69,90 @@ impl<M: Memory> StableAllocator<M> {
         }
     }
 
+    /// Use the given strategy to pick the free holes for the allocations, the strategy is not
+    /// part of the saved state and must be set again after [`load_state`](Self::load_state).
+    pub fn with_strategy(mut self, strategy: AllocationStrategy) -> Self {
+        self.hole_list.set_strategy(strategy);
+        self
+    }
+
+    /// Flush the allocator's free list to the stable storage and return the state that can be
+    /// passed to [`load_state`](Self::load_state) after an upgrade.
+    pub fn save_state(&self) -> AllocatorState {
+        AllocatorState {
+            roots: self.hole_list.save(),
+        }
+    }
+
+    /// Restore an allocator from a state returned by [`save_state`](Self::save_state), returns an
+    /// error if the free list on the stable storage is corrupted.
+    pub fn load_state(state: AllocatorState) -> Result<Self, StableError> {
+        Ok(Self {
+            hole_list: HoleList::load(&state.roots)?,
+        })
+    }
+
+    /// Write the state of the allocator to a new page at the end of the stable storage, so it can
+    /// be restored using [`load_from_stable`](Self::load_from_stable) after an upgrade. Nothing
+    /// must be allocated after this call.
+    pub fn save_to_stable(&self) -> Result<(), StableMemoryError> {
+        let state = self.save_state();
+        let start = M::stable_grow(1);
+
+        if start == -1 {
+            return Err(StableMemoryError::OutOfMemory);
+        }
+
+        let addr = (start as u64) << 16;
+        write_struct::<M, u64>(addr, &SAVED_STATE_MAGIC);
+        write_struct::<M, AllocatorState>(addr + 8, &state);
+
+        Ok(())
+    }
+
+    /// Restore the allocator that was written by [`save_to_stable`](Self::save_to_stable), the
+    /// page that held the state is given back to the allocator. Returns a new allocator if the
+    /// stable storage is empty, and an error if the free list on the stable storage is corrupted.
+    ///
+    /// # Panics
+    ///
+    /// If the last page of the stable storage does not hold a saved state.
+    pub fn load_from_stable() -> Result<Self, StableError> {
+        let size = M::stable_size();
+
+        if size == 0 {
+            return Ok(Self::new());
+        }
+
+        let addr = (size - 1) << 16;
+        if read_struct::<M, u64>(addr) != SAVED_STATE_MAGIC {
+            panic!("The allocator state was not saved to the stable storage.");
+        }
+
+        let state = read_struct::<M, AllocatorState>(addr + 8);
+        let mut allocator = Self::load_state(state)?;
+        allocator.hole_list.insert(addr, 1 << 16);
+        Ok(allocator)
+    }
+
+    /// Return the statistics about the stable storage usage by walking the free holes, this does
+    /// not read the stable storage.
+    pub fn stats(&self) -> AllocatorStats {
+        let mut stats = AllocatorStats {
+            total_size: M::stable_size() << 16,
+            ..AllocatorStats::default()
+        };
+
+        for (_, size) in self.hole_list.holes() {
+            stats.free += size;
+            stats.largest_free_hole = stats.largest_free_hole.max(size);
+            stats.holes += 1;
+        }
+
+        stats.allocated = stats.total_size - stats.free;
+        stats
+    }
+
     /// Allocate a stable storage block with the given size.
     pub fn allocate(&mut self, size: BlockSize) -> Result<BlockAddress, StableMemoryError> {
         // we need 8 more bytes to store the CheckedU40 for the block size.
@@ -87,6 +207,7 @@ impl<M: Memory> StableAllocator<M> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::global::{allocate, free, reset_global_allocator, save_allocator_state};
 
     #[test]
     #[should_panic]
@@ -105,4 +226,273 @@ mod tests {
         allocator.free(8);
         assert_eq!(allocator.allocate(100), Ok(8));
     }
+
+    #[test]
+    fn save_and_load_state() {
+        let mut allocator = StableAllocator::<DefaultMemory>::new();
+        let blocks = (0..10)
+            .map(|_| allocator.allocate(100).unwrap())
+            .collect::<Vec<_>>();
+
+        allocator.free(blocks[2]);
+        allocator.free(blocks[5]);
+        allocator.free(blocks[6]);
+
+        let state = allocator.save_state();
+        drop(allocator);
+
+        let mut allocator = StableAllocator::<DefaultMemory>::load_state(state).unwrap();
+        assert_eq!(allocator.allocate(100), Ok(blocks[2]));
+        assert_eq!(allocator.allocate(100), Ok(blocks[5]));
+        assert_eq!(allocator.allocate(100), Ok(blocks[6]));
+        assert_eq!(allocator.allocate(100), Ok(blocks[9] + 108));
+    }
+
+    #[test]
+    fn save_to_stable() {
+        reset_global_allocator(StableAllocator::new());
+        let blocks = (0..10).map(|_| allocate(100).unwrap()).collect::<Vec<_>>();
+
+        free(blocks[3]);
+        save_allocator_state().unwrap();
+        let size = DefaultMemory::stable_size();
+
+        // pre_upgrade -> post_upgrade, the heap is gone and the allocator is read back.
+        reset_global_allocator(StableAllocator::load_from_stable().unwrap());
+        assert_eq!(allocate(100), Ok(blocks[3]));
+        assert_eq!(allocate(100), Ok(blocks[9] + 108));
+
+        // The page that held the state is free again.
+        allocate(1 << 15).unwrap();
+        assert_eq!(DefaultMemory::stable_size(), size);
+    }
+
+    #[test]
+    #[should_panic(expected = "The allocator state was not saved")]
+    fn load_from_stable_without_save() {
+        let mut allocator = StableAllocator::<DefaultMemory>::new();
+        allocator.allocate(100).unwrap();
+        let _ = StableAllocator::<DefaultMemory>::load_from_stable();
+    }
+
+    #[test]
+    fn stable_raw_access() {
+        use ic_kit::rt::types::Env;
+        use ic_kit::rt::{Canister, Replica, TokioRuntimeBuilder};
+        use ic_kit::Principal;
+
+        TokioRuntimeBuilder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let replica = Replica::default();
+                let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+
+                canister
+                    .custom(
+                        || {
+                            let mut allocator = StableAllocator::<IcMemory>::new();
+                            let block = allocator.allocate(100).unwrap();
+                            allocator.allocate(100).unwrap();
+                            allocator.free(block);
+                            allocator.save_to_stable().unwrap();
+                            ic_kit::utils::reply(&[]);
+                        },
+                        Env::default(),
+                    )
+                    .await
+                    .assert_ok();
+
+                // Flip a byte in the size of the hole that starts at the beginning of the
+                // stable storage.
+                let byte = canister.stable_read_raw(0, 1).await[0];
+                canister.stable_write_raw(0, vec![!byte]).await;
+
+                // The corruption is reported instead of trapping the canister.
+                canister
+                    .custom(
+                        || {
+                            let result = StableAllocator::<IcMemory>::load_from_stable();
+                            assert!(matches!(result, Err(StableError::InvalidChecksum { .. })));
+                            ic_kit::utils::reply(&[]);
+                        },
+                        Env::default(),
+                    )
+                    .await
+                    .assert_ok();
+            });
+    }
+
+    #[test]
+    fn stats() {
+        let mut allocator = StableAllocator::<DefaultMemory>::new();
+        assert_eq!(allocator.stats(), AllocatorStats::default());
+        assert_eq!(allocator.stats().fragmentation(), 0.0);
+
+        let blocks = (0..3)
+            .map(|_| allocator.allocate(100).unwrap())
+            .collect::<Vec<_>>();
+
+        let stats = allocator.stats();
+        assert_eq!(stats.total_size, 1 << 16);
+        assert_eq!(stats.allocated, 3 * 108);
+        assert_eq!(stats.free, (1 << 16) - 3 * 108);
+        assert_eq!(stats.largest_free_hole, stats.free);
+        assert_eq!(stats.holes, 1);
+        assert_eq!(stats.fragmentation(), 0.0);
+
+        allocator.free(blocks[1]);
+
+        let stats = allocator.stats();
+        assert_eq!(stats.allocated, 2 * 108);
+        assert_eq!(stats.free, (1 << 16) - 2 * 108);
+        assert_eq!(stats.largest_free_hole, (1 << 16) - 3 * 108);
+        assert_eq!(stats.holes, 2);
+        assert!(stats.fragmentation() > 0.0);
+
+        allocator.free(blocks[0]);
+        allocator.free(blocks[2]);
+
+        let stats = allocator.stats();
+        assert_eq!(stats.allocated, 0);
+        assert_eq!(stats.holes, 1);
+    }
+
+    #[test]
+    fn strategies_do_not_overlap() {
+        let strategies = [
+            AllocationStrategy::Hybrid,
+            AllocationStrategy::BestFit,
+            AllocationStrategy::WorstFit,
+            AllocationStrategy::FirstFit,
+        ];
+
+        for strategy in strategies {
+            let _guard = crate::core::test_util::fresh_memory();
+            let mut allocator = StableAllocator::<DefaultMemory>::new().with_strategy(strategy);
+            let mut live: Vec<(BlockAddress, BlockSize)> = Vec::new();
+            let mut seed = 42u64;
+
+            for _ in 0..500 {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                let r = seed >> 33;
+
+                if r % 3 == 0 && !live.is_empty() {
+                    let (addr, _) = live.swap_remove(r as usize % live.len());
+                    allocator.free(addr);
+                    continue;
+                }
+
+                let size = 1 + r % 500;
+                let addr = allocator.allocate(size).unwrap();
+
+                for &(a, s) in &live {
+                    assert!(
+                        addr + size <= a || a + s <= addr,
+                        "{:?}: block {} overlaps {}",
+                        strategy,
+                        addr,
+                        a
+                    );
+                }
+
+                live.push((addr, size));
+            }
+        }
+    }
+
+    #[test]
+    fn strategies_fragmentation() {
+        enum Op {
+            Allocate(BlockSize),
+            /// Free the block of the n-th allocation.
+            Free(usize),
+        }
+
+        // A store of small records where a third of the records are removed again, next to a
+        // buffer that is moved to a twice as large block whenever it grows.
+        let mut trace = Vec::new();
+        let mut records = Vec::new();
+        let mut buffer = None;
+        let mut buffer_size = 64;
+        let mut allocations = 0;
+        let mut seed = 7u64;
+
+        for step in 0..2000 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            let r = seed >> 33;
+
+            if step % 50 == 0 {
+                trace.push(Op::Allocate(buffer_size));
+                if let Some(old) = buffer.replace(allocations) {
+                    trace.push(Op::Free(old));
+                }
+                allocations += 1;
+                buffer_size = (buffer_size * 2).min(1 << 14);
+            } else if r % 3 == 0 && !records.is_empty() {
+                let id = records.swap_remove(r as usize % records.len());
+                trace.push(Op::Free(id));
+            } else {
+                trace.push(Op::Allocate(32 + r % 224));
+                records.push(allocations);
+                allocations += 1;
+            }
+        }
+
+        let strategies = [
+            AllocationStrategy::Hybrid,
+            AllocationStrategy::BestFit,
+            AllocationStrategy::WorstFit,
+            AllocationStrategy::FirstFit,
+        ];
+
+        for strategy in strategies {
+            let _guard = crate::core::test_util::fresh_memory();
+            let mut allocator = StableAllocator::<DefaultMemory>::new().with_strategy(strategy);
+            let mut blocks: Vec<Option<(BlockAddress, BlockSize)>> = Vec::new();
+            let mut live = 0;
+
+            for op in &trace {
+                match *op {
+                    Op::Allocate(size) => {
+                        let addr = allocator.allocate(size).unwrap();
+                        blocks.push(Some((addr, size)));
+                        live += size + 8;
+                    }
+                    Op::Free(id) => {
+                        let (addr, size) = blocks[id].take().unwrap();
+                        allocator.free(addr);
+                        live -= size + 8;
+                    }
+                }
+            }
+
+            let stats = allocator.stats();
+            println!(
+                "{:?}: fragmentation = {:.3}, {:?}",
+                strategy,
+                stats.fragmentation(),
+                stats
+            );
+
+            // The blocks are rounded up, so the allocator can use more than what was asked for.
+            assert!(stats.allocated >= live, "{:?}", strategy);
+            assert!(stats.holes > 1, "{:?}", strategy);
+            assert!(
+                (0.0..1.0).contains(&stats.fragmentation()),
+                "{:?}",
+                strategy
+            );
+
+            // Whatever the strategy, the holes are merged back into one once everything is freed.
+            for (addr, _) in blocks.into_iter().flatten() {
+                allocator.free(addr);
+            }
+
+            let stats = allocator.stats();
+            assert_eq!(stats.allocated, 0, "{:?}", strategy);
+            assert_eq!(stats.holes, 1, "{:?}", strategy);
+            assert_eq!(stats.fragmentation(), 0.0, "{:?}", strategy);
+        }
+    }
 }
@@ -0,0 +1,791 @@
+use crate::core::allocator::{BlockAddress, BlockSize};
+use crate::core::global::{allocate, with_lru};
+use crate::core::memory::DefaultMemory;
+use crate::core::utils::{read_struct, write_struct};
+use ic_kit::candid::{decode_one, encode_one, CandidType};
+use ic_kit::stable::StableMemoryError;
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
+
+/// The maximum size of a candid encoded key in a [`StableBTreeMap`].
+pub const BTREE_MAX_KEY_SIZE: usize = 64;
+
+/// The maximum size of a candid encoded value in a [`StableBTreeMap`].
+pub const BTREE_MAX_VALUE_SIZE: usize = 256;
+
+/// The minimum degree of the tree, every node except the root has between `B - 1` and `2B - 1`
+/// keys.
+const B: usize = 6;
+
+/// The maximum number of keys in a node.
+const CAPACITY: usize = 2 * B - 1;
+
+/// The size of the block allocated for each node: the leaf flag, the number of keys, the keys and
+/// values each prefixed by their u16 length and the address of the children.
+const NODE_SIZE: usize = 2
+    + CAPACITY * (2 + BTREE_MAX_KEY_SIZE)
+    + CAPACITY * (2 + BTREE_MAX_VALUE_SIZE)
+    + (CAPACITY + 1) * 8;
+
+/// The header of a map which is stored at the map's address.
+#[repr(packed)]
+struct StableBTreeMapHeader {
+    /// Address of the root node, or zero if the map is empty.
+    root: BlockAddress,
+    len: u64,
+}
+
+/// An ordered map that lives on the stable storage, the nodes of the tree are allocated using the
+/// global allocator and are read through the LRU cache, writes go to the stable storage right
+/// away.
+///
+/// Keys and values are candid encoded and must not be larger than [`BTREE_MAX_KEY_SIZE`] and
+/// [`BTREE_MAX_VALUE_SIZE`] bytes respectively once encoded.
+///
+/// Just like [`StableVec`](crate::StableVec) the address of the map never changes, so it can be
+/// persisted across upgrades.
+pub struct StableBTreeMap<K, V> {
+    address: BlockAddress,
+    _marker: PhantomData<(K, V)>,
+}
+
+/// A node of the tree loaded in the heap, the values are kept encoded.
+struct Node<K> {
+    address: BlockAddress,
+    keys: Vec<K>,
+    values: Vec<Vec<u8>>,
+    /// The children of the node, empty for leaf nodes.
+    children: Vec<BlockAddress>,
+}
+
+impl<K, V> StableBTreeMap<K, V>
+where
+    K: CandidType + DeserializeOwned + Ord,
+    V: CandidType + DeserializeOwned,
+{
+    /// Allocate a new empty map.
+    pub fn new() -> Result<Self, StableMemoryError> {
+        let address = allocate(std::mem::size_of::<StableBTreeMapHeader>() as BlockSize)?;
+
+        write_struct::<DefaultMemory, StableBTreeMapHeader>(
+            address,
+            &StableBTreeMapHeader { root: 0, len: 0 },
+        );
+
+        Ok(Self::from_address(address))
+    }
+
+    /// Load a map that was created earlier at the given address.
+    pub fn from_address(address: BlockAddress) -> Self {
+        Self {
+            address,
+            _marker: PhantomData::default(),
+        }
+    }
+
+    /// Return the address of this map.
+    pub fn address(&self) -> BlockAddress {
+        self.address
+    }
+
+    /// Return the number of entries in the map.
+    pub fn len(&self) -> u64 {
+        self.header().len
+    }
+
+    /// Returns true if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the value associated with the key.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut address = self.header().root;
+
+        while address != 0 {
+            let node = Node::<K>::load(address);
+
+            match node.keys.binary_search(key) {
+                Ok(i) => return Some(decode_value(&node.values[i])),
+                Err(_) if node.is_leaf() => return None,
+                Err(i) => address = node.children[i],
+            }
+        }
+
+        None
+    }
+
+    /// Returns true if the map contains the key.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Insert the value for the given key and return the previous value.
+    ///
+    /// # Panics
+    ///
+    /// If the encoded key or value is larger than the max size.
+    pub fn insert(&mut self, key: K, value: &V) -> Result<Option<V>, StableMemoryError> {
+        assert!(
+            encode_one(&key).expect("Could not encode the key.").len() <= BTREE_MAX_KEY_SIZE,
+            "The key is larger than {} bytes.",
+            BTREE_MAX_KEY_SIZE
+        );
+
+        let value = encode_one(value).expect("Could not encode the value.");
+        assert!(
+            value.len() <= BTREE_MAX_VALUE_SIZE,
+            "The value is larger than {} bytes.",
+            BTREE_MAX_VALUE_SIZE
+        );
+
+        let mut header = self.header();
+
+        if header.root == 0 {
+            let mut root = Node::new()?;
+            root.keys.push(key);
+            root.values.push(value);
+            root.save();
+
+            header.root = root.address;
+            header.len = 1;
+            self.set_header(header);
+
+            return Ok(None);
+        }
+
+        let mut root = Node::load(header.root);
+
+        if root.keys.len() == CAPACITY {
+            let mut new_root = Node::new()?;
+            new_root.children.push(root.address);
+
+            if let Err(e) = split_child(&mut new_root, 0, &mut root) {
+                new_root.free();
+                return Err(e);
+            }
+
+            header.root = new_root.address;
+            self.set_header(header);
+            root = new_root;
+        }
+
+        let old = insert_non_full(root, key, value)?;
+
+        if old.is_none() {
+            let mut header = self.header();
+            header.len += 1;
+            self.set_header(header);
+        }
+
+        Ok(old.map(|v| decode_value(&v)))
+    }
+
+    /// Remove the key from the map and return its value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let mut header = self.header();
+
+        if header.root == 0 {
+            return None;
+        }
+
+        let mut root = Node::load(header.root);
+        let value = remove_from(&mut root, key);
+
+        // The root is the only node that can become empty, in that case its only child becomes
+        // the new root.
+        if root.keys.is_empty() {
+            header.root = root.children.first().copied().unwrap_or(0);
+            root.free();
+        }
+
+        if value.is_some() {
+            header.len -= 1;
+        }
+
+        self.set_header(header);
+
+        value.map(|v| decode_value(&v))
+    }
+
+    /// Return an iterator over the entries of the map in the key order.
+    pub fn iter(&self) -> StableBTreeMapIter<K, V> {
+        StableBTreeMapIter::new(self.header().root, Bound::Unbounded, Bound::Unbounded)
+    }
+
+    /// Return an iterator over the entries in the given range of keys.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> StableBTreeMapIter<K, V>
+    where
+        K: Clone,
+    {
+        StableBTreeMapIter::new(
+            self.header().root,
+            range.start_bound(),
+            range.end_bound().cloned(),
+        )
+    }
+
+    #[inline]
+    fn header(&self) -> StableBTreeMapHeader {
+        read_struct::<DefaultMemory, StableBTreeMapHeader>(self.address)
+    }
+
+    #[inline]
+    fn set_header(&self, header: StableBTreeMapHeader) {
+        write_struct::<DefaultMemory, StableBTreeMapHeader>(self.address, &header);
+    }
+}
+
+/// An iterator over the entries of a [`StableBTreeMap`].
+pub struct StableBTreeMapIter<K, V> {
+    /// The path from the root to the current node.
+    stack: Vec<StableBTreeMapIterEntry<K>>,
+    end: Bound<K>,
+    _marker: PhantomData<V>,
+}
+
+struct StableBTreeMapIterEntry<K> {
+    keys: std::vec::IntoIter<K>,
+    values: std::vec::IntoIter<Vec<u8>>,
+    children: Vec<BlockAddress>,
+    /// Index of the child that comes right before the next key.
+    child: usize,
+}
+
+impl<K, V> StableBTreeMapIter<K, V>
+where
+    K: CandidType + DeserializeOwned + Ord,
+{
+    fn new(root: BlockAddress, start: Bound<&K>, end: Bound<K>) -> Self {
+        let mut iter = Self {
+            stack: Vec::new(),
+            end,
+            _marker: PhantomData::default(),
+        };
+
+        let mut address = root;
+
+        while address != 0 {
+            let node = Node::<K>::load(address);
+            let i = match start {
+                Bound::Included(key) => node.keys.partition_point(|k| k < key),
+                Bound::Excluded(key) => node.keys.partition_point(|k| k <= key),
+                Bound::Unbounded => 0,
+            };
+
+            address = node.children.get(i).copied().unwrap_or(0);
+            iter.push(node, i);
+        }
+
+        iter
+    }
+
+    fn push(&mut self, mut node: Node<K>, start: usize) {
+        self.stack.push(StableBTreeMapIterEntry {
+            keys: node.keys.split_off(start).into_iter(),
+            values: node.values.split_off(start).into_iter(),
+            children: node.children,
+            child: start,
+        });
+    }
+
+    fn push_leftmost(&mut self, mut address: BlockAddress) {
+        while address != 0 {
+            let node = Node::<K>::load(address);
+            address = node.children.first().copied().unwrap_or(0);
+            self.push(node, 0);
+        }
+    }
+}
+
+impl<K, V> Iterator for StableBTreeMapIter<K, V>
+where
+    K: CandidType + DeserializeOwned + Ord,
+    V: CandidType + DeserializeOwned,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = self.stack.last_mut()?;
+
+            let key = match entry.keys.next() {
+                Some(key) => key,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+
+            let value = entry.values.next().unwrap();
+
+            let is_past_end = match &self.end {
+                Bound::Included(end) => &key > end,
+                Bound::Excluded(end) => &key >= end,
+                Bound::Unbounded => false,
+            };
+
+            if is_past_end {
+                self.stack.clear();
+                return None;
+            }
+
+            entry.child += 1;
+            if let Some(child) = entry.children.get(entry.child).copied() {
+                self.push_leftmost(child);
+            }
+
+            return Some((key, decode_value(&value)));
+        }
+    }
+}
+
+impl<K> Node<K>
+where
+    K: CandidType + DeserializeOwned + Ord,
+{
+    /// Allocate the block for a new empty node.
+    fn new() -> Result<Self, StableMemoryError> {
+        Ok(Self {
+            address: allocate(NODE_SIZE as BlockSize)?,
+            keys: Vec::with_capacity(CAPACITY),
+            values: Vec::with_capacity(CAPACITY),
+            children: Vec::new(),
+        })
+    }
+
+    /// Read the node at the given address.
+    ///
+    /// # Panics
+    ///
+    /// If the block header at the address is invalid.
+    fn load(address: BlockAddress) -> Self {
+        let data = with_lru(|lru| lru.get(address)).unwrap_or_else(|e| panic!("{}", e));
+        let bytes = unsafe { std::slice::from_raw_parts(data as *const u8, NODE_SIZE) };
+
+        let is_leaf = bytes[0] == 1;
+        let len = bytes[1] as usize;
+        let mut offset = 2;
+
+        let mut keys = Vec::with_capacity(CAPACITY);
+        let mut values = Vec::with_capacity(CAPACITY);
+        let mut children = Vec::new();
+
+        for _ in 0..len {
+            let size = read_u16(bytes, &mut offset) as usize;
+            let key = decode_one(read_bytes(bytes, &mut offset, size))
+                .expect("Could not decode the key in the stable btree map.");
+            keys.push(key);
+
+            let size = read_u16(bytes, &mut offset) as usize;
+            values.push(read_bytes(bytes, &mut offset, size).to_vec());
+        }
+
+        if !is_leaf {
+            for _ in 0..=len {
+                let address = read_bytes(bytes, &mut offset, 8);
+                children.push(BlockAddress::from_le_bytes(address.try_into().unwrap()));
+            }
+        }
+
+        Self {
+            address,
+            keys,
+            values,
+            children,
+        }
+    }
+
+    /// Write the node to the stable storage.
+    fn save(&self) {
+        let mut bytes = Vec::with_capacity(NODE_SIZE);
+        bytes.push(self.is_leaf() as u8);
+        bytes.push(self.keys.len() as u8);
+
+        for (key, value) in self.keys.iter().zip(self.values.iter()) {
+            let key = encode_one(key).expect("Could not encode the key.");
+            bytes.extend_from_slice(&(key.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&key);
+            bytes.extend_from_slice(&(value.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(value);
+        }
+
+        for child in &self.children {
+            bytes.extend_from_slice(&child.to_le_bytes());
+        }
+
+        with_lru(|lru| lru.write_through(self.address, &bytes));
+    }
+
+    /// Free the block of this node.
+    fn free(self) {
+        with_lru(|lru| lru.free(self.address));
+    }
+
+    #[inline]
+    fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+}
+
+/// Split the full child at the given index of the parent into two nodes and move its median key
+/// to the parent.
+fn split_child<K>(
+    parent: &mut Node<K>,
+    i: usize,
+    child: &mut Node<K>,
+) -> Result<(), StableMemoryError>
+where
+    K: CandidType + DeserializeOwned + Ord,
+{
+    let mut sibling = Node::new()?;
+    sibling.keys = child.keys.split_off(B);
+    sibling.values = child.values.split_off(B);
+
+    if !child.is_leaf() {
+        sibling.children = child.children.split_off(B);
+    }
+
+    parent.keys.insert(i, child.keys.pop().unwrap());
+    parent.values.insert(i, child.values.pop().unwrap());
+    parent.children.insert(i + 1, sibling.address);
+
+    child.save();
+    sibling.save();
+    parent.save();
+
+    Ok(())
+}
+
+/// Insert the key into the subtree of a node that is not full, full nodes on the way down are
+/// split so there is always room for the key.
+fn insert_non_full<K>(
+    mut node: Node<K>,
+    key: K,
+    value: Vec<u8>,
+) -> Result<Option<Vec<u8>>, StableMemoryError>
+where
+    K: CandidType + DeserializeOwned + Ord,
+{
+    loop {
+        let i = match node.keys.binary_search(&key) {
+            Ok(i) => {
+                let old = std::mem::replace(&mut node.values[i], value);
+                node.save();
+                return Ok(Some(old));
+            }
+            Err(i) => i,
+        };
+
+        if node.is_leaf() {
+            node.keys.insert(i, key);
+            node.values.insert(i, value);
+            node.save();
+            return Ok(None);
+        }
+
+        let mut child = Node::load(node.children[i]);
+
+        if child.keys.len() == CAPACITY {
+            split_child(&mut node, i, &mut child)?;
+
+            if key == node.keys[i] {
+                let old = std::mem::replace(&mut node.values[i], value);
+                node.save();
+                return Ok(Some(old));
+            }
+
+            if key > node.keys[i] {
+                child = Node::load(node.children[i + 1]);
+            }
+        }
+
+        node = child;
+    }
+}
+
+/// Remove the key from the subtree of the given node, every node visited on the way down has at
+/// least `B` keys so removing a key never leaves a node with less than `B - 1` keys.
+fn remove_from<K>(node: &mut Node<K>, key: &K) -> Option<Vec<u8>>
+where
+    K: CandidType + DeserializeOwned + Ord,
+{
+    let i = match node.keys.binary_search(key) {
+        Ok(i) if node.is_leaf() => {
+            node.keys.remove(i);
+            let value = node.values.remove(i);
+            node.save();
+            return Some(value);
+        }
+        Ok(i) => i,
+        Err(_) if node.is_leaf() => return None,
+        Err(i) => {
+            let mut child = fill_child(node, i);
+            return remove_from(&mut child, key);
+        }
+    };
+
+    // The key is in an internal node, replace it with its predecessor or successor if possible.
+    let mut left = Node::load(node.children[i]);
+    if left.keys.len() >= B {
+        let (k, v) = remove_last(&mut left);
+        node.keys[i] = k;
+        let value = std::mem::replace(&mut node.values[i], v);
+        node.save();
+        return Some(value);
+    }
+
+    let mut right = Node::load(node.children[i + 1]);
+    if right.keys.len() >= B {
+        let (k, v) = remove_first(&mut right);
+        node.keys[i] = k;
+        let value = std::mem::replace(&mut node.values[i], v);
+        node.save();
+        return Some(value);
+    }
+
+    merge(node, i, &mut left, right);
+    remove_from(&mut left, key)
+}
+
+/// Remove and return the largest entry in the subtree of the node.
+fn remove_last<K>(node: &mut Node<K>) -> (K, Vec<u8>)
+where
+    K: CandidType + DeserializeOwned + Ord,
+{
+    if node.is_leaf() {
+        let entry = (node.keys.pop().unwrap(), node.values.pop().unwrap());
+        node.save();
+        return entry;
+    }
+
+    let mut child = fill_child(node, node.children.len() - 1);
+    remove_last(&mut child)
+}
+
+/// Remove and return the smallest entry in the subtree of the node.
+fn remove_first<K>(node: &mut Node<K>) -> (K, Vec<u8>)
+where
+    K: CandidType + DeserializeOwned + Ord,
+{
+    if node.is_leaf() {
+        let entry = (node.keys.remove(0), node.values.remove(0));
+        node.save();
+        return entry;
+    }
+
+    let mut child = fill_child(node, 0);
+    remove_first(&mut child)
+}
+
+/// Make sure the child at the given index has at least `B` keys by either taking a key from one
+/// of its siblings or merging it with one, and return the node that now covers the child's keys.
+fn fill_child<K>(node: &mut Node<K>, i: usize) -> Node<K>
+where
+    K: CandidType + DeserializeOwned + Ord,
+{
+    let mut child = Node::load(node.children[i]);
+
+    if child.keys.len() >= B {
+        return child;
+    }
+
+    if i > 0 {
+        let mut left = Node::load(node.children[i - 1]);
+
+        if left.keys.len() >= B {
+            let key = std::mem::replace(&mut node.keys[i - 1], left.keys.pop().unwrap());
+            let value = std::mem::replace(&mut node.values[i - 1], left.values.pop().unwrap());
+            child.keys.insert(0, key);
+            child.values.insert(0, value);
+
+            if !left.is_leaf() {
+                child.children.insert(0, left.children.pop().unwrap());
+            }
+
+            left.save();
+            child.save();
+            node.save();
+            return child;
+        }
+    }
+
+    if i < node.keys.len() {
+        let mut right = Node::load(node.children[i + 1]);
+
+        if right.keys.len() >= B {
+            let key = std::mem::replace(&mut node.keys[i], right.keys.remove(0));
+            let value = std::mem::replace(&mut node.values[i], right.values.remove(0));
+            child.keys.push(key);
+            child.values.push(value);
+
+            if !right.is_leaf() {
+                child.children.push(right.children.remove(0));
+            }
+
+            right.save();
+            child.save();
+            node.save();
+            return child;
+        }
+
+        merge(node, i, &mut child, right);
+        return child;
+    }
+
+    let mut left = Node::load(node.children[i - 1]);
+    merge(node, i - 1, &mut left, child);
+    left
+}
+
+/// Merge the key at the given index of the node and the right child into the left child.
+fn merge<K>(node: &mut Node<K>, i: usize, left: &mut Node<K>, mut right: Node<K>)
+where
+    K: CandidType + DeserializeOwned + Ord,
+{
+    left.keys.push(node.keys.remove(i));
+    left.values.push(node.values.remove(i));
+    left.keys.append(&mut right.keys);
+    left.values.append(&mut right.values);
+    left.children.append(&mut right.children);
+    node.children.remove(i + 1);
+
+    left.save();
+    node.save();
+    right.free();
+}
+
+#[inline]
+fn decode_value<V: CandidType + DeserializeOwned>(bytes: &[u8]) -> V {
+    decode_one(bytes).expect("Could not decode the value in the stable btree map.")
+}
+
+#[inline]
+fn read_bytes<'a>(bytes: &'a [u8], offset: &mut usize, size: usize) -> &'a [u8] {
+    let slice = &bytes[*offset..*offset + size];
+    *offset += size;
+    slice
+}
+
+#[inline]
+fn read_u16(bytes: &[u8], offset: &mut usize) -> u16 {
+    u16::from_le_bytes(read_bytes(bytes, offset, 2).try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::allocator::StableAllocator;
+    use crate::core::global::*;
+
+    /// Return the numbers in `0..n` in a shuffled order.
+    fn shuffled(n: u64) -> impl Iterator<Item = u64> {
+        (0..n).map(move |i| (i * 7919) % n)
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        set_global_allocator(StableAllocator::new());
+
+        let mut map = StableBTreeMap::<u64, String>::new().unwrap();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&0), None);
+        assert_eq!(map.remove(&0), None);
+
+        for i in shuffled(1000) {
+            assert_eq!(map.insert(i, &format!("v{}", i)).unwrap(), None);
+        }
+
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.get(&17), Some("v17".to_string()));
+        assert_eq!(map.get(&1000), None);
+
+        assert_eq!(
+            map.insert(17, &"updated".to_string()).unwrap(),
+            Some("v17".to_string())
+        );
+        assert_eq!(map.get(&17), Some("updated".to_string()));
+        assert_eq!(map.len(), 1000);
+
+        for i in shuffled(1000).filter(|i| i % 3 != 0) {
+            assert!(map.remove(&i).is_some(), "key {} should exist", i);
+        }
+
+        assert_eq!(map.len(), 334);
+        assert_eq!(map.remove(&1), None);
+
+        for i in 0..1000 {
+            assert_eq!(map.contains_key(&i), i % 3 == 0);
+        }
+
+        for i in (0..1000).step_by(3) {
+            assert!(map.remove(&i).is_some());
+        }
+
+        assert!(map.is_empty());
+        assert_eq!(map.iter().count(), 0);
+    }
+
+    #[test]
+    fn iter_range() {
+        set_global_allocator(StableAllocator::new());
+
+        let mut map = StableBTreeMap::<u64, u64>::new().unwrap();
+
+        for i in shuffled(500) {
+            map.insert(i * 2, &i).unwrap();
+        }
+
+        assert_eq!(
+            map.iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            (0..500).map(|i| i * 2).collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            map.range(10..20).collect::<Vec<_>>(),
+            vec![(10, 5), (12, 6), (14, 7), (16, 8), (18, 9)]
+        );
+
+        assert_eq!(
+            map.range(11..=20).map(|(k, _)| k).collect::<Vec<_>>(),
+            vec![12, 14, 16, 18, 20]
+        );
+
+        assert_eq!(map.range(995..).map(|(k, _)| k).collect::<Vec<_>>(), vec![996, 998]);
+        assert_eq!(map.range(..4).map(|(k, _)| k).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(map.range(2000..).count(), 0);
+        assert_eq!(map.range(..).count(), 500);
+    }
+
+    #[test]
+    fn upgrade() {
+        set_global_allocator(StableAllocator::new());
+        init_root().unwrap();
+
+        let mut map = StableBTreeMap::<String, u64>::new().unwrap();
+        set_root(map.address());
+
+        for i in 0..100 {
+            map.insert(format!("key-{:03}", i), &i).unwrap();
+        }
+
+        // pre_upgrade -> post_upgrade, the heap is gone and we start with a new allocator.
+        drop(map);
+        reset_global_allocator(StableAllocator::new());
+
+        let map = StableBTreeMap::<String, u64>::from_address(root().unwrap());
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.get(&"key-042".to_string()), Some(42));
+        assert_eq!(map.iter().last(), Some(("key-099".to_string(), 99)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn key_too_large() {
+        set_global_allocator(StableAllocator::new());
+
+        let mut map = StableBTreeMap::<String, u64>::new().unwrap();
+        map.insert("x".repeat(BTREE_MAX_KEY_SIZE), &0).unwrap();
+    }
+}
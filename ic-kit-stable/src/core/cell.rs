@@ -0,0 +1,122 @@
+use crate::core::allocator::{BlockAddress, BlockSize};
+use crate::core::global::{allocate, free};
+use crate::core::memory::{DefaultMemory, Memory};
+use crate::core::utils::{read_struct, write_struct};
+use ic_kit::candid::{decode_one, encode_one, CandidType};
+use ic_kit::stable::StableMemoryError;
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+
+/// A single value of type `T` that lives on the stable storage.
+///
+/// The cell itself is a fixed 8 byte block that points to the candid encoded value, so the
+/// address of the cell does not change when the value is replaced and can be persisted across
+/// upgrades, see [`set_root`](crate::set_root).
+pub struct StableCell<T> {
+    address: BlockAddress,
+    _marker: PhantomData<T>,
+}
+
+impl<T> StableCell<T>
+where
+    T: CandidType + DeserializeOwned,
+{
+    /// Allocate a new cell with the given initial value.
+    pub fn new(value: &T) -> Result<Self, StableMemoryError> {
+        let value_address = write_blob(&encode_one(value).expect("Could not encode the value."))?;
+
+        let address = match allocate(8) {
+            Ok(address) => address,
+            Err(e) => {
+                free(value_address);
+                return Err(e);
+            }
+        };
+
+        write_struct::<DefaultMemory, BlockAddress>(address, &value_address);
+
+        Ok(Self::from_address(address))
+    }
+
+    /// Load a cell that was created earlier at the given address.
+    pub fn from_address(address: BlockAddress) -> Self {
+        Self {
+            address,
+            _marker: PhantomData::default(),
+        }
+    }
+
+    /// Return the address of this cell.
+    pub fn address(&self) -> BlockAddress {
+        self.address
+    }
+
+    /// Read and return the current value.
+    pub fn get(&self) -> T {
+        let bytes = read_blob(read_struct::<DefaultMemory, BlockAddress>(self.address));
+        decode_one(&bytes).expect("Could not decode the value in the stable cell.")
+    }
+
+    /// Replace the value in the cell, the old value is freed.
+    pub fn set(&mut self, value: &T) -> Result<(), StableMemoryError> {
+        let value_address = write_blob(&encode_one(value).expect("Could not encode the value."))?;
+        let old = read_struct::<DefaultMemory, BlockAddress>(self.address);
+        write_struct::<DefaultMemory, BlockAddress>(self.address, &value_address);
+        free(old);
+        Ok(())
+    }
+}
+
+/// Allocate a block for the given bytes and write them prefixed by their length.
+pub(crate) fn write_blob(bytes: &[u8]) -> Result<BlockAddress, StableMemoryError> {
+    let address = allocate(8 + bytes.len() as BlockSize)?;
+    write_struct::<DefaultMemory, u64>(address, &(bytes.len() as u64));
+    DefaultMemory::stable_write(address + 8, bytes);
+    Ok(address)
+}
+
+/// Read the bytes written by [`write_blob`] at the given address.
+pub(crate) fn read_blob(address: BlockAddress) -> Vec<u8> {
+    let len = read_struct::<DefaultMemory, u64>(address) as usize;
+    let mut bytes = vec![0; len];
+    DefaultMemory::stable_read(address + 8, &mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::allocator::StableAllocator;
+    use crate::core::global::*;
+
+    #[test]
+    fn get_set() {
+        set_global_allocator(StableAllocator::new());
+
+        let mut cell = StableCell::new(&String::from("Hello")).unwrap();
+        assert_eq!(cell.get(), "Hello");
+
+        cell.set(&String::from("Hello Dfinity World!")).unwrap();
+        assert_eq!(cell.get(), "Hello Dfinity World!");
+
+        cell.set(&String::new()).unwrap();
+        assert_eq!(cell.get(), "");
+    }
+
+    #[test]
+    fn upgrade() {
+        set_global_allocator(StableAllocator::new());
+        init_root().unwrap();
+        set_root(StableCell::new(&0u64).unwrap().address());
+
+        let mut cell = StableCell::<u64>::from_address(root().unwrap());
+        cell.set(&17).unwrap();
+
+        // pre_upgrade -> post_upgrade, the heap is gone and we start with a new allocator.
+        drop(cell);
+        reset_global_allocator(StableAllocator::new());
+
+        let cell = StableCell::<u64>::from_address(root().unwrap());
+        assert_eq!(cell.get(), 17);
+    }
+}
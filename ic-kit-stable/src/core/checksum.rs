@@ -60,6 +60,25 @@ impl CheckedU40 {
         }
     }
 
+    /// Return the checksum bytes stored alongside the value.
+    pub fn checksum(&self) -> [u8; 3] {
+        let value = self.0;
+        [(value >> 56) as u8, (value >> 48) as u8, (value >> 40) as u8]
+    }
+
+    /// Return the checksum bytes that the stored value should have.
+    pub fn expected_checksum(&self) -> [u8; 3] {
+        let value = self.0;
+        let a = (value & 0xff00000000) >> 32;
+        let b = (value & 0x00ff000000) >> 24;
+        let c = (value & 0x0000ff0000) >> 16;
+        let d = (value & 0x000000ff00) >> 8;
+        let e = value & 0x00000000ff;
+        let x = a ^ b ^ c;
+        let y = c ^ d ^ e;
+        [x as u8, y as u8, (x ^ y) as u8]
+    }
+
     /// Return the protected number without any checks.
     pub fn unchecked(&self) -> u64 {
         self.0 & 0x000000ffffffffff
@@ -0,0 +1,39 @@
+use crate::core::allocator::BlockAddress;
+use std::fmt;
+
+/// An error that is returned when the data on the stable storage can not be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StableError {
+    /// The address can not be the beginning of a block.
+    InvalidAddress(BlockAddress),
+    /// The checksum of the size header of the block at the given address does not match, either
+    /// the stable storage is corrupted or the address is not the beginning of a block.
+    InvalidChecksum {
+        address: BlockAddress,
+        /// The checksum bytes stored in the header.
+        checksum: [u8; 3],
+        /// The checksum bytes computed from the size stored in the header.
+        expected: [u8; 3],
+    },
+}
+
+impl fmt::Display for StableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StableError::InvalidAddress(address) => {
+                write!(f, "{} is not a valid block address.", address)
+            }
+            StableError::InvalidChecksum {
+                address,
+                checksum,
+                expected,
+            } => write!(
+                f,
+                "Invalid block header at {}: checksum {:02x?} does not match {:02x?}.",
+                address, checksum, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StableError {}
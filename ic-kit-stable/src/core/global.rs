@@ -1,5 +1,8 @@
-use crate::core::allocator::{BlockAddress, BlockSize, StableAllocator};
+use crate::core::allocator::{AllocatorStats, BlockAddress, BlockSize, StableAllocator};
+use crate::core::error::StableError;
 use crate::core::lru::LruCache;
+use crate::core::memory::{DefaultMemory, Memory};
+use crate::core::utils::{read_struct, write_struct};
 use ic_kit::stable::StableMemoryError;
 use std::borrow::BorrowMut;
 use std::cell::RefCell;
@@ -26,6 +29,45 @@ pub fn set_global_allocator(allocator: StableAllocator) {
     });
 }
 
+/// Write the state of the global allocator to the stable storage, this must be the last thing
+/// called in `pre_upgrade`, after [`flush_lru`]. See [`StableAllocator::save_to_stable`].
+pub fn save_allocator_state() -> Result<(), StableMemoryError> {
+    ALLOCATOR.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .expect("ic_kit_stable::set_global_allocator must have been called.")
+            .save_to_stable()
+    })
+}
+
+/// Set the global allocator to the one saved by [`save_allocator_state`], this should be called
+/// in `post_upgrade` instead of [`set_global_allocator`]. Returns an error and leaves the global
+/// allocator unset if the saved free list is corrupted.
+///
+/// # Panics
+///
+/// If the global allocator is already set or the state was not saved before the upgrade.
+pub fn restore_allocator_state() -> Result<(), StableError> {
+    set_global_allocator(StableAllocator::load_from_stable()?);
+    Ok(())
+}
+
+/// Replace the global allocator, this is used to simulate an upgrade in the tests.
+#[cfg(test)]
+pub(crate) fn reset_global_allocator(allocator: StableAllocator) {
+    ALLOCATOR.with(|cell| {
+        cell.borrow_mut().replace(allocator);
+    });
+}
+
+/// Remove the global allocator and LRU cache of the current thread, the cached blocks are dropped
+/// without being written back.
+#[cfg(any(test, all(feature = "test-util", not(target_family = "wasm"))))]
+pub(crate) fn reset_global_state() {
+    ALLOCATOR.with(|cell| cell.borrow_mut().take());
+    LRU.with(|cell| cell.borrow_mut().take());
+}
+
 /// Set a custom LRU cache for the canister.
 ///
 /// # Panics
@@ -44,6 +86,12 @@ pub fn set_global_lru(lru: LruCache) {
     });
 }
 
+/// Write all of the modified blocks in the global LRU cache to the stable storage, this must be
+/// called in `pre_upgrade`, see [`LruCache::flush`].
+pub fn flush_lru() {
+    with_lru(|lru| lru.flush());
+}
+
 /// Allocate a block with the given size from the global stable storage allocator.
 pub fn allocate(size: BlockSize) -> Result<BlockAddress, StableMemoryError> {
     ALLOCATOR.with(|cell| {
@@ -65,6 +113,16 @@ pub fn free(address: BlockSize) {
     })
 }
 
+/// Return the statistics of the global stable storage allocator, see [`StableAllocator::stats`].
+pub fn allocator_stats() -> AllocatorStats {
+    ALLOCATOR.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .expect("ic_kit_stable::set_global_allocator must have been called.")
+            .stats()
+    })
+}
+
 /// Use the LRU cache instance to the callback.
 #[inline]
 pub(crate) fn with_lru<U, F: FnOnce(&mut LruCache) -> U>(f: F) -> U {
@@ -74,3 +132,41 @@ pub(crate) fn with_lru<U, F: FnOnce(&mut LruCache) -> U>(f: F) -> U {
         f(lru_mut)
     })
 }
+
+/// The address of the root block, which is the first block that a new allocator returns.
+pub const ROOT_ADDRESS: BlockAddress = 8;
+
+/// Allocate the root block, the root block holds a single address that can be retrieved after
+/// an upgrade and is used to find the data structures that live on the stable storage.
+///
+/// # Panics
+///
+/// If anything else was allocated before the root block, this should be called in `init` right
+/// after [`set_global_allocator`].
+pub fn init_root() -> Result<(), StableMemoryError> {
+    let address = allocate(8)?;
+
+    if address != ROOT_ADDRESS {
+        panic!("init_root must be called before any other allocation.");
+    }
+
+    write_struct::<DefaultMemory, BlockAddress>(ROOT_ADDRESS, &0);
+    Ok(())
+}
+
+/// Store the given address in the root block.
+pub fn set_root(address: BlockAddress) {
+    write_struct::<DefaultMemory, BlockAddress>(ROOT_ADDRESS, &address);
+}
+
+/// Return the address stored in the root block, returns `None` if it was never set.
+pub fn root() -> Option<BlockAddress> {
+    if DefaultMemory::stable_size() == 0 {
+        return None;
+    }
+
+    match read_struct::<DefaultMemory, BlockAddress>(ROOT_ADDRESS) {
+        0 => None,
+        address => Some(address),
+    }
+}
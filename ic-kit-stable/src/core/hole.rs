@@ -1,19 +1,24 @@
 use crate::core::allocator::{BlockAddress, BlockSize, MIN_ALLOCATION_SIZE};
 use crate::core::checksum::CheckedU40;
+use crate::core::error::StableError;
 use crate::core::memory::Memory;
-use crate::core::utils::write_struct;
+use crate::core::utils::{read_struct, write_struct};
 use std::collections::BTreeMap;
 use std::marker::PhantomData;
 use std::ptr::NonNull;
 
 // used for testing if holes are properly dropped or not.
-#[cfg(test)]
+#[cfg(any(test, all(feature = "test-util", not(target_family = "wasm"))))]
 thread_local! {
     static ACTIVE_HOLE: std::cell::RefCell<usize> = std::cell::RefCell::new(0);
 }
 
 pub type Delta = BlockSize;
 
+/// The address used in place of a null pointer in the on-disk hole headers, zero can not be used
+/// since it is a valid hole address.
+pub const NULL_ADDRESS: BlockAddress = BlockAddress::MAX;
+
 /// A data structure to keep a list of memory holes that uses a combination of power-of-two linked
 /// lists and uses best-fit/worst-fit lookup through the linked lists to find a free hole, it is also
 /// capable of merging freed holes to form larger holes and prevent fragmentation.
@@ -30,9 +35,35 @@ pub struct HoleList<M: Memory> {
     //      for all `i < S` -> roots[i] == Null
     // assert(roots_left_boundary == 36 || roots[roots_left_boundary].is_some())
     roots_left_boundary: usize,
+    // the policy used to pick a hole from a linked list.
+    strategy: AllocationStrategy,
     _memory: PhantomData<M>,
 }
 
+/// The policy used to pick a free hole for an allocation, the holes are grouped in power-of-two
+/// size classes, and the strategy decides which hole to use among the holes of the smallest size
+/// class that has a hole big enough for the allocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationStrategy {
+    /// Use a hole that fits perfectly if there is one, otherwise use the largest hole unless the
+    /// remaining part is too small to be reused, in which case use the smallest hole. This is the
+    /// default strategy.
+    Hybrid,
+    /// Use the smallest hole, which keeps the large holes for the large allocations.
+    BestFit,
+    /// Use the largest hole, which leaves remaining parts that are more likely to be reused.
+    WorstFit,
+    /// Use the first hole that fits, which is the fastest, and works well when most of the
+    /// allocations have the same size.
+    FirstFit,
+}
+
+impl Default for AllocationStrategy {
+    fn default() -> Self {
+        AllocationStrategy::Hybrid
+    }
+}
+
 // On heap memory allocators this usually is stored within the hole itself, but we're doing this
 // for a secondary storage...
 #[derive(Debug)]
@@ -64,6 +95,7 @@ impl<M: Memory> Default for HoleList<M> {
             roots: [None; 36],
             roots_right_boundary: 0,
             roots_left_boundary: 36,
+            strategy: AllocationStrategy::default(),
             _memory: PhantomData::default(),
         }
     }
@@ -75,6 +107,11 @@ impl<M: Memory> HoleList<M> {
         Self::default()
     }
 
+    /// Use the given strategy to pick the holes for the next allocations.
+    pub fn set_strategy(&mut self, strategy: AllocationStrategy) {
+        self.strategy = strategy;
+    }
+
     /// Find and return a block that can fit the given size.
     pub fn find(&mut self, size: BlockSize) -> Option<(BlockAddress, BlockSize)> {
         // align by 4.
@@ -90,7 +127,7 @@ impl<M: Memory> HoleList<M> {
                 break None;
             }
 
-            if let Some((addr, delta)) = self.iter(i).find(size) {
+            if let Some((addr, delta)) = self.iter(i).find(size, self.strategy) {
                 break Some((addr, delta));
             }
 
@@ -255,6 +292,66 @@ impl<M: Memory> HoleList<M> {
         }
     }
 
+    /// Write the header of every hole to the stable storage and return the address of the first
+    /// hole in each of the linked lists, which can be used to [`load`](Self::load) the list again.
+    ///
+    /// The headers are rewritten since removing a hole does not update the `next` pointer of the
+    /// previous hole on the stable storage.
+    pub fn save(&self) -> [BlockAddress; 36] {
+        for hole in self.map.values() {
+            let hole = unsafe { hole.as_ref() };
+            write_struct::<M, HoleHeader>(hole.address, &hole.to_header());
+        }
+
+        let mut roots = [NULL_ADDRESS; 36];
+
+        for (i, root) in self.roots.iter().enumerate() {
+            if let Some(hole) = root {
+                roots[i] = unsafe { hole.as_ref().address };
+            }
+        }
+
+        roots
+    }
+
+    /// Rebuild the hole list by walking the hole headers on the stable storage starting from the
+    /// given roots, returns an error if any of the hole headers is corrupted.
+    pub fn load(roots: &[BlockAddress; 36]) -> Result<Self, StableError> {
+        let mut list = Self::new();
+
+        for &root in roots.iter() {
+            let mut holes = Vec::new();
+            let mut addr = root;
+
+            while addr != NULL_ADDRESS {
+                let header = read_struct::<M, HoleHeader>(addr);
+                let size = header.size.verify().ok_or(StableError::InvalidChecksum {
+                    address: addr,
+                    checksum: header.size.checksum(),
+                    expected: header.size.expected_checksum(),
+                })?;
+                holes.push((addr, size));
+                addr = header.next;
+            }
+
+            // raw_insert pushes to the front of the list, so insert in reverse to keep the order.
+            for (addr, size) in holes.into_iter().rev() {
+                list.raw_insert(addr, size, true);
+            }
+        }
+
+        Ok(list)
+    }
+
+    /// Return an iterator over the address and size of every hole in the list, ordered by the
+    /// address.
+    pub fn holes(&self) -> impl Iterator<Item = (BlockAddress, BlockSize)> + '_ {
+        self.map.iter().map(|(addr, hole)| {
+            let hole = unsafe { hole.as_ref() };
+            (*addr, hole.size)
+        })
+    }
+
     /// Return an iterator over the holes at the given level.
     #[inline]
     fn iter(&self, level: usize) -> HoleIterator {
@@ -264,7 +361,7 @@ impl<M: Memory> HoleList<M> {
 
 impl Hole {
     pub fn new(addr: BlockAddress, size: BlockSize, next: Option<NonNull<Hole>>) -> Self {
-        #[cfg(test)]
+        #[cfg(any(test, all(feature = "test-util", not(target_family = "wasm"))))]
         ACTIVE_HOLE.with(|c| {
             *c.borrow_mut() += 1;
         });
@@ -306,7 +403,7 @@ impl Hole {
             size: CheckedU40::new(self.size),
             next: match self.next {
                 Some(x) => unsafe { x.as_ref().address },
-                None => 0,
+                None => NULL_ADDRESS,
             },
         }
     }
@@ -322,7 +419,7 @@ impl<M: Memory> Drop for HoleList<M> {
     }
 }
 
-#[cfg(test)]
+#[cfg(any(test, all(feature = "test-util", not(target_family = "wasm"))))]
 impl Drop for Hole {
     fn drop(&mut self) {
         ACTIVE_HOLE.with(|c| {
@@ -331,6 +428,12 @@ impl Drop for Hole {
     }
 }
 
+/// Return the number of holes that are alive in the current thread.
+#[cfg(any(test, all(feature = "test-util", not(target_family = "wasm"))))]
+pub(crate) fn active_holes() -> usize {
+    ACTIVE_HOLE.with(|c| *c.borrow())
+}
+
 struct HoleIterator {
     head: Option<NonNull<Hole>>,
 }
@@ -343,11 +446,29 @@ impl HoleIterator {
 
     /// Tries to find a hole with size larger than or equal to the provided size, address of the
     /// block along side the value of delta is returned.
-    fn find(self, size: BlockSize) -> Option<(BlockAddress, Delta)> {
+    fn find(
+        mut self,
+        size: BlockSize,
+        strategy: AllocationStrategy,
+    ) -> Option<(BlockAddress, Delta)> {
         if self.head.is_none() {
             return None;
         }
 
+        let fits = |(addr, b_size): (BlockAddress, BlockSize)| {
+            b_size.checked_sub(size).map(|delta| (addr, delta))
+        };
+
+        match strategy {
+            AllocationStrategy::Hybrid => {}
+            AllocationStrategy::FirstFit => return self.find_map(fits),
+            AllocationStrategy::BestFit => return self.filter_map(fits).min_by_key(|x| x.1),
+            AllocationStrategy::WorstFit => {
+                // max_by_key returns the last maximum, reverse it to prefer the first one.
+                return self.filter_map(fits).min_by_key(|x| std::cmp::Reverse(x.1));
+            }
+        }
+
         // Do a worst-fit and best-fit search in parallel, and then consider:
         // if the best-fit is a prefect match (best_fit_delta == 0) use it (no fragmentation)
         // if the worst_fit_delta < MIN_ALLOCATION_SIZE: use best fit to minimize wasted gap.
@@ -436,7 +557,7 @@ mod tests {
 
     /// return the number of active holes in the current thread.
     fn holes() -> usize {
-        ACTIVE_HOLE.with(|c| c.borrow().clone())
+        active_holes()
     }
 
     #[test]
@@ -480,6 +601,27 @@ mod tests {
         assert_eq!(list.find(16), Some((100, 16)));
     }
 
+    #[test]
+    fn hole_list_find_strategy() {
+        MockMemory::stable_grow(1);
+
+        let expected = [
+            (AllocationStrategy::Hybrid, (0, 36)),
+            (AllocationStrategy::BestFit, (200, 40)),
+            (AllocationStrategy::WorstFit, (0, 36)),
+            (AllocationStrategy::FirstFit, (400, 50)),
+        ];
+
+        for (strategy, result) in expected {
+            let mut list = HoleList::<MockMemory>::new();
+            list.set_strategy(strategy);
+            list.insert(0, 60);
+            list.insert(200, 40);
+            list.insert(400, 50);
+            assert_eq!(list.find(36), Some(result), "{:?}", strategy);
+        }
+    }
+
     #[test]
     fn hole_list_find_small_size() {
         MockMemory::stable_grow(1);
@@ -617,4 +759,29 @@ mod tests {
 
         assert_eq!(holes(), 0);
     }
+
+    #[test]
+    fn hole_list_save_load() {
+        MockMemory::stable_grow(1);
+
+        let roots = {
+            let mut list = HoleList::<MockMemory>::new();
+            list.insert(0, 500);
+            list.insert(1000, 64);
+            list.insert(2000, 64);
+            list.insert(3000, 32);
+            assert_eq!(list.find(16), Some((3000, 16)));
+            list.save()
+        };
+
+        assert_eq!(holes(), 0);
+
+        let mut list = HoleList::<MockMemory>::load(&roots);
+        assert_eq!(holes(), 4);
+        assert_eq!(list.find(16), Some((3016, 16)));
+        assert_eq!(list.find(64), Some((2000, 64)));
+        assert_eq!(list.find(64), Some((1000, 64)));
+        assert_eq!(list.find(500), Some((0, 500)));
+        assert_eq!(list.find(16), None);
+    }
 }
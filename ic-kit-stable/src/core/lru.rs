@@ -2,6 +2,7 @@
 
 use crate::core::allocator::{BlockAddress, BlockSize};
 use crate::core::checksum::CheckedU40;
+use crate::core::error::StableError;
 use crate::core::global::free;
 use crate::core::memory::{DefaultMemory, Memory};
 use crate::core::utils::read_struct;
@@ -46,6 +47,17 @@ pub struct LruCacheConfig {
     pub total_capacity: u64,
 }
 
+/// A snapshot of the usage of an LRU cache, see [`LruCache::stats`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LruCacheStats {
+    /// Sum of the block size of all the blocks currently in the cache.
+    pub size: u64,
+    /// Sum of the block size of the modified blocks that are not written back yet.
+    pub modified_size: u64,
+    /// The number of blocks in the cache.
+    pub entries: usize,
+}
+
 #[derive(Debug)]
 pub(crate) struct BlockEntry {
     address: BlockAddress,
@@ -56,8 +68,9 @@ pub(crate) struct BlockEntry {
 }
 
 impl<M: Memory> LruCache<M> {
-    /// Return a new instance of the LRU-cache
-    fn new(config: LruCacheConfig) -> Self {
+    /// Return a new instance of the LRU-cache with the given configuration, the cache can be
+    /// used as the global cache via [`crate::set_global_lru`].
+    pub fn with_config(config: LruCacheConfig) -> Self {
         Self {
             config,
             map: Default::default(),
@@ -71,14 +84,27 @@ impl<M: Memory> LruCache<M> {
         }
     }
 
+    /// Return the current usage of the cache.
+    pub fn stats(&self) -> LruCacheStats {
+        LruCacheStats {
+            size: self.size,
+            modified_size: self.modified_size,
+            entries: self.map.len(),
+        }
+    }
+
     /// Load the content of a block at the given address and move it to the head of the LruCache.
-    fn load_internal(&mut self, address: BlockAddress) -> *mut BlockEntry {
-        let block_ptr = *self.map.entry(address).or_insert_with(|| unsafe {
-            let block = BlockEntry::new(address);
-            let size = block.size as BlockSize;
-            self.size += size;
-            Box::leak(Box::new(block))
-        });
+    fn load_internal(&mut self, address: BlockAddress) -> Result<*mut BlockEntry, StableError> {
+        let block_ptr = match self.map.get(&address) {
+            Some(&block_ptr) => block_ptr,
+            None => {
+                let block = BlockEntry::new(address)?;
+                self.size += block.size as BlockSize;
+                let block_ptr = Box::leak(Box::new(block)) as *mut BlockEntry;
+                self.map.insert(address, block_ptr);
+                block_ptr
+            }
+        };
 
         unsafe {
             let is_head = self.head == block_ptr;
@@ -100,18 +126,14 @@ impl<M: Memory> LruCache<M> {
             self.head = block_ptr;
         }
 
-        block_ptr
+        Ok(block_ptr)
     }
 
-    /// Return the data at the given address.
-    pub fn get(&mut self, address: BlockAddress) -> *mut u8 {
-        unsafe {
-            self.load_internal(address)
-                .as_ref()
-                .unwrap()
-                .data()
-                .as_ptr() as *mut u8
-        }
+    /// Return the data at the given address, an error is returned if the block header at the
+    /// address is invalid.
+    pub fn get(&mut self, address: BlockAddress) -> Result<*mut u8, StableError> {
+        let block_ptr = self.load_internal(address)?;
+        Ok(unsafe { block_ptr.as_ref().unwrap().data().as_ptr() as *mut u8 })
     }
 
     /// Mark the block at the given address as modified so we know to flush it to the stable storage.
@@ -124,6 +146,17 @@ impl<M: Memory> LruCache<M> {
         }
     }
 
+    /// Write the data to the beginning of the block at the given address, the data is written to
+    /// the stable storage right away and the cached copy of the block is updated if there is one.
+    pub fn write_through(&mut self, address: BlockAddress, data: &[u8]) {
+        if let Some(&entry) = self.map.get(&address) {
+            let entry = unsafe { &mut *entry };
+            entry.data_mut()[..data.len()].copy_from_slice(data);
+        }
+
+        M::stable_write(address, data);
+    }
+
     /// Increment the reference count for a block, so we don't accidentally drop it.
     pub fn pin(&mut self, address: BlockAddress) {
         *self.ref_count.entry(address).or_default() += 1;
@@ -140,9 +173,58 @@ impl<M: Memory> LruCache<M> {
         }
     }
 
-    /// Free the given block address.
+    /// Free the given block address, the block is dropped from the cache without being written
+    /// back and the address is given back to the global allocator.
     pub fn free(&mut self, address: BlockAddress) {
-        // 1. Remove it from the linked list.
+        if let Some(entry) = self.map.remove(&address) {
+            let entry_mut = unsafe { &mut *entry };
+
+            // 1. Remove it from the linked list.
+            if entry_mut.prev.is_null() {
+                self.head = entry_mut.next;
+            } else {
+                unsafe { (*entry_mut.prev).next = entry_mut.next };
+            }
+
+            if entry_mut.next.is_null() {
+                self.tail = entry_mut.prev;
+            } else {
+                unsafe { (*entry_mut.next).prev = entry_mut.prev };
+            }
+
+            // 2. Update the sizes, a freed block does not need to be flushed.
+            let size = entry_mut.size as u64;
+            self.size -= size;
+
+            if self.modified.remove(&address) {
+                self.modified_size -= size;
+            }
+
+            // 3. Drop the cached data.
+            unsafe {
+                let _ = Box::from_raw(entry);
+            }
+        }
+
+        self.ref_count.remove(&address);
+
+        // 4. Give the block back to the allocator.
+        free(address);
+    }
+
+    /// Write all of the modified blocks to the stable storage, the blocks stay in the cache, so
+    /// the later reads are still served from the memory.
+    ///
+    /// The modified blocks only live on the heap until they are written back, so this must be
+    /// called in the `pre_upgrade` hook, otherwise the changes are lost during the upgrade. For
+    /// the global cache use [`crate::flush_lru`].
+    pub fn flush(&mut self) {
+        for addr in std::mem::take(&mut self.modified) {
+            let entry = unsafe { &(**self.map.get(&addr).unwrap()) };
+            M::stable_write(entry.address, entry.data());
+        }
+
+        self.modified_size = 0;
     }
 
     /// Forcefully clear the LRU cache, write all of the data to the stable storage and clear
@@ -253,7 +335,7 @@ impl<M: Memory> LruCache<M> {
 
 impl Default for LruCache {
     fn default() -> Self {
-        Self::new(LruCacheConfig::default())
+        Self::with_config(LruCacheConfig::default())
     }
 }
 
@@ -267,9 +349,26 @@ impl Default for LruCacheConfig {
     }
 }
 
+impl LruCacheConfig {
+    /// Set the maximum size of the modified blocks in bytes, once the cache goes over this limit
+    /// the modified blocks are written back to the stable storage.
+    #[inline]
+    pub fn modified_capacity(mut self, bytes: u64) -> Self {
+        self.modified_capacity = bytes;
+        self
+    }
+
+    /// Set the maximum size of all of the blocks in the cache in bytes.
+    #[inline]
+    pub fn total_capacity(mut self, bytes: u64) -> Self {
+        self.total_capacity = bytes;
+        self
+    }
+}
+
 impl BlockEntry {
     /// Create a new BlockEntry by loading the content from the given stable storage address.
-    pub fn new(address: BlockAddress) -> Self {
+    pub fn new(address: BlockAddress) -> Result<Self, StableError> {
         load_block::<DefaultMemory>(address)
     }
 
@@ -296,13 +395,22 @@ impl Drop for BlockEntry {
     }
 }
 
-fn load_block<M: Memory>(address: BlockAddress) -> BlockEntry {
-    // TODO(qti3e) Handle the error here if the address is not a valid block beginning.
+fn load_block<M: Memory>(address: BlockAddress) -> Result<BlockEntry, StableError> {
+    if address < 8 {
+        return Err(StableError::InvalidAddress(address));
+    }
 
-    let size = read_struct::<M, CheckedU40>(address - 8)
-        .verify()
-        .expect("X");
-    let size = (size - 8) as usize;
+    let header = read_struct::<M, CheckedU40>(address - 8);
+    let size = match header.verify() {
+        Some(size) if size >= 8 => (size - 8) as usize,
+        _ => {
+            return Err(StableError::InvalidChecksum {
+                address,
+                checksum: header.checksum(),
+                expected: header.expected_checksum(),
+            });
+        }
+    };
 
     let data = unsafe {
         let mut data = Vec::<u8>::with_capacity(size);
@@ -311,13 +419,13 @@ fn load_block<M: Memory>(address: BlockAddress) -> BlockEntry {
         data.leak().as_mut_ptr()
     };
 
-    BlockEntry {
+    Ok(BlockEntry {
         address,
         size,
         data,
         next: ptr::null_mut(),
         prev: ptr::null_mut(),
-    }
+    })
 }
 
 #[cfg(test)]
@@ -333,7 +441,7 @@ mod tests {
 
         for size in (16..256).step_by(4) {
             let address = allocate(size).unwrap();
-            let block = BlockEntry::new(address);
+            let block = BlockEntry::new(address).unwrap();
             assert_eq!(block.size as BlockSize, size);
             assert_eq!(address, 8);
             block.free();
@@ -346,7 +454,118 @@ mod tests {
         let content = b"Hello Dfinity World!";
         let address = allocate(content.len() as BlockSize).unwrap();
         MockMemory::stable_write(address, content.as_slice());
-        let block = BlockEntry::new(address);
+        let block = BlockEntry::new(address).unwrap();
         assert_eq!(block.data(), content);
     }
+
+    #[test]
+    fn free() {
+        set_global_allocator(StableAllocator::new());
+        let mut lru = LruCache::<MockMemory>::with_config(LruCacheConfig::default());
+
+        let a = allocate(100).unwrap();
+        let b = allocate(100).unwrap();
+        lru.get(a).unwrap();
+        lru.get(b).unwrap();
+        lru.mark_modified(a);
+
+        lru.free(a);
+        assert!(!lru.map.contains_key(&a));
+        assert!(lru.modified.is_empty());
+        assert_eq!(lru.modified_size, 0);
+        assert_eq!(lru.size, 100);
+        assert_eq!(lru.head, lru.tail);
+
+        // The address is reused by the allocator.
+        assert_eq!(allocate(100), Ok(a));
+
+        lru.free(b);
+        assert!(lru.map.is_empty());
+        assert!(lru.head.is_null());
+        assert!(lru.tail.is_null());
+        assert_eq!(lru.size, 0);
+
+        assert_eq!(allocate(100), Ok(b));
+    }
+
+    #[test]
+    fn corrupted_block() {
+        set_global_allocator(StableAllocator::new());
+        let mut lru = LruCache::<MockMemory>::with_config(LruCacheConfig::default());
+
+        let address = allocate(100).unwrap();
+        let header = read_struct::<MockMemory, CheckedU40>(address - 8);
+        MockMemory::stable_write(address - 6, &[0xff]);
+
+        assert_eq!(
+            lru.get(address),
+            Err(StableError::InvalidChecksum {
+                address,
+                checksum: header.checksum(),
+                expected: read_struct::<MockMemory, CheckedU40>(address - 8).expected_checksum(),
+            })
+        );
+        assert!(lru.map.is_empty());
+        assert_eq!(lru.size, 0);
+
+        assert_eq!(lru.get(4), Err(StableError::InvalidAddress(4)));
+    }
+
+    #[test]
+    fn with_config() {
+        set_global_allocator(StableAllocator::new());
+        let config = LruCacheConfig::default().modified_capacity(150);
+        let mut lru = LruCache::<MockMemory>::with_config(config);
+
+        let a = allocate(100).unwrap();
+        let b = allocate(100).unwrap();
+        lru.get(a).unwrap();
+        lru.get(b).unwrap();
+        lru.mark_modified(a);
+
+        assert_eq!(
+            lru.stats(),
+            LruCacheStats {
+                size: 200,
+                modified_size: 100,
+                entries: 2
+            }
+        );
+
+        // Going over the modified capacity writes the blocks back.
+        lru.mark_modified(b);
+        assert_eq!(lru.stats().modified_size, 100);
+        assert_eq!(lru.stats().entries, 2);
+    }
+
+    #[test]
+    fn flush() {
+        set_global_allocator(StableAllocator::new());
+        let mut lru = LruCache::<MockMemory>::with_config(LruCacheConfig::default());
+
+        let addresses = (0..4u8)
+            .map(|i| {
+                let address = allocate(16).unwrap();
+                let data = lru.get(address).unwrap();
+                unsafe { data.write_bytes(i + 1, 16) };
+                lru.mark_modified(address);
+                address
+            })
+            .collect::<Vec<_>>();
+
+        // Nothing is written back before the flush.
+        let mut buf = [0u8; 16];
+        MockMemory::stable_read(addresses[0], &mut buf);
+        assert_eq!(buf, [0; 16]);
+
+        lru.flush();
+        assert_eq!(lru.stats().modified_size, 0);
+        assert_eq!(lru.stats().entries, 4);
+        drop(lru);
+
+        for (i, &address) in addresses.iter().enumerate() {
+            MockMemory::stable_read(address, &mut buf);
+            assert_eq!(buf, [i as u8 + 1; 16]);
+        }
+    }
 }
@@ -19,6 +19,13 @@ pub mod mock {
     // A memory interface that uses ic-kit's HeapStableMemory.
     pub struct MockMemory;
 
+    impl MockMemory {
+        /// Replace the memory of the current thread with an empty one.
+        pub fn reset() {
+            MEMORY.with(|c| *c.borrow_mut() = HeapStableMemory::default());
+        }
+    }
+
     impl Memory for MockMemory {
         fn stable_size() -> u64 {
             MEMORY.with(|c| c.borrow_mut().stable_size())
@@ -1,16 +1,27 @@
 mod allocator;
+mod btree;
+mod cell;
 mod checksum;
 mod copy;
+mod error;
 mod global;
 mod hole;
 mod lru;
 mod memory;
 mod pointer;
+#[cfg(any(test, all(feature = "test-util", not(target_family = "wasm"))))]
+pub mod test_util;
 mod utils;
+mod vec;
 
 pub use copy::StableCopy;
 
 pub use allocator::*;
+pub use btree::*;
+pub use cell::*;
+pub use error::StableError;
 pub use global::*;
+pub use hole::AllocationStrategy;
 pub use lru::*;
 pub use pointer::*;
+pub use vec::*;
@@ -1,5 +1,6 @@
 use crate::core::allocator::{BlockAddress, BlockSize};
 use crate::core::copy::StableCopy;
+use crate::core::error::StableError;
 use crate::core::global::{allocate, with_lru};
 use crate::core::memory::DefaultMemory;
 use crate::core::utils::write_struct;
@@ -55,9 +56,11 @@ where
             None
         } else {
             let data = with_lru(|lru| {
+                let data = lru.get(self.0)?;
                 lru.pin(self.0);
-                lru.get(self.0)
-            });
+                Ok(data)
+            })
+            .unwrap_or_else(|e: StableError| panic!("{}", e));
 
             Some(StableRef {
                 data: unsafe { data as *mut T },
@@ -72,11 +75,12 @@ where
             None
         } else {
             let data = with_lru(|lru| {
+                let data = lru.get(self.0)?;
                 lru.pin(self.0);
-                let data = lru.get(self.0);
                 lru.mark_modified(self.0);
-                data
-            });
+                Ok(data)
+            })
+            .unwrap_or_else(|e: StableError| panic!("{}", e));
 
             Some(StableRefMut {
                 data: unsafe { data as *mut T },
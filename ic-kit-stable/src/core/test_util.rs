@@ -0,0 +1,89 @@
+//! Helpers for testing the code that uses the stable data structures, this module is available in
+//! the tests of this crate and with the `test-util` feature on non-wasm targets.
+//!
+//! Enabling the feature does not change the stable storage that the data structures use, outside
+//! of a canister use [`MockMemory`] which keeps an in-memory stable storage for each thread:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! ic-kit-stable = { version = "...", features = ["test-util"] }
+//! ```
+
+use crate::core::allocator::StableAllocator;
+use crate::core::global::{reset_global_state, set_global_allocator};
+use crate::core::hole;
+
+pub use crate::core::memory::mock::MockMemory;
+
+/// A guard returned by [`fresh_memory`], the stable storage, the global allocator and the global
+/// LRU cache of the current thread are removed once it is dropped.
+#[must_use = "The state is torn down as soon as the guard is dropped."]
+pub struct StableTestGuard {
+    _private: (),
+}
+
+impl Drop for StableTestGuard {
+    fn drop(&mut self) {
+        reset();
+    }
+}
+
+/// Give the current thread an empty [`MockMemory`] and a new global allocator, the state is torn
+/// down once the returned guard is dropped, even if the test panics.
+pub fn fresh_memory() -> StableTestGuard {
+    reset();
+    set_global_allocator(StableAllocator::new());
+    StableTestGuard { _private: () }
+}
+
+/// Run the given closure with a fresh stable storage and global allocator, see [`fresh_memory`].
+///
+/// ```ignore
+/// ic_kit_stable::test_util::with_allocator(|| {
+///     let vec = StableVec::<u64>::new().unwrap();
+///     // ...
+/// });
+/// ```
+pub fn with_allocator<R, F: FnOnce() -> R>(f: F) -> R {
+    let _guard = fresh_memory();
+    f()
+}
+
+/// Return the number of the allocator's holes that are alive in the current thread, once the
+/// allocator is dropped this should go back to zero, otherwise the holes are leaked.
+pub fn active_holes() -> usize {
+    hole::active_holes()
+}
+
+fn reset() {
+    reset_global_state();
+    MockMemory::reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::global::allocate;
+    use crate::core::memory::{DefaultMemory, Memory};
+
+    #[test]
+    fn isolated_state() {
+        let address = with_allocator(|| {
+            let address = allocate(100).unwrap();
+            DefaultMemory::stable_write(address, b"hello");
+            address
+        });
+
+        assert_eq!(active_holes(), 0);
+        assert_eq!(DefaultMemory::stable_size(), 0);
+
+        with_allocator(|| {
+            // The allocator is new, so the same address is returned on an empty memory.
+            assert_eq!(allocate(100), Ok(address));
+
+            let mut buf = [0u8; 5];
+            DefaultMemory::stable_read(address, &mut buf);
+            assert_eq!(buf, [0; 5]);
+        });
+    }
+}
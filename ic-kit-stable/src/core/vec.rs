@@ -0,0 +1,177 @@
+use crate::core::allocator::{BlockAddress, BlockSize};
+use crate::core::cell::{read_blob, write_blob};
+use crate::core::global::{allocate, free};
+use crate::core::memory::DefaultMemory;
+use crate::core::utils::{read_struct, write_struct};
+use ic_kit::candid::{decode_one, encode_one, CandidType};
+use ic_kit::stable::StableMemoryError;
+use serde::de::DeserializeOwned;
+use std::marker::PhantomData;
+
+/// The header of a vector which is stored at the vector's address.
+#[repr(packed)]
+struct StableVecHeader {
+    len: u64,
+    capacity: u64,
+    /// Address of the block which holds `capacity` addresses, one for each of the elements.
+    elements: BlockAddress,
+}
+
+/// A growable list of values of type `T` that lives on the stable storage, each element is
+/// candid encoded into its own block.
+///
+/// Just like [`StableCell`](crate::StableCell) the address of the vector never changes, so it can
+/// be persisted across upgrades.
+pub struct StableVec<T> {
+    address: BlockAddress,
+    _marker: PhantomData<T>,
+}
+
+impl<T> StableVec<T>
+where
+    T: CandidType + DeserializeOwned,
+{
+    /// Allocate a new empty vector.
+    pub fn new() -> Result<Self, StableMemoryError> {
+        let address = allocate(std::mem::size_of::<StableVecHeader>() as BlockSize)?;
+
+        write_struct::<DefaultMemory, StableVecHeader>(
+            address,
+            &StableVecHeader {
+                len: 0,
+                capacity: 0,
+                elements: 0,
+            },
+        );
+
+        Ok(Self::from_address(address))
+    }
+
+    /// Load a vector that was created earlier at the given address.
+    pub fn from_address(address: BlockAddress) -> Self {
+        Self {
+            address,
+            _marker: PhantomData::default(),
+        }
+    }
+
+    /// Return the address of this vector.
+    pub fn address(&self) -> BlockAddress {
+        self.address
+    }
+
+    /// Return the number of elements in the vector.
+    pub fn len(&self) -> u64 {
+        self.header().len
+    }
+
+    /// Returns true if the vector is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append the value to the end of the vector.
+    pub fn push(&mut self, value: &T) -> Result<(), StableMemoryError> {
+        let mut header = self.header();
+
+        if header.len == header.capacity {
+            let capacity = (header.capacity * 2).max(4);
+            let elements = allocate(capacity * 8)?;
+
+            for i in 0..header.len {
+                let element = read_struct::<DefaultMemory, BlockAddress>(header.elements + i * 8);
+                write_struct::<DefaultMemory, BlockAddress>(elements + i * 8, &element);
+            }
+
+            if header.capacity > 0 {
+                free(header.elements);
+            }
+
+            header.capacity = capacity;
+            header.elements = elements;
+        }
+
+        let element = write_blob(&encode_one(value).expect("Could not encode the value."))?;
+        write_struct::<DefaultMemory, BlockAddress>(header.elements + header.len * 8, &element);
+        header.len += 1;
+
+        write_struct::<DefaultMemory, StableVecHeader>(self.address, &header);
+
+        Ok(())
+    }
+
+    /// Return the element at the given index, or `None` if it is out of bounds.
+    pub fn get(&self, index: u64) -> Option<T> {
+        let header = self.header();
+
+        if index >= header.len {
+            return None;
+        }
+
+        let element = read_struct::<DefaultMemory, BlockAddress>(header.elements + index * 8);
+        let bytes = read_blob(element);
+        Some(decode_one(&bytes).expect("Could not decode the value in the stable vector."))
+    }
+
+    /// Return an iterator over the elements of the vector.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len()).map(move |i| self.get(i).unwrap())
+    }
+
+    #[inline]
+    fn header(&self) -> StableVecHeader {
+        read_struct::<DefaultMemory, StableVecHeader>(self.address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::allocator::StableAllocator;
+    use crate::core::global::*;
+
+    #[test]
+    fn push_get() {
+        set_global_allocator(StableAllocator::new());
+
+        let mut vec = StableVec::<String>::new().unwrap();
+        assert!(vec.is_empty());
+        assert_eq!(vec.get(0), None);
+
+        for i in 0..20 {
+            vec.push(&format!("item-{}", i)).unwrap();
+        }
+
+        assert_eq!(vec.len(), 20);
+        assert_eq!(vec.get(7), Some("item-7".to_string()));
+        assert_eq!(vec.get(20), None);
+        assert_eq!(
+            vec.iter().collect::<Vec<_>>(),
+            (0..20).map(|i| format!("item-{}", i)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn upgrade() {
+        set_global_allocator(StableAllocator::new());
+        init_root().unwrap();
+
+        let mut vec = StableVec::<u64>::new().unwrap();
+        set_root(vec.address());
+
+        for i in 0..10 {
+            vec.push(&(i * i)).unwrap();
+        }
+
+        // pre_upgrade -> post_upgrade, the heap is gone and we start with a new allocator.
+        drop(vec);
+        reset_global_allocator(StableAllocator::new());
+
+        let mut vec = StableVec::<u64>::from_address(root().unwrap());
+        assert_eq!(vec.len(), 10);
+        assert_eq!(vec.get(9), Some(81));
+
+        vec.push(&100).unwrap();
+        assert_eq!(vec.iter().last(), Some(100));
+    }
+}
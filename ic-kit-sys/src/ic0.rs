@@ -237,6 +237,7 @@ macro_rules! ic0_module {
 // s: the (start) module initialization function
 // F: from canister_inspect_message
 // H: from canister_heartbeat
+// T: from canister_global_timer
 // * = I G U Q Ry Rt C F H (NB: Not (start))
 ic0_module! {
     ic0.msg_arg_data_size : () -> isize;                                               // I U Q Ry F
@@ -264,6 +265,7 @@ ic0_module! {
     ic0.canister_cycle_balance : () -> i64;                                            // *
     ic0.canister_cycle_balance128 : (dst : isize) -> ();                               // *
     ic0.canister_status : () -> i32;                                                   // *
+    ic0.is_controller : (src : isize, size : isize) -> ( result : i32);                // * s
 
     ic0.msg_method_name_size : () -> isize;                                            // F
     ic0.msg_method_name_copy : (dst : isize, offset : isize, size : isize) -> ();      // F
@@ -283,6 +285,7 @@ ic0_module! {
     ic0.call_data_append : (src : isize, size : isize) -> ();                          // U Ry Rt H
     ic0.call_cycles_add : (amount : i64) -> ();                                        // U Ry Rt H
     ic0.call_cycles_add128 : (amount_high : i64, amount_low: i64) -> ();               // U Ry Rt H
+    ic0.call_with_best_effort_response : (timeout_seconds : i32) -> ();                // U Ry Rt H
     ic0.call_perform : () -> ( err_code : i32 );                                       // U Ry Rt H
 
     ic0.stable_size : () -> (page_count : i32);                                        // *
@@ -300,6 +303,7 @@ ic0_module! {
     ic0.data_certificate_copy : (dst: isize, offset: isize, size: isize) -> ();        // *
 
     ic0.time : () -> (timestamp : i64);                                                // *
+    ic0.global_timer_set : (timestamp : i64) -> i64;                                   // I U Ry Rt C T
     ic0.performance_counter : (counter_type : i32) -> (counter : i64);                 // * s
 
     ic0.debug_print : (src : isize, size : isize) -> ();                               // * s
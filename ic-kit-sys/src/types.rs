@@ -7,7 +7,7 @@ pub const CANDID_EMPTY_ARG: &[u8] = &[68, 73, 68, 76, 0, 0];
 /// Rejection code from calling another canister.
 #[allow(missing_docs)]
 #[repr(i32)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RejectionCode {
     NoError = 0,
     SysFatal = 1,
@@ -15,6 +15,7 @@ pub enum RejectionCode {
     DestinationInvalid = 3,
     CanisterReject = 4,
     CanisterError = 5,
+    SysUnknown = 6,
     Unknown,
 }
 
@@ -27,6 +28,7 @@ impl From<i32> for RejectionCode {
             3 => RejectionCode::DestinationInvalid,
             4 => RejectionCode::CanisterReject,
             5 => RejectionCode::CanisterError,
+            6 => RejectionCode::SysUnknown,
             _ => RejectionCode::Unknown,
         }
     }
@@ -43,19 +45,63 @@ pub enum CallError {
     /// Indicates that the `ic0::call_perform` failed and the call is not queued.
     CouldNotSend,
     /// The rejection callback wsa called from the IC, the call failed with the given rejection
-    /// code and message.
-    Rejected(RejectionCode, String),
+    /// code and message, the last field is the amount of cycles that were refunded to the caller.
+    Rejected(RejectionCode, String, u128),
     /// The call happened successfully, but there was an error during deserialization of the
     /// response.
     /// The raw response is captured here.
     ResponseDeserializationError(Vec<u8>),
 }
 
+impl CallError {
+    /// The rejection code of the call, or `None` if the call was not rejected.
+    pub fn rejection_code(&self) -> Option<RejectionCode> {
+        match self {
+            CallError::Rejected(code, _, _) => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// The rejection message of the call, or `None` if the call was not rejected.
+    pub fn rejection_message(&self) -> Option<&str> {
+        match self {
+            CallError::Rejected(_, message, _) => Some(message.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the destination canister or the method does not exist.
+    pub fn is_destination_invalid(&self) -> bool {
+        self.rejection_code() == Some(RejectionCode::DestinationInvalid)
+    }
+
+    /// Returns true if the call failed because either of the canisters ran out of cycles.
+    pub fn is_out_of_cycles(&self) -> bool {
+        match self {
+            CallError::Rejected(_, message, _) => {
+                let message = message.to_lowercase();
+                message.contains("out of cycles") || message.contains("insufficient cycles")
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if the call might succeed if it's retried later, that is when the call could
+    /// not be sent or it was rejected with [`RejectionCode::SysTransient`].
+    pub fn is_transient(&self) -> bool {
+        match self {
+            CallError::CouldNotSend => true,
+            CallError::Rejected(code, _, _) => *code == RejectionCode::SysTransient,
+            CallError::ResponseDeserializationError(_) => false,
+        }
+    }
+}
+
 impl fmt::Display for CallError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             CallError::CouldNotSend => f.write_str("Could not send message"),
-            CallError::Rejected(c, m) => write!(f, "Call rejected (code={:?}): '{}'", c, m),
+            CallError::Rejected(c, m, _) => write!(f, "Call rejected (code={:?}): '{}'", c, m),
             CallError::ResponseDeserializationError(..) => {
                 f.write_str("Could not deserialize the response.")
             }
@@ -84,3 +130,31 @@ impl fmt::Display for StableMemoryError {
 }
 
 impl error::Error for StableMemoryError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn call_error_predicates() {
+        let error = CallError::Rejected(
+            RejectionCode::CanisterError,
+            "Canister abc is out of cycles".into(),
+            0,
+        );
+        assert_eq!(error.rejection_code(), Some(RejectionCode::CanisterError));
+        assert!(error.is_out_of_cycles());
+        assert!(!error.is_transient());
+        assert!(!error.is_destination_invalid());
+
+        let error = CallError::Rejected(RejectionCode::SysTransient, "Queue full".into(), 0);
+        assert!(error.is_transient());
+        assert!(!error.is_out_of_cycles());
+
+        let error = CallError::Rejected(RejectionCode::DestinationInvalid, String::new(), 0);
+        assert!(error.is_destination_invalid());
+
+        assert!(CallError::CouldNotSend.is_transient());
+        assert_eq!(CallError::CouldNotSend.rejection_code(), None);
+    }
+}
@@ -1,3 +1,9 @@
+use crate::ic::{CallBuilder, CallError};
+use candid::utils::ArgumentEncoder;
+use candid::{encode_args, encode_one, CandidType, Principal};
+use ic_kit_sys::types::CANDID_EMPTY_ARG;
+use serde::Deserialize;
+
 /// A canister.
 pub trait KitCanister {
     /// Create a new instance of this canister using the provided canister id.
@@ -13,3 +19,85 @@ pub trait KitCanister {
     /// The candid description of the canister.
     fn candid() -> String;
 }
+
+/// The mode to install the code of a canister with.
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstallMode {
+    /// Install the code on an empty canister.
+    #[serde(rename = "install")]
+    Install,
+    /// Replace the code and discard the state of the canister.
+    #[serde(rename = "reinstall")]
+    Reinstall,
+    /// Upgrade the code of the canister and keep its stable memory.
+    #[serde(rename = "upgrade")]
+    Upgrade,
+}
+
+/// The options used to install the code of a canister, see `KitCanister::install_code` for the
+/// method generated by the derive macro.
+#[derive(Clone, Debug)]
+pub struct InstallConfig {
+    mode: InstallMode,
+    arg: Vec<u8>,
+}
+
+#[derive(CandidType)]
+struct InstallCodeArgument {
+    mode: InstallMode,
+    canister_id: Principal,
+    wasm_module: Vec<u8>,
+    arg: Vec<u8>,
+}
+
+impl InstallConfig {
+    /// Create a new config with the given mode and no arguments.
+    pub fn new(mode: InstallMode) -> Self {
+        Self {
+            mode,
+            arg: CANDID_EMPTY_ARG.to_vec(),
+        }
+    }
+
+    /// Use the given candid tuple value as the argument of the init or post_upgrade hook.
+    pub fn with_args<T: ArgumentEncoder>(mut self, arguments: T) -> Self {
+        self.arg = encode_args(arguments).unwrap();
+        self
+    }
+
+    /// Shorthand for `with_args((argument, ))`.
+    pub fn with_arg<T: CandidType>(mut self, argument: T) -> Self {
+        self.arg = encode_one(argument).unwrap();
+        self
+    }
+
+    /// Pass the given raw buffer as the argument of the init or post_upgrade hook.
+    pub fn with_raw_arg<A: Into<Vec<u8>>>(mut self, argument: A) -> Self {
+        self.arg = argument.into();
+        self
+    }
+
+    /// The install mode.
+    pub fn mode(&self) -> InstallMode {
+        self.mode
+    }
+
+    /// Install the given wasm module on the canister by calling the management canister.
+    pub async fn install_code(
+        self,
+        canister_id: Principal,
+        wasm_module: Vec<u8>,
+    ) -> Result<(), CallError> {
+        let argument = InstallCodeArgument {
+            mode: self.mode,
+            canister_id,
+            wasm_module,
+            arg: self.arg,
+        };
+
+        CallBuilder::new(Principal::management_canister(), "install_code")
+            .with_arg(argument)
+            .perform_rejection()
+            .await
+    }
+}
@@ -253,6 +253,8 @@ fn cleanup(state_ptr: *const InnerCell<CallFutureState>) {
 /// API requires us to pass one thin pointer, while a a pointer to a `dyn Trait`
 /// can only be fat. So we create one additional thin pointer, pointing to the
 /// fat pointer and pass it instead.
+///
+/// The future does not have to be `Send`, since the canister only ever runs on a single thread.
 #[inline]
 pub fn spawn<F: 'static + Future<Output = ()>>(future: F) {
     let future_ptr = Box::into_raw(Box::new(future));
@@ -1,22 +1,26 @@
 use crate::futures;
 use crate::futures::CallFuture;
-use crate::ic::Cycles;
+use crate::ic::{msg_cycles_refunded, Cycles, RetryPolicy};
 use crate::utils::arg_data_raw;
 use candid::utils::{ArgumentDecoder, ArgumentEncoder};
 use candid::{decode_args, decode_one, encode_args, encode_one, CandidType, Principal};
 use ic_kit_sys::ic0;
 use serde::de::DeserializeOwned;
 
-use ic_kit_sys::types::RejectionCode;
-pub use ic_kit_sys::types::{CallError, CANDID_EMPTY_ARG};
+pub use ic_kit_sys::types::{CallError, RejectionCode, CANDID_EMPTY_ARG};
 
 /// A call builder that let's you create an inter-canister call which can be then sent to the
 /// destination.
+///
+/// The callee observes the id of the current canister as its [`caller`](crate::ic::caller), the
+/// sender of an inter-canister call can not be changed. When testing, the runtime's
+/// `CallBuilder::with_caller` can be used to send a call from any other principal.
 pub struct CallBuilder {
     canister_id: Principal,
     method_name: String,
     payment: Cycles,
     arg: Option<Vec<u8>>,
+    timeout: Option<u32>,
 }
 
 impl CallBuilder {
@@ -28,6 +32,7 @@ impl CallBuilder {
             method_name: method_name.into(),
             payment: 0,
             arg: None,
+            timeout: None,
         }
     }
 
@@ -102,21 +107,30 @@ impl CallBuilder {
         self
     }
 
+    /// Make this a best-effort call, which gives up waiting for the response after the given
+    /// number of seconds. A call that times out is rejected with [`RejectionCode::SysUnknown`],
+    /// in which case the callee may or may not have executed the call, and the cycles attached
+    /// to the call might be lost.
+    ///
+    /// The IC caps the timeout at 300 seconds.
+    pub fn with_best_effort_response(mut self, timeout_secs: u32) -> Self {
+        self.timeout = Some(timeout_secs);
+        self
+    }
+
     /// Should be called after the `ic0::call_new` to set the call arguments.
     #[inline(always)]
     unsafe fn ic0_internal_call_perform(&self) -> i32 {
-        #[cfg(not(feature = "experimental-cycles128"))]
-        ic0::call_cycles_add(self.payment as i64);
-
-        #[cfg(feature = "experimental-cycles128")]
-        if self.payment > 0 && self.payment < (u64::MAX as u128) {
-            ic0::call_cycles_add(self.payment as i64);
-        } else if self.payment > 0 {
+        if self.payment > 0 {
             let high = (self.payment >> 64) as u64 as i64;
-            let low = (self.payment & (1 << 64)) as u64 as i64;
+            let low = self.payment as u64 as i64;
             ic0::call_cycles_add128(high, low);
         }
 
+        if let Some(timeout) = self.timeout {
+            ic0::call_with_best_effort_response(timeout as i32);
+        }
+
         let args_raw = self.arg.as_deref().unwrap_or(CANDID_EMPTY_ARG);
 
         if !args_raw.is_empty() {
@@ -217,6 +231,7 @@ impl CallBuilder {
         Err(CallError::Rejected(
             rejection_code.into(),
             String::from_utf8_lossy(&bytes).to_string(),
+            msg_cycles_refunded(),
         ))
     }
 
@@ -268,4 +283,45 @@ impl CallBuilder {
             Ok(r) => Ok(r),
         }
     }
+
+    /// Perform the call and retry it while it fails with a transient error, as determined by
+    /// [`CallError::is_transient`], other errors are returned right away.
+    ///
+    /// Every attempt is a new call, the cycles of a rejected attempt are refunded and an attempt
+    /// that could not be sent was never charged, so the payment is only spent once.
+    ///
+    /// # Traps
+    ///
+    /// This method traps if the amount determined in the `payment` is larger than the canister's
+    /// balance at the time of invocation.
+    pub async fn perform_with_retry<R: for<'a> ArgumentDecoder<'a>>(
+        &self,
+        policy: RetryPolicy,
+    ) -> Result<R, CallError> {
+        policy.run(|| self.perform()).await
+    }
+}
+
+/// Call the given method of a canister with the candid encoded arguments and return the candid
+/// decoded response, this is a thin wrapper over
+/// `CallBuilder::new(canister_id, method_name).with_args(arguments).perform()`, use the
+/// [`CallBuilder`] directly to attach cycles or to get the raw response.
+///
+/// ```no_run
+/// use ic_kit::prelude::*;
+///
+/// async fn get_counter(canister_id: Principal) -> u64 {
+///     let (value,): (u64,) = ic::call(canister_id, "get_counter", ()).await.unwrap();
+///     value
+/// }
+/// ```
+pub async fn call<T: ArgumentEncoder, R: for<'a> ArgumentDecoder<'a>, S: Into<String>>(
+    canister_id: Principal,
+    method_name: S,
+    arguments: T,
+) -> Result<R, CallError> {
+    CallBuilder::new(canister_id, method_name)
+        .with_args(arguments)
+        .perform()
+        .await
 }
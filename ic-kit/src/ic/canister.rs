@@ -1,5 +1,5 @@
 use crate::ic::{with, Cycles};
-use candid::Principal;
+use candid::{encode_one, CandidType, Principal};
 use ic_kit_sys::ic0;
 use std::convert::TryFrom;
 
@@ -54,21 +54,26 @@ pub fn time() -> u64 {
 /// The balance of the canister.
 #[inline(always)]
 pub fn balance() -> Cycles {
-    #[cfg(not(feature = "experimental-cycles128"))]
-    unsafe {
-        ic0::canister_cycle_balance() as u64
-    }
+    let mut recv = 0u128;
+    unsafe { ic0::canister_cycle_balance128(&mut recv as *mut u128 as isize) }
+    u128::from_le(recv)
+}
 
-    #[cfg(feature = "experimental-cycles128")]
-    unsafe {
-        let mut recv = 0u128;
-        ic0::canister_cycle_balance128(&mut recv as *mut u128 as isize);
-        u128::from_le(recv)
-    }
+/// The balance of the canister as a 64-bit number.
+#[deprecated(
+    since = "0.5.0",
+    note = "The balance can be larger than u64::MAX, use ic::balance instead."
+)]
+#[inline(always)]
+pub fn balance64() -> u64 {
+    unsafe { ic0::canister_cycle_balance() as u64 }
 }
 
 /// The caller who has invoked this method on the canister.
 ///
+/// For ingress messages this is the principal of the user that signed the request and for
+/// inter-canister calls this is the id of the calling canister.
+///
 /// # Panics
 ///
 /// If called after a reply/reject callback.
@@ -82,6 +87,41 @@ pub fn caller() -> Principal {
     Principal::try_from(&bytes).unwrap()
 }
 
+/// Returns true if the given principal is a controller of the canister.
+#[inline(always)]
+pub fn is_controller(principal: &Principal) -> bool {
+    let bytes = principal.as_slice();
+    unsafe { ic0::is_controller(bytes.as_ptr() as isize, bytes.len() as isize) != 0 }
+}
+
+/// Returns true if the caller of the current call is a controller of the canister.
+///
+/// # Panics
+///
+/// When called from a context where there is no caller, see [`caller`].
+#[inline(always)]
+pub fn caller_is_controller() -> bool {
+    is_controller(&caller())
+}
+
+/// Reply to the current call with the candid encoded value, this is meant to be used in the
+/// methods that are exported with `manual_reply = true`.
+///
+/// # Panics
+///
+/// If the value could not be encoded.
+#[inline(always)]
+pub fn reply<T: CandidType>(value: T) {
+    let bytes = encode_one(value).expect("Could not encode canister's response.");
+    crate::utils::reply(&bytes);
+}
+
+/// Reply to the current call with the given raw bytes.
+#[inline(always)]
+pub fn reply_raw(bytes: &[u8]) {
+    crate::utils::reply(bytes);
+}
+
 /// Set the certified data of the canister, this method traps if data.len > 32.
 #[inline(always)]
 pub fn set_certified_data(data: &[u8]) {
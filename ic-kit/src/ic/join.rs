@@ -0,0 +1,176 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// A future that is either still running or has completed with its output.
+enum MaybeDone<F: Future> {
+    Pending(Pin<Box<F>>),
+    Done(Option<F::Output>),
+}
+
+impl<F: Future> MaybeDone<F> {
+    fn new(future: F) -> Self {
+        MaybeDone::Pending(Box::pin(future))
+    }
+
+    /// Poll the future if it's still running and return `true` once it has completed.
+    fn poll(&mut self, cx: &mut Context<'_>) -> bool {
+        if let MaybeDone::Pending(future) = self {
+            match future.as_mut().poll(cx) {
+                Poll::Ready(output) => *self = MaybeDone::Done(Some(output)),
+                Poll::Pending => return false,
+            }
+        }
+
+        true
+    }
+
+    fn take(&mut self) -> F::Output {
+        match self {
+            MaybeDone::Done(output) => output.take().expect("Output is already taken."),
+            MaybeDone::Pending(_) => panic!("Future is not completed yet."),
+        }
+    }
+}
+
+/// The future returned by [`join`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Join<A: Future, B: Future> {
+    a: MaybeDone<A>,
+    b: MaybeDone<B>,
+}
+
+// The inner futures are boxed, so they are never moved.
+impl<A: Future, B: Future> Unpin for Join<A, B> {}
+
+impl<A: Future, B: Future> Future for Join<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        // Both of the futures must be polled, so they register the waker for their own calls.
+        let a = this.a.poll(cx);
+        let b = this.b.poll(cx);
+
+        if a && b {
+            Poll::Ready((this.a.take(), this.b.take()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// The future returned by [`join_all`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct JoinAll<F: Future> {
+    futures: Vec<MaybeDone<F>>,
+}
+
+impl<F: Future> Unpin for JoinAll<F> {}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut done = true;
+
+        for future in self.futures.iter_mut() {
+            done &= future.poll(cx);
+        }
+
+        if done {
+            Poll::Ready(self.futures.iter_mut().map(MaybeDone::take).collect())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Await two futures concurrently and return both of the outputs, this can be used to perform
+/// two inter-canister calls in parallel.
+///
+/// ```ignore
+/// let (a, b) = ic::join(
+///     CallBuilder::new(id(), "fib").with_arg(n - 1).perform_one::<u64>(),
+///     CallBuilder::new(id(), "fib").with_arg(n - 2).perform_one::<u64>(),
+/// )
+/// .await;
+/// ```
+pub fn join<A: Future, B: Future>(a: A, b: B) -> Join<A, B> {
+    Join {
+        a: MaybeDone::new(a),
+        b: MaybeDone::new(b),
+    }
+}
+
+/// Await all of the given futures concurrently and return the outputs in the same order.
+pub fn join_all<I>(futures: I) -> JoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    JoinAll {
+        futures: futures.into_iter().map(MaybeDone::new).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::noop_waker;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A future that is pending for the given number of polls.
+    struct Yield(usize, u64);
+
+    impl Future for Yield {
+        type Output = u64;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u64> {
+            if self.0 == 0 {
+                return Poll::Ready(self.1);
+            }
+
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    fn block_on<F: Future + Unpin>(mut future: F) -> (F::Output, usize) {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut polls = 0;
+
+        loop {
+            polls += 1;
+            if let Poll::Ready(output) = Pin::new(&mut future).poll(&mut cx) {
+                return (output, polls);
+            }
+        }
+    }
+
+    #[test]
+    fn join_polls_both() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let c = counter.clone();
+        let a = async move {
+            c.fetch_add(1, Ordering::SeqCst);
+            Yield(2, 1).await
+        };
+
+        let (output, polls) = block_on(join(a, Yield(1, 2)));
+        assert_eq!(output, (1, 2));
+        assert_eq!(polls, 3);
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn join_all_keeps_order() {
+        let futures = vec![Yield(3, 0), Yield(0, 1), Yield(1, 2)];
+        let (output, polls) = block_on(join_all(futures));
+        assert_eq!(output, vec![0, 1, 2]);
+        assert_eq!(polls, 4);
+    }
+}
@@ -1,13 +1,25 @@
 mod call;
 mod canister;
 mod cycles;
+mod join;
+mod retry;
 mod spawn;
 mod stable;
 mod storage;
+mod timers;
+
+/// Checked arithmetic over the Candid `Nat` and `Int` types.
+pub mod num;
+
+/// Subaccount and ledger account identifier helpers for principals.
+pub mod principal;
 
 pub use call::*;
 pub use canister::*;
 pub use cycles::*;
+pub use join::*;
+pub use retry::*;
 pub use spawn::*;
 pub use stable::*;
 pub use storage::*;
+pub use timers::*;
@@ -0,0 +1,221 @@
+//! Checked arithmetic helpers over the Candid [`Nat`] and [`Int`] types.
+//!
+//! The Candid number types are arbitrary-precision, which is what we want for things such as
+//! ledger amounts, but converting them back to fixed size integers is where overflows happen.
+//! This module provides the conversions as `Option` returning methods and a small set of
+//! arithmetic functions that never panic.
+//!
+//! # Example
+//!
+//! ```
+//! use ic_kit::ic::num::{self, NatExt};
+//! use ic_kit::Nat;
+//!
+//! let balance = Nat::from(100u64);
+//! let fee = Nat::from(10u64);
+//!
+//! let remaining = num::checked_sub(&balance, &fee).unwrap();
+//! assert_eq!(remaining.to_u64_checked(), Some(90));
+//! assert_eq!(num::checked_sub(&fee, &balance), None);
+//! ```
+
+use candid::{Int, Nat};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+/// Extension methods for [`Nat`].
+pub trait NatExt: Sized {
+    /// Convert the number to a `u32`, returns `None` if the value does not fit.
+    fn to_u32_checked(&self) -> Option<u32>;
+
+    /// Convert the number to a `u64`, returns `None` if the value does not fit.
+    fn to_u64_checked(&self) -> Option<u64>;
+
+    /// Convert the number to a `u128`, returns `None` if the value does not fit.
+    fn to_u128_checked(&self) -> Option<u128>;
+
+    /// Returns true if the number is zero.
+    fn is_zero(&self) -> bool;
+
+    /// Subtract `rhs` from this number, returns `None` if the result would be negative.
+    fn checked_sub(&self, rhs: &Self) -> Option<Self>;
+
+    /// Subtract `rhs` from this number, clamping the result at zero.
+    fn saturating_sub(&self, rhs: &Self) -> Self;
+}
+
+impl NatExt for Nat {
+    #[inline]
+    fn to_u32_checked(&self) -> Option<u32> {
+        u32::try_from(&self.0).ok()
+    }
+
+    #[inline]
+    fn to_u64_checked(&self) -> Option<u64> {
+        u64::try_from(&self.0).ok()
+    }
+
+    #[inline]
+    fn to_u128_checked(&self) -> Option<u128> {
+        u128::try_from(&self.0).ok()
+    }
+
+    #[inline]
+    fn is_zero(&self) -> bool {
+        self.0.bits() == 0
+    }
+
+    #[inline]
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        checked_sub(self, rhs)
+    }
+
+    #[inline]
+    fn saturating_sub(&self, rhs: &Self) -> Self {
+        checked_sub(self, rhs).unwrap_or_else(|| Nat::from(0u64))
+    }
+}
+
+/// Extension methods for [`Int`].
+pub trait IntExt {
+    /// Convert the number to an `i64`, returns `None` if the value does not fit.
+    fn to_i64_checked(&self) -> Option<i64>;
+
+    /// Convert the number to an `i128`, returns `None` if the value does not fit.
+    fn to_i128_checked(&self) -> Option<i128>;
+
+    /// Convert the number to a [`Nat`], returns `None` if the value is negative.
+    fn to_nat_checked(&self) -> Option<Nat>;
+}
+
+impl IntExt for Int {
+    #[inline]
+    fn to_i64_checked(&self) -> Option<i64> {
+        i64::try_from(&self.0).ok()
+    }
+
+    #[inline]
+    fn to_i128_checked(&self) -> Option<i128> {
+        i128::try_from(&self.0).ok()
+    }
+
+    #[inline]
+    fn to_nat_checked(&self) -> Option<Nat> {
+        self.0.to_biguint().map(Nat)
+    }
+}
+
+/// Returns `a + b`.
+#[inline]
+pub fn add(a: &Nat, b: &Nat) -> Nat {
+    Nat(&a.0 + &b.0)
+}
+
+/// Returns `a - b`, or `None` if `b` is larger than `a`.
+#[inline]
+pub fn checked_sub(a: &Nat, b: &Nat) -> Option<Nat> {
+    if a.0 < b.0 {
+        None
+    } else {
+        Some(Nat(&a.0 - &b.0))
+    }
+}
+
+/// Returns `a * b`.
+#[inline]
+pub fn mul(a: &Nat, b: &Nat) -> Nat {
+    Nat(&a.0 * &b.0)
+}
+
+/// Returns `a / b`, or `None` if `b` is zero.
+#[inline]
+pub fn checked_div(a: &Nat, b: &Nat) -> Option<Nat> {
+    if b.is_zero() {
+        None
+    } else {
+        Some(Nat(&a.0 / &b.0))
+    }
+}
+
+/// Compare a [`Nat`] with a primitive integer without allocating a new [`Nat`].
+#[inline]
+pub fn cmp_u64(a: &Nat, b: u64) -> Ordering {
+    match a.to_u64_checked() {
+        Some(a) => a.cmp(&b),
+        None => Ordering::Greater,
+    }
+}
+
+/// Returns `a + b` for signed integers.
+#[inline]
+pub fn add_int(a: &Int, b: &Int) -> Int {
+    Int(&a.0 + &b.0)
+}
+
+/// Returns `a - b` for signed integers.
+#[inline]
+pub fn sub_int(a: &Int, b: &Int) -> Int {
+    Int(&a.0 - &b.0)
+}
+
+/// Returns `a * b` for signed integers.
+#[inline]
+pub fn mul_int(a: &Int, b: &Int) -> Int {
+    Int(&a.0 * &b.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_u64_checked() {
+        assert_eq!(Nat::from(0u64).to_u64_checked(), Some(0));
+        assert_eq!(Nat::from(u64::MAX).to_u64_checked(), Some(u64::MAX));
+
+        let overflow = add(&Nat::from(u64::MAX), &Nat::from(1u64));
+        assert_eq!(overflow.to_u64_checked(), None);
+        assert_eq!(overflow.to_u128_checked(), Some(u64::MAX as u128 + 1));
+        assert_eq!(Nat::from(u64::MAX).to_u32_checked(), None);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = Nat::from(1_000_000_000_000u64);
+        let b = Nat::from(7u64);
+
+        assert_eq!(add(&a, &b), Nat::from(1_000_000_000_007u64));
+        assert_eq!(checked_sub(&a, &b), Some(Nat::from(999_999_999_993u64)));
+        assert_eq!(checked_sub(&b, &a), None);
+        assert_eq!(b.saturating_sub(&a), Nat::from(0u64));
+        assert_eq!(mul(&a, &b), Nat::from(7_000_000_000_000u64));
+        assert_eq!(checked_div(&a, &b), Some(Nat::from(142_857_142_857u64)));
+        assert_eq!(checked_div(&a, &Nat::from(0u64)), None);
+
+        let big = mul(&Nat::from(u64::MAX), &Nat::from(u64::MAX));
+        assert_eq!(
+            big.to_u128_checked(),
+            Some(u64::MAX as u128 * u64::MAX as u128)
+        );
+
+        assert_eq!(cmp_u64(&a, 7), Ordering::Greater);
+        assert_eq!(cmp_u64(&b, 7), Ordering::Equal);
+        assert_eq!(cmp_u64(&big, u64::MAX), Ordering::Greater);
+    }
+
+    #[test]
+    fn int() {
+        let a = Int::from(-5i64);
+        let b = Int::from(3i64);
+
+        assert_eq!(add_int(&a, &b).to_i64_checked(), Some(-2));
+        assert_eq!(sub_int(&b, &a).to_i64_checked(), Some(8));
+        assert_eq!(mul_int(&a, &b).to_i64_checked(), Some(-15));
+        assert_eq!(a.to_nat_checked(), None);
+        assert_eq!(b.to_nat_checked(), Some(Nat::from(3u64)));
+
+        let overflow = add_int(&Int::from(i64::MAX), &Int::from(1i64));
+        assert_eq!(overflow.to_i64_checked(), None);
+        assert_eq!(overflow.to_i128_checked(), Some(i64::MAX as i128 + 1));
+    }
+}
@@ -0,0 +1,172 @@
+//! Helpers to derive the ledger subaccounts and account identifiers of a [`Principal`].
+//!
+//! An account on the ICP ledger is identified by the hash of its owner and a 32 byte subaccount,
+//! the [`account_identifier`] function computes this hash the same way the ledger does, including
+//! the domain separator and the CRC32 checksum.
+//!
+//! # Example
+//!
+//! ```
+//! use ic_kit::ic::principal::{self, PrincipalExt};
+//! use ic_kit::Principal;
+//!
+//! let user = Principal::anonymous();
+//! assert!(user.is_anonymous());
+//!
+//! let account = principal::account_identifier(&user, &principal::DEFAULT_SUBACCOUNT);
+//! assert_eq!(account[..4], [0x1c, 0x7a, 0x48, 0xba]);
+//! ```
+
+use candid::Principal;
+use sha2::{Digest, Sha224, Sha256};
+
+/// A 32 byte subaccount of a principal on the ledger.
+pub type Subaccount = [u8; 32];
+
+/// A 32 byte account identifier, the CRC32 checksum of the hash followed by the hash itself.
+pub type AccountIdentifier = [u8; 32];
+
+/// The subaccount used when none is specified.
+pub const DEFAULT_SUBACCOUNT: Subaccount = [0; 32];
+
+/// Extension methods for [`Principal`].
+pub trait PrincipalExt {
+    /// Returns true if this is the anonymous principal.
+    fn is_anonymous(&self) -> bool;
+
+    /// Returns true if this is the id of the management canister.
+    fn is_management(&self) -> bool;
+}
+
+impl PrincipalExt for Principal {
+    fn is_anonymous(&self) -> bool {
+        *self == Principal::anonymous()
+    }
+
+    fn is_management(&self) -> bool {
+        *self == Principal::management_canister()
+    }
+}
+
+/// Return the subaccount that stores the given principal, the first byte is the length of the
+/// principal followed by its bytes and zero padding. This is the usual way of giving each user
+/// of a canister a separate subaccount of the canister.
+pub fn principal_to_subaccount(principal: &Principal) -> Subaccount {
+    let bytes = principal.as_slice();
+    let mut subaccount = [0; 32];
+    subaccount[0] = bytes.len() as u8;
+    subaccount[1..1 + bytes.len()].copy_from_slice(bytes);
+    subaccount
+}
+
+/// Derive a subaccount from a principal and a nonce, this can be used when a principal needs
+/// more than one subaccount, for example one per deposit. The subaccount is the SHA-256 hash of
+/// `"\x0Asubaccount"`, the principal and the big-endian nonce.
+pub fn derive_subaccount(principal: &Principal, nonce: u64) -> Subaccount {
+    let mut hasher = Sha256::new();
+    hasher.update(b"\x0Asubaccount");
+    hasher.update(principal.as_slice());
+    hasher.update(nonce.to_be_bytes());
+    hasher.finalize().into()
+}
+
+/// Return the ledger account identifier of the given owner and subaccount.
+pub fn account_identifier(owner: &Principal, subaccount: &Subaccount) -> AccountIdentifier {
+    let mut hasher = Sha224::new();
+    hasher.update(b"\x0Aaccount-id");
+    hasher.update(owner.as_slice());
+    hasher.update(subaccount);
+    let hash = hasher.finalize();
+
+    let mut result = [0; 32];
+    result[..4].copy_from_slice(&crc32(&hash).to_be_bytes());
+    result[4..].copy_from_slice(&hash);
+    result
+}
+
+/// Returns true if the given bytes are an account identifier with a valid checksum.
+pub fn is_valid_account_identifier(bytes: &[u8]) -> bool {
+    bytes.len() == 32 && bytes[..4] == crc32(&bytes[4..]).to_be_bytes()
+}
+
+/// The CRC32 (IEEE) checksum used by the ledger.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn account_identifier_vectors() {
+        assert_eq!(
+            hex(&account_identifier(
+                &Principal::anonymous(),
+                &DEFAULT_SUBACCOUNT
+            )),
+            "1c7a48ba6a562aa9eaa2481a9049cdf0433b9738c992d698c31d8abf89cadc79"
+        );
+
+        let mut subaccount = DEFAULT_SUBACCOUNT;
+        subaccount[31] = 1;
+        let owner = Principal::from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 1, 1]);
+        let account = account_identifier(&owner, &subaccount);
+        assert_eq!(
+            hex(&account),
+            "3593f52e2df55b057920693feaab7471b3f5a23d991fe2cc4cd1df85338a3047"
+        );
+
+        assert!(is_valid_account_identifier(&account));
+        let mut corrupted = account;
+        corrupted[10] ^= 1;
+        assert!(!is_valid_account_identifier(&corrupted));
+        assert!(!is_valid_account_identifier(&account[1..]));
+    }
+
+    #[test]
+    fn subaccounts() {
+        let principal = Principal::anonymous();
+
+        let subaccount = principal_to_subaccount(&principal);
+        assert_eq!(subaccount[..2], [1, 4]);
+        assert!(subaccount[2..].iter().all(|b| *b == 0));
+
+        assert_eq!(
+            hex(&derive_subaccount(&principal, 7)),
+            "e8d33bdfac7cb1adf72ba34ec3651661b749174966a9a53221067dafe3dea393"
+        );
+        assert_ne!(
+            derive_subaccount(&principal, 7),
+            derive_subaccount(&principal, 8)
+        );
+    }
+
+    #[test]
+    fn principal_ext() {
+        assert!(Principal::anonymous().is_anonymous());
+        assert!(!Principal::anonymous().is_management());
+        assert!(Principal::management_canister().is_management());
+        assert!(!Principal::from_slice(&[1]).is_anonymous());
+    }
+}
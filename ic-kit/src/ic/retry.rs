@@ -0,0 +1,281 @@
+use crate::ic::{set_timer, CallError};
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// The amount of time to wait between the attempts of a [`RetryPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Retry right away.
+    None,
+    /// Wait the same amount of time before every retry.
+    Fixed(Duration),
+    /// Wait `initial` before the first retry and double the delay every time after that, up to
+    /// `max`.
+    Exponential { initial: Duration, max: Duration },
+}
+
+/// Determines how many times a call is attempted and how long to wait between the attempts, see
+/// [`crate::ic::CallBuilder::perform_with_retry`].
+///
+/// The delay is implemented using the timers, waiting on a timer does not keep the incoming call
+/// open, so a method that has to reply to its caller should only use [`Backoff::None`], and the
+/// other backoffs are meant to be used from timers and spawned tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff: Backoff,
+}
+
+impl Default for RetryPolicy {
+    /// Attempt a call at most three times without waiting between the attempts.
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl RetryPolicy {
+    /// Create a new policy that tries the call at most `max_attempts` times, including the first
+    /// attempt, without waiting between the attempts.
+    ///
+    /// # Panics
+    ///
+    /// If `max_attempts` is zero.
+    pub fn new(max_attempts: u32) -> Self {
+        assert!(max_attempts > 0, "max_attempts can not be zero.");
+
+        Self {
+            max_attempts,
+            backoff: Backoff::None,
+        }
+    }
+
+    /// Wait between the attempts using the given backoff.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// The maximum number of attempts.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// The delay before the given retry, the first retry is `1`.
+    pub fn delay(&self, retry: u32) -> Duration {
+        match self.backoff {
+            Backoff::None => Duration::ZERO,
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 1u32.checked_shl(retry - 1).unwrap_or(u32::MAX);
+                initial.saturating_mul(factor).min(max)
+            }
+        }
+    }
+
+    /// Run the given function until it succeeds, returns an error which is not transient, or the
+    /// attempts are exhausted. See [`CallError::is_transient`].
+    pub(crate) async fn run<T, F, Fut>(&self, mut f: F) -> Result<T, CallError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, CallError>>,
+    {
+        let mut retry = 0;
+
+        loop {
+            match f().await {
+                Err(e) if e.is_transient() && retry + 1 < self.max_attempts => {
+                    retry += 1;
+
+                    let delay = self.delay(retry);
+                    if delay > Duration::ZERO {
+                        sleep(delay).await;
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct SleepState {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/// A future that is resolved once the timer with the given delay is executed.
+struct Sleep(Rc<RefCell<SleepState>>);
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.0.borrow_mut();
+
+        if state.done {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+fn sleep(delay: Duration) -> Sleep {
+    let state = Rc::new(RefCell::new(SleepState::default()));
+    let timer_state = state.clone();
+
+    set_timer(delay, move || {
+        // Release the borrow before waking, since the waker polls the future.
+        let waker = {
+            let mut state = timer_state.borrow_mut();
+            state.done = true;
+            state.waker.take()
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+
+    Sleep(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ic::{reply, spawn, CallBuilder};
+    use crate::rt::types::Env;
+    use crate::rt::{Canister, CanisterMethod, Replica, TokioRuntimeBuilder};
+    use crate::test_utils::noop_waker;
+    use candid::Principal;
+    use ic_kit_sys::types::RejectionCode;
+    use std::cell::Cell;
+
+    /// Poll a future which is expected to be ready right away.
+    fn now_or_never<F: Future>(future: F) -> F::Output {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("The future is not ready."),
+        }
+    }
+
+    /// A call that fails with the given error for the first `failures` attempts.
+    fn flaky(
+        attempts: &Cell<u32>,
+        failures: u32,
+        error: fn() -> CallError,
+    ) -> impl Future<Output = Result<u32, CallError>> + '_ {
+        attempts.set(attempts.get() + 1);
+        let attempt = attempts.get();
+        async move {
+            if attempt <= failures {
+                Err(error())
+            } else {
+                Ok(attempt)
+            }
+        }
+    }
+
+    fn transient() -> CallError {
+        CallError::Rejected(RejectionCode::SysTransient, "Try again.".into(), 0)
+    }
+
+    fn permanent() -> CallError {
+        CallError::Rejected(RejectionCode::CanisterReject, "No.".into(), 0)
+    }
+
+    #[test]
+    fn retry_transient() {
+        let attempts = Cell::new(0);
+        let policy = RetryPolicy::new(3);
+        let result = now_or_never(policy.run(|| flaky(&attempts, 2, transient)));
+        assert_eq!(result.unwrap(), 3);
+
+        let attempts = Cell::new(0);
+        let result = now_or_never(policy.run(|| flaky(&attempts, 3, transient)));
+        assert!(result.unwrap_err().is_transient());
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[test]
+    fn permanent_error_is_not_retried() {
+        let attempts = Cell::new(0);
+        let result = now_or_never(RetryPolicy::new(3).run(|| flaky(&attempts, 2, permanent)));
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn exponential_delay() {
+        let policy = RetryPolicy::new(10).with_backoff(Backoff::Exponential {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(5),
+        });
+
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(4), Duration::from_secs(5));
+        assert_eq!(policy.delay(40), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn retry_rejected_call() {
+        struct Foo;
+
+        impl CanisterMethod for Foo {
+            const EXPORT_NAME: &'static str = "canister_update foo";
+
+            fn exported_method() {
+                reply(7u32);
+            }
+        }
+
+        fn call_foo(max_attempts: u32) {
+            spawn(async move {
+                let result = CallBuilder::new(Principal::from_slice(&[2]), "foo")
+                    .perform_with_retry::<(u32,)>(RetryPolicy::new(max_attempts))
+                    .await;
+
+                reply(result.map(|(value,)| value).map_err(|e| e.to_string()));
+            });
+        }
+
+        TokioRuntimeBuilder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let replica = Replica::default();
+                let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+                replica.add_canister(
+                    Canister::new(Principal::from_slice(&[2]))
+                        .with_method::<Foo>()
+                        .with_rejecting_method_times(
+                            "foo",
+                            5,
+                            RejectionCode::SysTransient,
+                            "Try again.",
+                        ),
+                );
+
+                // The three attempts are rejected.
+                let reply = canister.custom(|| call_foo(3), Env::default()).await;
+                let result = reply.decode_one::<Result<u32, String>>().unwrap();
+                assert!(result.unwrap_err().contains("Try again."));
+
+                // Two more rejections are left, so the third attempt goes through.
+                let reply = canister.custom(|| call_foo(3), Env::default()).await;
+                let result = reply.decode_one::<Result<u32, String>>().unwrap();
+                assert_eq!(result, Ok(7));
+            });
+    }
+}
@@ -2,7 +2,35 @@ use crate::futures;
 
 /// Execute a future without blocking the current call. The given future is polled once initially
 /// to kickstart the async calls.
+///
+/// Canisters are single threaded, so unlike most executors the future is not required to be
+/// `Send`, both in WASM and when running under the ic-kit runtime.
 #[inline(always)]
 pub fn spawn<F: 'static + std::future::Future<Output = ()>>(future: F) {
     futures::spawn(future)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ic::CallBuilder;
+    use candid::Principal;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// This only has to compile, the spawned future holds an `Rc` across an inter-canister call
+    /// which makes it `!Send`.
+    #[allow(dead_code)]
+    fn spawn_non_send_future() {
+        let counter = Rc::new(Cell::new(0u64));
+
+        spawn(async move {
+            let value: u64 = CallBuilder::new(Principal::management_canister(), "get_counter")
+                .perform_one()
+                .await
+                .unwrap();
+
+            counter.set(value);
+        });
+    }
+}
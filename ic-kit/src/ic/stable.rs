@@ -1,3 +1,4 @@
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
 use ic_kit_sys::ic0;
 use ic_kit_sys::types::StableMemoryError;
 
@@ -100,3 +101,68 @@ pub(crate) fn stable_bytes() -> Vec<u8> {
     }
     vec
 }
+
+/// Encode the given candid tuple and save it to the beginning of the stable memory, growing the
+/// memory if needed. This is meant to be called from the `pre_upgrade` hook, and the value can
+/// be loaded in `post_upgrade` using [`stable_restore`].
+///
+/// The stable memory is laid out as:
+///
+/// - The length of the candid encoded value as a little-endian `u64` at offset 0.
+/// - The candid encoded value (`DIDL` magic followed by the args) starting at offset 8.
+///
+/// The rest of the stable memory is not touched. This layout is not compatible with the
+/// deprecated `ic_kit::stable::stable_store`, which does not store the length.
+pub fn stable_save<T: ArgumentEncoder>(data: T) -> Result<(), String> {
+    let bytes = candid::encode_args(data).map_err(|e| format!("{:?}", e))?;
+    write_prefixed(&[], &bytes)
+}
+
+/// Write the length of `bytes` as a little-endian `u64` at offset 0, followed by the header and
+/// the bytes themselves, growing the stable memory if needed.
+pub(crate) fn write_prefixed(header: &[u8], bytes: &[u8]) -> Result<(), String> {
+    let len = (bytes.len() as u64).to_le_bytes();
+    let offset = (len.len() + header.len()) as u64;
+    let required = offset + bytes.len() as u64;
+    let current = (stable_size() as u64) << 16;
+
+    if required > current {
+        let pages = (required - current + 0xffff) >> 16;
+        stable_grow(pages as StableSize).map_err(|e| format!("{:?}", e))?;
+    }
+
+    stable_write(0, &len);
+    stable_write(len.len() as StableSize, header);
+    stable_write(offset as StableSize, bytes);
+
+    Ok(())
+}
+
+/// Restore the value that was saved using [`stable_save`], an error is returned if the stable
+/// memory does not contain a valid value of the given type.
+pub fn stable_restore<T>() -> Result<T, String>
+where
+    T: for<'de> ArgumentDecoder<'de>,
+{
+    let size = (stable_size() as u64) << 16;
+
+    if size < 8 {
+        return Err("The stable memory is empty.".into());
+    }
+
+    let mut len = [0u8; 8];
+    stable_read(0, &mut len);
+    let len = u64::from_le_bytes(len);
+
+    if len > size - 8 {
+        return Err(format!(
+            "The stored value of {} bytes does not fit in the stable memory.",
+            len
+        ));
+    }
+
+    let mut bytes = vec![0u8; 8 + len as usize];
+    stable_read(0, &mut bytes);
+
+    candid::decode_args(&bytes[8..]).map_err(|e| format!("{:?}", e))
+}
@@ -0,0 +1,335 @@
+use crate::ic::{time, with_mut};
+use ic_kit_sys::ic0;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time::Duration;
+
+/// An opaque id of a timer, returned by [`set_timer`] and [`set_timer_interval`] and can be used
+/// to cancel the timer using [`clear_timer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimerId(u64);
+
+enum Task {
+    Once(Box<dyn FnOnce()>),
+    Repeated {
+        interval: u64,
+        // The function is taken out while it is being executed.
+        func: Option<Box<dyn FnMut()>>,
+    },
+}
+
+/// A task function that is taken out of the storage to be executed.
+enum Runnable {
+    Once(Box<dyn FnOnce()>),
+    Repeated(Box<dyn FnMut()>),
+}
+
+#[derive(Default)]
+struct Timers {
+    next_id: u64,
+    tasks: HashMap<TimerId, Task>,
+    /// The deadlines of the timers, the queue may contain timers that have been cleared, these
+    /// are skipped since they no longer exists in `tasks`.
+    queue: BinaryHeap<Reverse<(u64, TimerId)>>,
+}
+
+impl Timers {
+    fn insert(&mut self, deadline: u64, task: Task) -> TimerId {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+        self.tasks.insert(id, task);
+        self.queue.push(Reverse((deadline, id)));
+        id
+    }
+
+    /// Return the deadline of the next timer that should be executed.
+    fn next_deadline(&mut self) -> Option<u64> {
+        while let Some(Reverse((deadline, id))) = self.queue.peek() {
+            if self.tasks.contains_key(id) {
+                return Some(*deadline);
+            }
+
+            self.queue.pop();
+        }
+
+        None
+    }
+
+    /// Remove the timers that have expired by the given time from the queue and return them.
+    fn pop_expired(&mut self, now: u64) -> Vec<TimerId> {
+        let mut result = Vec::new();
+
+        while let Some(deadline) = self.next_deadline() {
+            if deadline > now {
+                break;
+            }
+
+            let Reverse((_, id)) = self.queue.pop().unwrap();
+            result.push(id);
+        }
+
+        result
+    }
+}
+
+/// Execute the given function once after the provided delay, the timer is only checked by the
+/// system after each message execution, so the delay is the minimum amount of time that will be
+/// passed before the execution.
+pub fn set_timer<F: FnOnce() + 'static>(delay: Duration, func: F) -> TimerId {
+    let deadline = time().saturating_add(delay.as_nanos() as u64);
+    let id = with_mut(|timers: &mut Timers| timers.insert(deadline, Task::Once(Box::new(func))));
+    update_global_timer();
+    id
+}
+
+/// Execute the given function repeatedly every `interval`, until the timer is cleared using
+/// [`clear_timer`].
+pub fn set_timer_interval<F: FnMut() + 'static>(interval: Duration, func: F) -> TimerId {
+    let interval = interval.as_nanos() as u64;
+    let deadline = time().saturating_add(interval);
+    let id = with_mut(|timers: &mut Timers| {
+        timers.insert(
+            deadline,
+            Task::Repeated {
+                interval,
+                func: Some(Box::new(func)),
+            },
+        )
+    });
+    update_global_timer();
+    id
+}
+
+/// Cancel the given timer, does nothing if the timer has already been executed or cleared.
+pub fn clear_timer(id: TimerId) {
+    with_mut(|timers: &mut Timers| {
+        timers.tasks.remove(&id);
+    });
+    update_global_timer();
+}
+
+/// Set the system's global timer to the deadline of the next timer.
+fn update_global_timer() {
+    let deadline = with_mut(|timers: &mut Timers| timers.next_deadline()).unwrap_or(0);
+    unsafe {
+        ic0::global_timer_set(deadline as i64);
+    }
+}
+
+/// Execute all of the expired timers, this is the body of the `canister_global_timer` entry
+/// point.
+fn run_timers() {
+    let now = time();
+    let expired = with_mut(|timers: &mut Timers| timers.pop_expired(now));
+
+    for id in expired {
+        // The timer's function might set or clear other timers, so we can not hold the borrow
+        // while executing the task.
+        let task = with_mut(|timers: &mut Timers| match timers.tasks.remove(&id)? {
+            Task::Once(func) => Some(Runnable::Once(func)),
+            Task::Repeated { interval, func } => {
+                // Keep the entry so the timer can still be cleared by its own function.
+                timers
+                    .tasks
+                    .insert(id, Task::Repeated { interval, func: None });
+                func.map(Runnable::Repeated)
+            }
+        });
+
+        match task {
+            Some(Runnable::Once(func)) => func(),
+            Some(Runnable::Repeated(mut func)) => {
+                func();
+
+                // Put the function back if the timer was not cleared during the execution.
+                with_mut(|timers: &mut Timers| {
+                    if let Some(Task::Repeated { interval, func: slot }) = timers.tasks.get_mut(&id)
+                    {
+                        let deadline = now.saturating_add(*interval);
+                        *slot = Some(func);
+                        timers.queue.push(Reverse((deadline, id)));
+                    }
+                });
+            }
+            None => {}
+        }
+    }
+
+    update_global_timer();
+}
+
+/// The global timer entry point which is exported by every ic-kit canister.
+#[cfg(target_family = "wasm")]
+#[doc(hidden)]
+#[export_name = "canister_global_timer"]
+fn _ic_kit_canister_global_timer() {
+    crate::setup_hooks();
+    run_timers();
+}
+
+/// The global timer entry point which is registered on every ic-kit canister in the runtime.
+#[cfg(not(target_family = "wasm"))]
+#[doc(hidden)]
+pub struct CanisterGlobalTimer;
+
+#[cfg(not(target_family = "wasm"))]
+impl ic_kit_runtime::CanisterMethod for CanisterGlobalTimer {
+    const EXPORT_NAME: &'static str = "canister_global_timer";
+
+    fn exported_method() {
+        run_timers();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ic::{reply, with};
+    use crate::rt::handle::CanisterHandle;
+    use crate::rt::types::Env;
+    use crate::rt::{Canister, Replica, TokioRuntimeBuilder};
+    use candid::Principal;
+
+    /// The number of times the timers of the canister have fired.
+    #[derive(Default)]
+    struct Fired(u32);
+
+    /// The id of the timer that is cleared by a later message or by itself.
+    #[derive(Default)]
+    struct Timer(Option<TimerId>);
+
+    fn fire() -> u32 {
+        with_mut(|fired: &mut Fired| {
+            fired.0 += 1;
+            fired.0
+        })
+    }
+
+    fn timer_id() -> TimerId {
+        with(|timer: &Timer| timer.0.unwrap())
+    }
+
+    fn save_timer_id(id: TimerId) {
+        with_mut(|timer: &mut Timer| timer.0 = Some(id));
+    }
+
+    /// A canister that only exports the global timer entry point.
+    fn canister() -> Canister {
+        Canister::new(Principal::from_slice(&[1])).with_method::<CanisterGlobalTimer>()
+    }
+
+    async fn fired(canister: &CanisterHandle<'_>) -> u32 {
+        canister
+            .custom(|| reply(with(|fired: &Fired| fired.0)), Env::default())
+            .await
+            .decode_one()
+            .unwrap()
+    }
+
+    #[test]
+    fn interval_fires_repeatedly() {
+        TokioRuntimeBuilder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let replica = Replica::default();
+                let canister = replica.add_canister(canister());
+
+                canister
+                    .custom(
+                        || {
+                            set_timer_interval(Duration::from_secs(10), || {
+                                fire();
+                            });
+                            reply(());
+                        },
+                        Env::default(),
+                    )
+                    .await
+                    .assert_ok();
+
+                replica.advance_time(Duration::from_secs(5));
+                assert_eq!(fired(&canister).await, 0);
+
+                for i in 1..=3 {
+                    replica.advance_time(Duration::from_secs(10));
+                    assert_eq!(fired(&canister).await, i);
+                }
+            });
+    }
+
+    #[test]
+    fn clear_timer_before_deadline() {
+        TokioRuntimeBuilder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let replica = Replica::default();
+                let canister = replica.add_canister(canister());
+
+                canister
+                    .custom(
+                        || {
+                            save_timer_id(set_timer(Duration::from_secs(10), || {
+                                fire();
+                            }));
+                            reply(());
+                        },
+                        Env::default(),
+                    )
+                    .await
+                    .assert_ok();
+
+                replica.advance_time(Duration::from_secs(5));
+
+                canister
+                    .custom(
+                        || {
+                            clear_timer(timer_id());
+                            reply(());
+                        },
+                        Env::default(),
+                    )
+                    .await
+                    .assert_ok();
+
+                replica.advance_time(Duration::from_secs(10));
+                assert_eq!(fired(&canister).await, 0);
+            });
+    }
+
+    #[test]
+    fn interval_clears_itself() {
+        TokioRuntimeBuilder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let replica = Replica::default();
+                let canister = replica.add_canister(canister());
+
+                canister
+                    .custom(
+                        || {
+                            let id = set_timer_interval(Duration::from_secs(10), || {
+                                // The function is taken out of its slot while it runs, so the
+                                // timer is cleared without putting it back.
+                                if fire() == 2 {
+                                    clear_timer(timer_id());
+                                }
+                            });
+                            save_timer_id(id);
+                            reply(());
+                        },
+                        Env::default(),
+                    )
+                    .await
+                    .assert_ok();
+
+                for _ in 0..4 {
+                    replica.advance_time(Duration::from_secs(10));
+                }
+
+                assert_eq!(fired(&canister).await, 2);
+            });
+    }
+}
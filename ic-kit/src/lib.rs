@@ -2,6 +2,8 @@ mod canister;
 mod futures;
 mod setup;
 mod storage;
+#[cfg(test)]
+mod test_utils;
 
 /// System APIs for the Internet Computer.
 pub mod ic;
@@ -18,7 +20,7 @@ pub use ic_kit_macros as macros;
 pub use setup::setup_hooks;
 
 // The KitCanister derive macro.
-pub use canister::KitCanister;
+pub use canister::{InstallConfig, InstallMode, KitCanister};
 pub use ic_kit_macros::KitCanister;
 
 /// The IC-kit runtime, which can be used for testing the canister in non-wasm environments.
@@ -1,7 +1,10 @@
 /// Provides utility methods to deal with stable storage on your canister.
 // This file is copied from ic_cdk, but changed so that it works with IC-Kit.
-use crate::ic::{stable_bytes, stable_grow, stable_read, stable_size, stable_write, StableSize};
+use crate::ic::{
+    stable_bytes, stable_grow, stable_read, stable_size, stable_write, write_prefixed, StableSize,
+};
 use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use std::cmp::Ordering;
 use std::io;
 
 pub use ic_kit_sys::types::StableMemoryError;
@@ -113,7 +116,7 @@ impl io::Read for StableReader {
 /// Store the given data to the stable storage.
 #[deprecated(
     since = "0.5.0",
-    note = "This is a non-performant legacy from IC-CDK for us to deal with."
+    note = "This is a non-performant legacy from IC-CDK, use ic::stable_save instead."
 )]
 pub fn stable_store<T>(data: T) -> Result<(), candid::Error>
 where
@@ -126,7 +129,7 @@ where
 /// is returned.
 #[deprecated(
     since = "0.5.0",
-    note = "This is a non-performant legacy from IC-CDK for us to deal with."
+    note = "This is a non-performant legacy from IC-CDK, use ic::stable_restore instead."
 )]
 pub fn stable_restore<T>() -> Result<T, String>
 where
@@ -138,3 +141,175 @@ where
     let res = ArgumentDecoder::decode(&mut de).map_err(|e| format!("{:?}", e))?;
     Ok(res)
 }
+
+/// A value that is saved to the stable storage along with the version of its schema, so the
+/// `post_upgrade` hook can tell which layout it is reading and migrate the older layouts forward
+/// instead of decoding them as the current one.
+///
+/// The stable memory is laid out like [`crate::ic::stable_save`] with the version in between:
+///
+/// - The length of the candid encoded value as a little-endian `u64` at offset 0.
+/// - The version as a little-endian `u32` at offset 8.
+/// - The candid encoded value starting at offset 12.
+///
+/// ```ignore
+/// #[pre_upgrade]
+/// fn pre_upgrade() {
+///     let data = ic::with(|s: &State| (s.count, s.name.clone()));
+///     Versioned::new(2, data).save().unwrap();
+/// }
+///
+/// #[post_upgrade]
+/// fn post_upgrade() {
+///     let (count, name): (u64, String) =
+///         Versioned::restore_with(2, |version, bytes| match version {
+///             1 => {
+///                 let (count,): (u64,) = candid::decode_args(bytes).map_err(|e| e.to_string())?;
+///                 Ok((count, String::new()))
+///             }
+///             _ => Err(format!("Unknown version {}.", version)),
+///         })
+///         .unwrap();
+/// }
+/// ```
+pub struct Versioned<T> {
+    version: u32,
+    data: T,
+}
+
+impl<T> Versioned<T> {
+    /// Wrap the given candid tuple with the version of its schema.
+    pub fn new(version: u32, data: T) -> Self {
+        Self { version, data }
+    }
+
+    /// The version of the schema.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Return the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+}
+
+impl<T: ArgumentEncoder> Versioned<T> {
+    /// Encode the value and save it to the beginning of the stable memory along with its version,
+    /// growing the memory if needed.
+    pub fn save(self) -> Result<(), String> {
+        let bytes = candid::encode_args(self.data).map_err(|e| format!("{:?}", e))?;
+        write_prefixed(&self.version.to_le_bytes(), &bytes)
+    }
+}
+
+impl<T> Versioned<T>
+where
+    T: for<'de> ArgumentDecoder<'de>,
+{
+    /// Restore a value that was saved with the given version, an error is returned if the stored
+    /// value has any other version.
+    pub fn restore(version: u32) -> Result<T, String> {
+        Self::restore_with(version, |stored, _| {
+            Err(format!("No migration from version {}.", stored))
+        })
+    }
+
+    /// Restore the value saved using [`Versioned::save`]. If the stored version is older than the
+    /// given version, the `migrate` function is called with the stored version and the candid
+    /// encoded value, and should return the value in the current schema.
+    ///
+    /// An error is returned if the stored version is newer than the current version, since the
+    /// canister is being downgraded.
+    pub fn restore_with<F>(version: u32, migrate: F) -> Result<T, String>
+    where
+        F: FnOnce(u32, &[u8]) -> Result<T, String>,
+    {
+        let size = (stable_size() as u64) << 16;
+
+        if size < 12 {
+            return Err("The stable memory is empty.".into());
+        }
+
+        let mut header = [0u8; 12];
+        stable_read(0, &mut header);
+
+        let mut len = [0u8; 8];
+        len.copy_from_slice(&header[..8]);
+        let len = u64::from_le_bytes(len);
+
+        let mut stored = [0u8; 4];
+        stored.copy_from_slice(&header[8..]);
+        let stored = u32::from_le_bytes(stored);
+
+        if len > size - 12 {
+            return Err(format!(
+                "The stored value of {} bytes does not fit in the stable memory.",
+                len
+            ));
+        }
+
+        let mut bytes = vec![0u8; len as usize];
+        stable_read(12, &mut bytes);
+
+        match stored.cmp(&version) {
+            Ordering::Equal => candid::decode_args(&bytes).map_err(|e| format!("{:?}", e)),
+            Ordering::Less => migrate(stored, &bytes),
+            Ordering::Greater => Err(format!(
+                "The stored version {} is newer than the current version {}.",
+                stored, version
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rt::types::Env;
+    use crate::rt::{Canister, Replica, TokioRuntimeBuilder};
+    use candid::Principal;
+
+    fn migrate(version: u32, bytes: &[u8]) -> Result<(u64, String), String> {
+        match version {
+            1 => {
+                let (count,): (u64,) = candid::decode_args(bytes).map_err(|e| e.to_string())?;
+                Ok((count, "migrated".to_string()))
+            }
+            _ => Err(format!("Unknown version {}.", version)),
+        }
+    }
+
+    #[test]
+    fn versioned_migration() {
+        TokioRuntimeBuilder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let replica = Replica::default();
+                let canister = replica.add_canister(Canister::new(Principal::from_slice(&[1])));
+
+                canister
+                    .custom(
+                        || {
+                            Versioned::new(1, (42u64,)).save().unwrap();
+
+                            let data: (u64, String) = Versioned::restore_with(2, migrate).unwrap();
+                            assert_eq!(data, (42, "migrated".to_string()));
+                            Versioned::new(2, data).save().unwrap();
+
+                            let data: (u64, String) = Versioned::restore_with(2, migrate).unwrap();
+                            assert_eq!(data, (42, "migrated".to_string()));
+
+                            assert!(Versioned::<(u64,)>::restore(1).is_err());
+                            assert!(Versioned::<(u64, String)>::restore(3).is_err());
+
+                            unsafe { ic_kit_sys::ic0::msg_reply() };
+                        },
+                        Env::default(),
+                    )
+                    .await
+                    .assert_ok();
+            });
+    }
+}
@@ -0,0 +1,14 @@
+//! Helpers that are shared by the tests of this crate.
+
+use std::task::{RawWaker, RawWakerVTable, Waker};
+
+/// Return a waker that does nothing when it is woken, used to poll the futures by hand.
+pub(crate) fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+}
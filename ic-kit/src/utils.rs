@@ -1,4 +1,13 @@
 use ic_kit_sys::ic0;
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+thread_local! {
+    /// Set once the current call is replied to or rejected using [`reply`] or [`reject`].
+    static REPLIED: Cell<bool> = Cell::new(false);
+}
 
 /// Return the size of the raw argument to this entry point.
 pub fn arg_data_size() -> usize {
@@ -28,11 +37,55 @@ pub fn reply(buf: &[u8]) {
         }
         ic0::msg_reply()
     }
+
+    REPLIED.with(|r| r.set(true));
 }
 
 /// Reject the current call.
 pub fn reject(message: &str) {
     unsafe { ic0::msg_reject(message.as_ptr() as isize, message.len() as isize) }
+    REPLIED.with(|r| r.set(true));
+}
+
+/// Returns true if the current call is already replied to or rejected using [`reply`],
+/// [`reject`], or any of the methods built on them such as [`crate::ic::reply`]. The methods
+/// generated by the entry point macros use this to not reply twice.
+pub fn is_replied() -> bool {
+    REPLIED.with(|r| r.get())
+}
+
+/// Mark the current call as not replied, used by the generated entry points.
+#[doc(hidden)]
+pub fn reset_replied() {
+    REPLIED.with(|r| r.set(false));
+}
+
+/// Wrap the future of an async entry point so it keeps its own reply state, the calls that are
+/// processed while the future is waiting for an inter-canister call have their own state.
+#[doc(hidden)]
+pub fn reply_scope<F: Future>(future: F) -> ReplyScope<F> {
+    ReplyScope {
+        future: Box::pin(future),
+        replied: false,
+    }
+}
+
+/// The future returned by [`reply_scope`].
+#[doc(hidden)]
+pub struct ReplyScope<F: Future> {
+    future: Pin<Box<F>>,
+    replied: bool,
+}
+
+impl<F: Future> Future for ReplyScope<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let outer = REPLIED.with(|r| r.replace(self.replied));
+        let result = self.future.as_mut().poll(cx);
+        self.replied = REPLIED.with(|r| r.replace(outer));
+        result
+    }
 }
 
 /// Accept the incoming message.
@@ -61,3 +114,50 @@ pub fn method_name() -> String {
 pub fn performance_counter(counter_type: u32) -> u64 {
     unsafe { ic0::performance_counter(counter_type as i32) as u64 }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::noop_waker;
+
+    /// A future that is pending for the first poll.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+
+            self.0 = true;
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn reply_scope_keeps_its_own_state() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        reset_replied();
+
+        let mut scope = reply_scope(async {
+            assert!(!is_replied());
+            REPLIED.with(|r| r.set(true));
+            YieldOnce(false).await;
+            assert!(is_replied());
+        });
+
+        assert!(Pin::new(&mut scope).poll(&mut cx).is_pending());
+        assert!(!is_replied());
+
+        // Other calls run and reply while the scope is waiting.
+        REPLIED.with(|r| r.set(true));
+        reset_replied();
+
+        assert!(Pin::new(&mut scope).poll(&mut cx).is_ready());
+        assert!(!is_replied());
+    }
+}